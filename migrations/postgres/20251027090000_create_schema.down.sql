@@ -0,0 +1,4 @@
+DROP TABLE idempotency_keys;
+DROP TABLE book_revisions;
+DROP TABLE book_redirects;
+DROP TABLE books;
@@ -0,0 +1,48 @@
+-- Esquema equivalente a las migraciones de SQLite hasta la fecha
+CREATE TABLE books (
+    id             TEXT PRIMARY KEY,
+    title          TEXT NOT NULL,
+    author         TEXT NOT NULL,
+    published_year INTEGER,
+    isbn           TEXT,
+    created_at     TIMESTAMPTZ NOT NULL,
+    version        BIGINT NOT NULL DEFAULT 1,
+    updated_at     TIMESTAMPTZ NOT NULL,
+    updated_by     TEXT,
+    -- NULL = libro activo; con valor, el libro está en la papelera
+    deleted_at     TIMESTAMPTZ
+);
+
+CREATE INDEX idx_books_created_at ON books (created_at);
+CREATE INDEX idx_books_updated_at ON books (updated_at);
+
+-- Ids de libros fusionados → libro que los absorbió
+CREATE TABLE book_redirects (
+    old_id     TEXT PRIMARY KEY,
+    new_id     TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+-- Historial de cambios de cada libro; `before`/`after` son el libro en JSON
+CREATE TABLE book_revisions (
+    id          BIGSERIAL PRIMARY KEY,
+    book_id     TEXT NOT NULL,
+    action      TEXT NOT NULL,
+    actor       TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    before      JSONB,
+    after       JSONB
+);
+
+CREATE INDEX idx_book_revisions_book_id ON book_revisions (book_id, id);
+
+CREATE TABLE idempotency_keys (
+    scope        TEXT NOT NULL,
+    key          TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    status       INTEGER,
+    content_type TEXT,
+    body         BYTEA,
+    created_at   TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,7 @@
+ALTER TABLE idempotency_keys ADD COLUMN content_type TEXT;
+UPDATE idempotency_keys
+   SET content_type = (
+       SELECT pair->>1 FROM json_array_elements(headers::JSON) AS pair
+        WHERE pair->>0 = 'content-type'
+   );
+ALTER TABLE idempotency_keys DROP COLUMN headers;
@@ -0,0 +1,5 @@
+DROP INDEX idx_books_match_key;
+DROP INDEX idx_books_isbn_key;
+
+ALTER TABLE books DROP COLUMN match_key;
+ALTER TABLE books DROP COLUMN isbn_key;
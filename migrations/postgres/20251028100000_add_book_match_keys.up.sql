@@ -0,0 +1,8 @@
+-- Claves para buscar candidatos a duplicado con un índice. Las calcula la
+-- aplicación (`domain::duplicates`): las filas existentes se rellenan al
+-- terminar de migrar.
+ALTER TABLE books ADD COLUMN isbn_key TEXT;
+ALTER TABLE books ADD COLUMN match_key TEXT;
+
+CREATE INDEX idx_books_isbn_key ON books (isbn_key);
+CREATE INDEX idx_books_match_key ON books (match_key);
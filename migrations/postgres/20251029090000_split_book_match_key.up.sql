@@ -0,0 +1,11 @@
+-- Dos claves de bloque en lugar de `match_key`: un duplicado con una errata
+-- en el apellido o en el título sigue compartiendo la otra. Las filas
+-- existentes las rellena la aplicación al arrancar.
+DROP INDEX idx_books_match_key;
+ALTER TABLE books DROP COLUMN match_key;
+
+ALTER TABLE books ADD COLUMN author_key TEXT;
+ALTER TABLE books ADD COLUMN title_key TEXT;
+
+CREATE INDEX idx_books_author_key ON books (author_key);
+CREATE INDEX idx_books_title_key ON books (title_key);
@@ -0,0 +1,2 @@
+DROP TABLE book_redirects;
+ALTER TABLE books DROP COLUMN isbn;
@@ -0,0 +1,8 @@
+ALTER TABLE books ADD COLUMN isbn TEXT;
+
+-- Ids de libros fusionados → libro que los absorbió
+CREATE TABLE book_redirects (
+    old_id     TEXT PRIMARY KEY NOT NULL,
+    new_id     TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
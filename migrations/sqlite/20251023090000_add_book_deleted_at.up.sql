@@ -0,0 +1,2 @@
+-- NULL = libro activo; con valor, el libro está en la papelera
+ALTER TABLE books ADD COLUMN deleted_at TEXT;
@@ -0,0 +1,12 @@
+-- Historial de cambios de cada libro; `before`/`after` son el libro en JSON
+CREATE TABLE book_revisions (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    book_id     TEXT NOT NULL,
+    action      TEXT NOT NULL,
+    actor       TEXT NOT NULL,
+    recorded_at TEXT NOT NULL,
+    before      TEXT,
+    after       TEXT
+);
+
+CREATE INDEX idx_book_revisions_book_id ON book_revisions (book_id, id);
@@ -0,0 +1,3 @@
+DROP INDEX idx_books_updated_at;
+ALTER TABLE books DROP COLUMN updated_by;
+ALTER TABLE books DROP COLUMN updated_at;
@@ -0,0 +1,7 @@
+ALTER TABLE books ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+ALTER TABLE books ADD COLUMN updated_by TEXT;
+
+-- Los libros existentes no se han modificado desde su alta
+UPDATE books SET updated_at = created_at;
+
+CREATE INDEX idx_books_updated_at ON books (updated_at);
@@ -0,0 +1,2 @@
+-- El formato normalizado sigue siendo RFC 3339: no hay nada que deshacer
+DROP INDEX idx_books_created_at;
@@ -0,0 +1,13 @@
+-- Todas las marcas de tiempo pasan a RFC 3339 en UTC con milisegundos
+-- (`2025-01-31T09:30:00.123Z`): mismo ancho, así que ordenar y comparar
+-- como texto equivale a hacerlo cronológicamente.
+UPDATE books
+   SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ', created_at),
+       updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', updated_at),
+       deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ', deleted_at);
+
+UPDATE book_redirects SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ', created_at);
+
+UPDATE book_revisions SET recorded_at = strftime('%Y-%m-%dT%H:%M:%fZ', recorded_at);
+
+CREATE INDEX idx_books_created_at ON books (created_at);
@@ -0,0 +1,7 @@
+ALTER TABLE idempotency_keys ADD COLUMN content_type TEXT;
+UPDATE idempotency_keys
+   SET content_type = (
+       SELECT json_extract(value, '$[1]') FROM json_each(headers)
+        WHERE json_extract(value, '$[0]') = 'content-type'
+   );
+ALTER TABLE idempotency_keys DROP COLUMN headers;
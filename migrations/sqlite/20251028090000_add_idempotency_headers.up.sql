@@ -0,0 +1,7 @@
+-- Las respuestas guardadas conservan las cabeceras que se repiten, como
+-- pares JSON `[nombre, valor]`; `content_type` pasa a ser una de ellas
+ALTER TABLE idempotency_keys ADD COLUMN headers TEXT;
+UPDATE idempotency_keys
+   SET headers = json_array(json_array('content-type', content_type))
+ WHERE content_type IS NOT NULL;
+ALTER TABLE idempotency_keys DROP COLUMN content_type;
@@ -0,0 +1,8 @@
+DROP INDEX idx_books_title_key;
+DROP INDEX idx_books_author_key;
+
+ALTER TABLE books DROP COLUMN title_key;
+ALTER TABLE books DROP COLUMN author_key;
+
+ALTER TABLE books ADD COLUMN match_key TEXT;
+CREATE INDEX idx_books_match_key ON books (match_key);
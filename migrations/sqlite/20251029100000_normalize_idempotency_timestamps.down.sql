@@ -0,0 +1,2 @@
+-- El formato normalizado sigue siendo RFC 3339: no hay nada que deshacer
+SELECT 1;
@@ -0,0 +1,4 @@
+-- `idempotency_keys` se quedó fuera de 20251026090000: sus fechas iban con
+-- un número variable de decimales y caducar o reclamar claves comparándolas
+-- como texto fallaba. Mismo formato que el resto de tablas.
+UPDATE idempotency_keys SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ', created_at);
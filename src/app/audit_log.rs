@@ -0,0 +1,97 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    app::book_repository::RepoError,
+    domain::book::{self, Book},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionAction {
+    Create,
+    Update,
+    Delete,
+    /// Vuelta al estado de una revisión anterior
+    Revert,
+    /// El libro absorbió a otro en una fusión; el absorbido queda con un `Delete`
+    Merge,
+    /// Salida de la papelera
+    Restore,
+    /// Borrado definitivo desde la papelera
+    Purge,
+}
+
+impl RevisionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionAction::Create => "create",
+            RevisionAction::Update => "update",
+            RevisionAction::Delete => "delete",
+            RevisionAction::Revert => "revert",
+            RevisionAction::Merge => "merge",
+            RevisionAction::Restore => "restore",
+            RevisionAction::Purge => "purge",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "create" => Some(RevisionAction::Create),
+            "update" => Some(RevisionAction::Update),
+            "delete" => Some(RevisionAction::Delete),
+            "revert" => Some(RevisionAction::Revert),
+            "merge" => Some(RevisionAction::Merge),
+            "restore" => Some(RevisionAction::Restore),
+            "purge" => Some(RevisionAction::Purge),
+            _ => None,
+        }
+    }
+}
+
+/// Un cambio de un libro, con el libro antes y después
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BookRevision {
+    /// Creciente por libro; se asigna al guardar
+    pub id: i64,
+    pub book_id: String,
+    pub action: RevisionAction,
+    /// `sub` del JWT que hizo el cambio
+    pub actor: String,
+    pub recorded_at: DateTime<Utc>,
+    /// `null` en las altas
+    pub before: Option<Book>,
+    /// `null` en los borrados y purgas
+    pub after: Option<Book>,
+}
+
+impl BookRevision {
+    pub fn new(action: RevisionAction, actor: &str, before: Option<Book>, after: Option<Book>) -> Self {
+        let book_id = after
+            .as_ref()
+            .or(before.as_ref())
+            .map(|b| b.id.clone())
+            .expect("una revisión necesita el libro antes o después");
+        Self {
+            id: 0,
+            book_id,
+            action,
+            actor: actor.to_string(),
+            recorded_at: book::now(),
+            before,
+            after,
+        }
+    }
+}
+
+/// Lectura del registro de auditoría. Las revisiones las escribe
+/// `BookRepository` en la misma transacción que el cambio: no hay escritura
+/// confirmada sin su revisión ni revisión de una escritura deshecha.
+#[async_trait]
+pub trait AuditLog: Send + Sync {
+    /// Revisiones de un libro, de la más antigua a la más reciente
+    async fn history(&self, book_id: &str) -> Result<Vec<BookRevision>, RepoError>;
+    async fn get(&self, book_id: &str, revision_id: i64) -> Result<Option<BookRevision>, RepoError>;
+}
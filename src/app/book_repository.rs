@@ -1,16 +1,133 @@
 use crate::domain::book::Book;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use thiserror::Error;
 
+/// Fallos del repositorio ya clasificados, independientes del motor de BD
+#[derive(Error, Debug)]
+pub enum RepoError {
+    #[error("Book {0} not found")]
+    NotFound(String),
+
+    /// Clave primaria o restricción UNIQUE duplicada
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// Referencia a una fila inexistente (FOREIGN KEY)
+    #[error("Foreign key violation: {0}")]
+    ForeignKey(String),
+
+    /// NOT NULL, CHECK u otra restricción del esquema
+    #[error("Constraint violation: {0}")]
+    Constraint(String),
+
+    /// La versión esperada ya no es la actual (otro cliente escribió antes)
+    #[error("Book {0} was modified concurrently")]
+    VersionMismatch(String),
+
+    /// BD ocupada/bloqueada o pool agotado: reintentable
+    #[error("Database unavailable: {0}")]
+    Unavailable(String),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Criterios de `BookRepository::search`; los ausentes no filtran. El
+/// resultado va ordenado por `created_at` y, a igualdad, por `id`.
+#[derive(Debug, Clone, Default)]
+pub struct BookFilter {
+    /// Coincidencia parcial como `LIKE '%…%'`: sin distinguir mayúsculas
+    /// ASCII, con `%` y `_` como comodines
+    pub title: Option<String>,
+    /// Igual que `title`
+    pub author: Option<String>,
+    /// Libros escritos en este instante o después
+    pub updated_since: Option<DateTime<Utc>>,
+    /// Libros creados en este instante o después
+    pub created_from: Option<DateTime<Utc>>,
+    /// Libros creados antes de este instante (exclusivo)
+    pub created_to: Option<DateTime<Utc>>,
+}
+
+/// Escritura individual dentro de `BookRepository::write_batch`
+#[derive(Debug, Clone)]
+pub enum BookWrite {
+    Create(Book),
+    /// Mismas reglas de versión que `BookRepository::update`
+    Update(Book),
+    /// Un libro inexistente es `RepoError::NotFound`; `actor` firma la revisión
+    Delete { id: String, expected_version: Option<i64>, actor: String },
+}
+
+#[derive(Debug)]
+pub struct BatchResult {
+    /// Un resultado por escritura ejecutada (`None` para los borrados). En
+    /// modo atómico se detiene en el primer error.
+    pub results: Vec<Result<Option<Book>, RepoError>>,
+    /// `false` si la transacción se deshizo
+    pub committed: bool,
+}
+
+/// Totales del catálogo, sin cargar los libros
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BookCounts {
+    pub active: i64,
+    /// En la papelera
+    pub trashed: i64,
+}
+
+/// Cada escritura guarda su `BookRevision` en la misma transacción. El autor
+/// es el `updated_by` del libro escrito o, donde no se recibe un libro, el
+/// parámetro `actor`.
 #[async_trait]
 pub trait BookRepository: Send + Sync {
-    async fn get_all(&self) -> Result<Vec<Book>, anyhow::Error>;
-    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, anyhow::Error>;
-    async fn create(&self, book: Book) -> Result<Book, anyhow::Error>;
-    async fn update(&self, book: Book) -> Result<Book, anyhow::Error>;
-    async fn delete(&self, id: &str) -> Result<(), anyhow::Error>;
-    async fn search(
-        &self,
-        title: Option<&str>,
-        author: Option<&str>,
-    ) -> Result<Vec<Book>, anyhow::Error>;
-}
\ No newline at end of file
+    async fn get_all(&self) -> Result<Vec<Book>, RepoError>;
+    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, RepoError>;
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Book>, RepoError>;
+    /// Hasta `limit` libros activos ordenados por id, a partir del siguiente
+    /// a `after`: para recorrer el catálogo sin cargarlo entero
+    async fn list_page(&self, after: Option<&str>, limit: i64) -> Result<Vec<Book>, RepoError>;
+    /// `create` y `update` fijan `updated_at`; `updated_by` lo pone el llamador
+    async fn create(&self, book: Book) -> Result<Book, RepoError>;
+    /// Guarda `book` solo si su `version` sigue siendo la almacenada y
+    /// devuelve el libro con la versión incrementada. `RepoError::NotFound`
+    /// si no existe, `RepoError::VersionMismatch` si la versión no coincide.
+    async fn update(&self, book: Book) -> Result<Book, RepoError>;
+    /// Como `update`, pero la revisión queda como `RevisionAction::Revert`
+    async fn revert(&self, book: Book) -> Result<Book, RepoError>;
+    /// Mueve el libro a la papelera; los libros en la papelera no aparecen
+    /// en el resto de lecturas. Devuelve `false` si no existía un libro
+    /// activo con ese id. Con `expected_version`, borra solo si coincide
+    /// con la almacenada.
+    async fn delete(&self, id: &str, expected_version: Option<i64>, actor: &str) -> Result<bool, RepoError>;
+    /// Ejecuta `writes` en una transacción. Con `atomic`, el primer error
+    /// deshace todo; si no, cada escritura se confirma o descarta por separado.
+    async fn write_batch(&self, writes: Vec<BookWrite>, atomic: bool) -> Result<BatchResult, RepoError>;
+    /// Fusiona `source_id` en `target` en una sola transacción: guarda
+    /// `target` con las reglas de `update`, borra el origen y redirige su id
+    /// (y los que ya apuntaban a él) al destino. `RepoError::NotFound` si
+    /// el origen no existe. El destino queda con una revisión `Merge` y el
+    /// origen con una `Delete`.
+    async fn merge(&self, source_id: &str, target: Book) -> Result<Book, RepoError>;
+    /// Id del libro que absorbió a `id` en una fusión, si lo hubo
+    async fn resolve_redirect(&self, id: &str) -> Result<Option<String>, RepoError>;
+    /// Libros en la papelera, los más recientes primero. Con
+    /// `deleted_since`, solo los borrados en ese instante o después: junto a
+    /// `BookFilter::updated_since`, lo que necesita una sincronización
+    /// incremental
+    async fn list_trash(&self, deleted_since: Option<DateTime<Utc>>) -> Result<Vec<Book>, RepoError>;
+    /// Saca un libro de la papelera; `None` si no estaba en ella
+    async fn restore(&self, id: &str, actor: &str) -> Result<Option<Book>, RepoError>;
+    /// Borra definitivamente un libro de la papelera; `false` si no estaba en ella
+    async fn purge(&self, id: &str, actor: &str) -> Result<bool, RepoError>;
+    async fn search(&self, filter: &BookFilter) -> Result<Vec<Book>, RepoError>;
+    /// Libros activos, salvo el propio `book`, que comparten con él ISBN
+    /// normalizado, `author_key` o `title_key` (`domain::duplicates::share_key`):
+    /// los candidatos que hay que pasar a `find_duplicates`
+    async fn duplicate_candidates(&self, book: &Book) -> Result<Vec<Book>, RepoError>;
+    /// Libros activos que comparten alguna de esas claves con algún otro, en el
+    /// orden de `search`: los candidatos que hay que pasar a `duplicate_pairs`
+    async fn all_duplicate_candidates(&self) -> Result<Vec<Book>, RepoError>;
+    async fn count(&self) -> Result<BookCounts, RepoError>;
+}
@@ -0,0 +1,33 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::app::book_repository::RepoError;
+
+/// Conexiones del pool en este momento
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolStats {
+    /// Abiertas, en uso o no
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageStats {
+    /// Motor de BD: `sqlite`, `postgres` o `memory`
+    pub backend: String,
+    /// Tamaño de la BD en disco; `None` sin BD
+    pub size_bytes: Option<i64>,
+    /// `None` sin BD
+    pub pool: Option<PoolStats>,
+}
+
+/// Estado del almacenamiento para las sondas y `/status`
+#[async_trait]
+pub trait StorageHealth: Send + Sync {
+    /// Consulta trivial: falla si no se puede obtener una conexión
+    async fn ping(&self) -> Result<(), RepoError>;
+
+    async fn stats(&self) -> Result<StorageStats, RepoError>;
+}
@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::app::book_repository::RepoError;
+
+/// Respuesta guardada para repetirla ante un reintento
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: u16,
+    /// Solo las cabeceras que se repiten (ver `middleware::idempotency::REPLAYED_HEADERS`), en minúsculas
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum Reservation {
+    /// La clave es nueva: el llamador debe procesar la petición
+    Reserved,
+    /// La clave ya existía. `response` es `None` si la petición original
+    /// todavía está en curso.
+    Existing {
+        request_hash: String,
+        response: Option<StoredResponse>,
+    },
+}
+
+/// Almacén de `Idempotency-Key`. `scope` separa claves de distintos usuarios
+/// y rutas.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Reserva `key` o devuelve lo guardado. Las claves creadas antes de
+    /// `not_before` se consideran caducadas y se descartan; las
+    /// reservas sin respuesta anteriores a `lease_before` son de una petición
+    /// que no terminó (proceso caído, `release` fallido) y se pueden reclamar.
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        not_before: DateTime<Utc>,
+        lease_before: DateTime<Utc>,
+    ) -> Result<Reservation, RepoError>;
+    async fn complete(&self, scope: &str, key: &str, response: &StoredResponse) -> Result<(), RepoError>;
+    /// Libera una reserva para que el cliente pueda reintentar
+    async fn release(&self, scope: &str, key: &str) -> Result<(), RepoError>;
+}
+
+/// Las cabeceras se guardan como una lista JSON de pares `[nombre, valor]`
+pub fn encode_headers(headers: &[(String, String)]) -> Result<String, RepoError> {
+    serde_json::to_string(headers).map_err(|e| RepoError::Other(e.into()))
+}
+
+pub fn decode_headers(stored: Option<String>) -> Result<Vec<(String, String)>, RepoError> {
+    stored.map_or(Ok(Vec::new()), |json| serde_json::from_str(&json).map_err(|e| RepoError::Other(e.into())))
+}
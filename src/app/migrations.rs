@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::app::book_repository::RepoError;
+
+/// Migración registrada en la BD
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    /// `false` si falló a medias y la BD quedó en un estado intermedio
+    pub success: bool,
+    pub execution_ms: i64,
+}
+
+/// Migración incluida en el binario que la BD todavía no tiene
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct MigrationStatus {
+    /// Motor de BD: `sqlite`, `postgres` o `memory`
+    pub backend: String,
+    pub applied: Vec<AppliedMigration>,
+    pub pending: Vec<PendingMigration>,
+    /// Versiones aplicadas que este binario no conoce: la BD va por delante
+    pub unknown: Vec<i64>,
+}
+
+impl MigrationStatus {
+    /// El esquema es exactamente el que espera este binario
+    pub fn is_current(&self) -> bool {
+        self.pending.is_empty() && self.unknown.is_empty() && self.applied.iter().all(|m| m.success)
+    }
+}
+
+/// Estado del esquema frente a las migraciones embebidas en el binario
+#[async_trait]
+pub trait SchemaMigrations: Send + Sync {
+    async fn status(&self) -> Result<MigrationStatus, RepoError>;
+}
@@ -1,38 +1,219 @@
 pub mod book_repository;
+pub mod idempotency_store;
+pub mod audit_log;
+pub mod migrations;
+pub mod health;
 
 use axum::{
-    Router,
-    routing::{get, post, put, delete},
-    middleware::from_fn,
+    Extension, Router,
+    extract::FromRef,
+    routing::{delete, get, post, put},
+    middleware::{from_fn, from_fn_with_state},
+};
+use std::{sync::Arc, time::Instant};
+
+use self::{
+    audit_log::AuditLog, book_repository::BookRepository, idempotency_store::IdempotencyStore,
+    health::StorageHealth, migrations::SchemaMigrations,
 };
-use std::sync::Arc;
 
 use crate::{
+    config::Config,
     handlers::{
         book_handler::{
             get_books, get_book, post_book,
-            put_book, delete_book, search_books,
+            put_book, patch_book, delete_book, search_books,
         },
         auth_handler::login,
+        batch_handler::batch_books,
+        duplicates_handler::{get_duplicates, merge_book},
+        history_handler::{book_history, revert_book},
+        trash_handler::{list_trash, purge_book, restore_book},
+        admin_handler::migrations,
+        health_handler::{healthz, readyz, status, StartedAt},
+        metrics_handler::{self, UnmeteredBooks},
+        docs_handler::{docs, openapi_json},
+        graphql_handler::{build_schema, graphiql, graphql, LibrarySchema},
     },
-    infra::sqlite_book_repository::SqliteBookRepository,
-    middleware::auth::auth,
+    middleware::{
+        access_log::access_log, auth::{auth, require_admin}, cors::cors, idempotency::idempotency, metrics::track_metrics,
+        rate_limit::{limit_by_ip, limit_by_subject, limit_by_subject_or_ip, RateLimiter}, request_id::request_id, trace::trace_request,
+    },
+    metrics::{metered_book_repository::MeteredBookRepository, Metrics},
 };
 
-/// Construye el Router con rutas públicas y protegidas
-pub fn build_app(repo: Arc<SqliteBookRepository>) -> Router {
+/// Almacenes sobre los que se monta la app; comparten base de datos (ver
+/// `infra::sqlite_backend` y compañía)
+pub struct Backend<R> {
+    pub books: Arc<R>,
+    pub idempotency_store: Arc<dyn IdempotencyStore>,
+    pub audit_log: Arc<dyn AuditLog>,
+    pub migrations: Arc<dyn SchemaMigrations>,
+    pub health: Arc<dyn StorageHealth>,
+}
+
+/// Estado compartido por los handlers; cada uno extrae la parte que usa
+/// (`State<Arc<R>>`, `State<Arc<Config>>`...)
+pub struct AppState<R> {
+    pub repo: Arc<R>,
+    pub config: Arc<Config>,
+    pub schema: LibrarySchema,
+}
+
+// A mano: `derive(Clone)` exigiría `R: Clone`
+impl<R> Clone for AppState<R> {
+    fn clone(&self) -> Self {
+        Self { repo: self.repo.clone(), config: self.config.clone(), schema: self.schema.clone() }
+    }
+}
+
+impl<R: BookRepository> FromRef<AppState<R>> for Arc<R> {
+    fn from_ref(state: &AppState<R>) -> Self {
+        state.repo.clone()
+    }
+}
+
+impl<R> FromRef<AppState<R>> for Arc<Config> {
+    fn from_ref(state: &AppState<R>) -> Self {
+        state.config.clone()
+    }
+}
+
+impl<R> FromRef<AppState<R>> for LibrarySchema {
+    fn from_ref(state: &AppState<R>) -> Self {
+        state.schema.clone()
+    }
+}
+
+impl<R: BookRepository> Backend<R> {
+    /// El mismo backend con el repositorio medido y trazado en `metrics`
+    pub fn metered(self, metrics: Arc<Metrics>) -> Backend<MeteredBookRepository<R>> {
+        Backend {
+            books: Arc::new(MeteredBookRepository::new(self.books, metrics)),
+            idempotency_store: self.idempotency_store,
+            audit_log: self.audit_log,
+            migrations: self.migrations,
+            health: self.health,
+        }
+    }
+}
+
+/// Construye el Router con rutas públicas y protegidas. Las llamadas al
+/// repositorio quedan medidas para `/metrics`.
+pub fn build_app<R: BookRepository + 'static>(backend: Backend<R>, config: Config) -> Router {
+    let metrics = Arc::new(Metrics::new());
+    build_metered_app(backend.metered(metrics.clone()), config, metrics)
+}
+
+/// Como `build_app`, para quien comparte el repositorio medido y `metrics`
+/// con otro servidor (el de gRPC en el binario)
+pub fn build_metered_app<R: BookRepository + 'static>(
+    backend: Backend<MeteredBookRepository<R>>,
+    config: Config,
+    metrics: Arc<Metrics>,
+) -> Router {
+    let unmetered = UnmeteredBooks(backend.books.inner().clone());
+    routes(backend, config, metrics, unmetered)
+}
+
+fn routes<R: BookRepository + 'static>(
+    backend: Backend<R>,
+    config: Config,
+    metrics: Arc<Metrics>,
+    unmetered: UnmeteredBooks,
+) -> Router {
+    let Backend { books: repo, idempotency_store, audit_log, migrations: schema, health } = backend;
+    let config = Arc::new(config);
+    // Un limitador para todo menos `/login`: las claves `ip:` y `sub:` no se pisan
+    let limiter = Arc::new(RateLimiter::from_config(&config));
+    limiter.spawn_sweeper();
+    let login_limiter = Arc::new(RateLimiter::for_login(&config));
+    login_limiter.spawn_sweeper();
+    let state = AppState {
+        repo: repo.clone(),
+        config: config.clone(),
+        schema: build_schema(repo as Arc<dyn BookRepository>),
+    };
+
+    // `::<R>`: con varios `FromRef` sobre el estado el repo no se puede inferir
     let public = Router::new()
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(docs))
+        .route("/books", get(get_books::<R>))
+        .route("/books/:id", get(get_book::<R>))
+        .route("/books/search", get(search_books::<R>))
+        .with_state(state.clone())
+        .layer(from_fn_with_state(limiter.clone(), limit_by_ip));
+
+    // Cubo propio y más pequeño para frenar a quien prueba contraseñas
+    let login_api = Router::new()
         .route("/login", post(login))
-        .route("/books", get(get_books))
-        .route("/books/:id", get(get_book))
-        .route("/books/search", get(search_books))
-        .with_state(repo.clone());
+        .with_state(state.clone())
+        .layer(from_fn_with_state(login_limiter, limit_by_ip));
+
+    // Sondas y métricas para el orquestador, sin autenticación
+    let probes = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics_handler::metrics))
+        .with_state(state.clone())
+        .layer(Extension(schema.clone()))
+        .layer(Extension(health.clone()))
+        .layer(Extension(unmetered))
+        .layer(Extension(metrics.clone()));
+
+    // GraphQL: las mutaciones validan el JWT dentro del schema; con token se
+    // cuenta por usuario, como en las rutas protegidas
+    let graphql_api = Router::new()
+        .route("/graphql", get(graphiql).post(graphql))
+        .with_state(state.clone())
+        .layer(from_fn_with_state((limiter.clone(), config.clone()), limit_by_subject_or_ip));
 
     let protected = Router::new()
-        .route("/books", post(post_book))
-        .route("/books/:id", put(put_book).delete(delete_book))
-        .with_state(repo)
-        .layer(from_fn(auth));
+        .route("/books", post(post_book::<R>))
+        .route("/books/batch", post(batch_books::<R>))
+        .route("/books/duplicates", get(get_duplicates::<R>))
+        .route("/books/:id/merge", post(merge_book::<R>))
+        .route("/books/:id/history", get(book_history))
+        .route("/books/:id/history/:revision/revert", post(revert_book::<R>))
+        .route(
+            "/books/:id",
+            put(put_book::<R>).patch(patch_book::<R>).delete(delete_book::<R>),
+        )
+        .with_state(state.clone())
+        .layer(Extension(audit_log))
+        // `auth` va por fuera: una petición sin token no consume la clave
+        .layer(from_fn_with_state(idempotency_store, idempotency))
+        // Dentro de `auth`, que es quien deja el `sub` del token
+        .layer(from_fn_with_state(limiter.clone(), limit_by_subject))
+        .layer(from_fn_with_state(config.clone(), auth));
+
+    // `require_admin` lee los claims que deja `auth`, así que va por dentro
+    let admin = Router::new()
+        .route("/books/trash", get(list_trash::<R>))
+        .route("/books/trash/:id", delete(purge_book::<R>))
+        .route("/books/trash/:id/restore", post(restore_book::<R>))
+        .route("/admin/migrations", get(migrations))
+        .route("/status", get(status))
+        .with_state(state)
+        .layer(Extension(schema))
+        .layer(Extension(health))
+        .layer(Extension(StartedAt(Instant::now())))
+        .layer(from_fn(require_admin))
+        .layer(from_fn_with_state(limiter, limit_by_subject))
+        .layer(from_fn_with_state(config.clone(), auth));
 
-    public.merge(protected)
+    // `cors` por fuera de todo: los preflight no llevan token
+    public
+        .merge(login_api)
+        .merge(probes)
+        .merge(graphql_api)
+        .merge(protected)
+        .merge(admin)
+        .layer(from_fn_with_state(config, cors))
+        .layer(from_fn_with_state(metrics, track_metrics))
+        // Dentro del span de la petición para que la línea lleve su `trace_id`
+        .layer(from_fn(access_log))
+        .layer(from_fn(trace_request))
+        .layer(from_fn(request_id))
 }
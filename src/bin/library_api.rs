@@ -1,26 +1,119 @@
-use library_api::{ config::load_env,
-                   infra::sqlite_book_repository::SqliteBookRepository,
-                   app::build_app };
+use library_api::{ config::{ Config, redact_url },
+                   infra::{ Database, sqlite_backend, postgres_backend },
+                   grpc::{ catalog_service::Catalog, telemetry::GrpcTelemetryLayer },
+                   metrics::Metrics,
+                   shutdown::{ Shutdown, terminate_signal },
+                   telemetry::Telemetry,
+                   app::{ build_metered_app, Backend, book_repository::BookRepository } };
 use axum::serve;
-use sqlx::sqlite::SqlitePoolOptions;
 use tokio::net::TcpListener;
-use std::{sync::Arc, net::SocketAddr};
-use tracing_subscriber;
+use tonic::transport::Server;
+use std::{error::Error, net::SocketAddr, sync::Arc};
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-    load_env();
+    // Aún no hay subscriber: el error va directo a stderr
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Invalid configuration: {}", e);
+        std::process::exit(2);
+    });
+    let telemetry = Telemetry::init(&config);
 
-    let pool = SqlitePoolOptions::new()
-        .connect(&std::env::var("DATABASE_URL").unwrap())
-        .await
-        .unwrap();
-    let repo = Arc::new(SqliteBookRepository { pool });
-    let app  = build_app(repo);
+    let database = match Database::connect(&config.database_url, config.db_max_connections).await {
+        Ok(database) => database,
+        Err(e) => {
+            // El error puede repetir la URL, contraseña incluida
+            let url = &config.database_url;
+            tracing::error!("Cannot connect to database: {}", e.to_string().replace(url.as_str(), &redact_url(url)));
+            std::process::exit(1);
+        }
+    };
+    // Sin migrar también se comprueba que la BD no sea de una versión posterior
+    match database.migrate(!config.skip_migrations).await {
+        Ok(status) if !status.is_current() => {
+            tracing::warn!("Database schema has {} pending migrations", status.pending.len())
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Refusing to start: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let drain_timeout = config.shutdown_timeout;
+    let served = match database.clone() {
+        Database::Sqlite(pool) => run(sqlite_backend(pool), config).await,
+        Database::Postgres(pool) => run(postgres_backend(pool), config).await,
+    };
+    if let Err(e) = &served {
+        tracing::error!("Stopping: {}", e);
+    }
+    // Las conexiones HTTP abortadas pueden seguir reteniendo conexiones del
+    // pool: el cierre tiene el mismo plazo que el drenaje
+    if tokio::time::timeout(drain_timeout, database.close()).await.is_err() {
+        tracing::warn!("Database pool did not close within {:?}", drain_timeout);
+    }
+    telemetry.shutdown().await;
+    tracing::info!("Shutdown complete");
+    if served.is_err() {
+        std::process::exit(1);
+    }
+}
+
+/// Sirve HTTP y gRPC hasta la señal de apagado. `Err` si uno de los dos
+/// servidores cae antes (el otro se para con él) o falla durante el drenaje.
+async fn run<R: BookRepository + 'static>(backend: Backend<R>, config: Config) -> Result<(), String> {
+    let (addr, grpc_addr) = (config.bind_addr, config.grpc_bind_addr);
+    let drain_timeout = config.shutdown_timeout;
+    // Un solo repositorio medido para los dos servidores
+    let metrics = Arc::new(Metrics::new());
+    let backend = backend.metered(metrics.clone());
+    let grpc_service = Catalog::new(backend.books.clone(), Arc::new(config.clone())).into_service();
+    let app  = build_metered_app(backend, config, metrics.clone());
 
-    let addr = SocketAddr::from(([127,0,0,1],3000));
     println!("🚀 http://{}", addr);
-    let listener = TcpListener::bind(addr).await.unwrap();
-    serve(listener, app).await.unwrap();
+    println!("🚀 grpc://{}", grpc_addr);
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("HTTP server cannot bind {}: {}", addr, e))?;
+
+    let shutdown = Shutdown::new();
+    // La IP del cliente hace falta para el límite de peticiones anónimas
+    let http = serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.signalled());
+    let http = async move { http.await.map_err(|e| format!("HTTP server failed: {}", e)) };
+    let grpc = Server::builder()
+        .layer(GrpcTelemetryLayer::new(metrics))
+        .add_service(grpc_service)
+        .serve_with_shutdown(grpc_addr, shutdown.signalled());
+    let grpc = async move {
+        // El `Display` de tonic es solo "transport error": la causa va en `source`
+        grpc.await.map_err(|e| match e.source() {
+            Some(cause) => format!("gRPC server failed on {}: {}: {}", grpc_addr, e, cause),
+            None => format!("gRPC server failed on {}: {}", grpc_addr, e),
+        })
+    };
+    // El primer error suelta el otro futuro, así que ese servidor se para también
+    let mut servers = tokio::spawn(async move { tokio::try_join!(http, grpc).map(|_| ()) });
+
+    tokio::select! {
+        // Solo termina antes de la señal si uno de los servidores falla
+        result = &mut servers => {
+            return match result {
+                Ok(Ok(())) => Err("Servers stopped before the shutdown signal".to_string()),
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(format!("Server task failed: {}", e)),
+            };
+        }
+        _ = terminate_signal() => {}
+    }
+    tracing::info!("Shutdown signal received, draining in-flight requests for up to {:?}", drain_timeout);
+    match shutdown.drain(&mut servers, drain_timeout).await {
+        Some(result) => result.map_err(|e| format!("Server task failed: {}", e))?,
+        None => {
+            tracing::warn!("Drain timeout expired, aborting remaining requests");
+            servers.abort();
+            Ok(())
+        }
+    }
 }
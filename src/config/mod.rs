@@ -1,16 +1,470 @@
 // src/config/mod.rs
 
 use dotenvy::dotenv;
-use std::env;
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use thiserror::Error;
+use toml_edit::{DocumentMut, Item};
 
 pub fn load_env() {
     dotenv().ok();
 }
 
-pub fn database_url() -> String {
-    env::var("DATABASE_URL").expect("DATABASE_URL must be set")
+/// Claves admitidas. En el TOML van tal cual, en el entorno en mayúsculas
+/// (`BIND_ADDR`) y en la línea de comandos con guiones (`--bind-addr`).
+const KEYS: [&str; 16] = [
+    "bind_addr",
+    "grpc_bind_addr",
+    "database_url",
+    "db_max_connections",
+    "jwt_secret",
+    "token_ttl_secs",
+    "cors_allowed_origins",
+    "skip_migrations",
+    "shutdown_timeout_secs",
+    "otlp_endpoint",
+    "log_format",
+    "rate_limit_per_minute",
+    "rate_limit_burst",
+    "rate_limit_login_per_minute",
+    "rate_limit_login_burst",
+    "trusted_proxies",
+];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("Missing required setting {key} (set {env} or --{flag})")]
+    Missing { key: &'static str, env: String, flag: String },
+
+    #[error("Invalid {key} {value:?} (from {origin}): {reason}")]
+    Invalid { key: &'static str, value: String, origin: String, reason: String },
+
+    #[error("Cannot read config file {path}: {reason}")]
+    File { path: String, reason: String },
+
+    #[error("Unknown setting {0}")]
+    Unknown(String),
+}
+
+/// Formato de las líneas de log por stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// Un objeto JSON por línea, para agregadores de logs
+    Json,
+}
+
+/// Configuración del servicio, cargada y validada una sola vez al arrancar
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind_addr: SocketAddr,
+    pub grpc_bind_addr: SocketAddr,
+    /// `sqlite:`, `postgres:` o `postgresql:`
+    pub database_url: String,
+    pub db_max_connections: u32,
+    pub jwt_secret: String,
+    /// Validez de los tokens emitidos por `/login`
+    pub token_ttl: Duration,
+    /// Orígenes con acceso CORS; vacío lo desactiva y `*` admite cualquiera
+    pub cors_allowed_origins: Vec<String>,
+    /// El esquema lo gestiona otro proceso: no se aplican migraciones al arrancar
+    pub skip_migrations: bool,
+    /// Tiempo que se espera a las peticiones en curso tras SIGTERM
+    pub shutdown_timeout: Duration,
+    /// Colector OTLP/gRPC (`http://localhost:4317`); sin él no se exportan trazas
+    pub otlp_endpoint: Option<String>,
+    pub log_format: LogFormat,
+    /// Fichas que recupera por minuto cada IP (rutas anónimas) o cada `sub`
+    /// (rutas protegidas); 0 desactiva el límite
+    pub rate_limit_per_minute: u32,
+    /// Peticiones seguidas que admite un cliente con el cubo lleno
+    pub rate_limit_burst: u32,
+    /// Cubo aparte para `/login`, por IP, más estricto que el general para
+    /// frenar los intentos de adivinar contraseñas; 0 lo desactiva
+    pub rate_limit_login_per_minute: u32,
+    pub rate_limit_login_burst: u32,
+    /// Proxies inversos cuyo `X-Forwarded-For` se cree para saber la IP del
+    /// cliente; vacío, cuenta la IP de la conexión
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Config {
+    /// Valores por defecto para lo que no es obligatorio
+    pub fn new(database_url: String, jwt_secret: String) -> Self {
+        Self {
+            bind_addr: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            grpc_bind_addr: SocketAddr::from(([127, 0, 0, 1], 50051)),
+            database_url,
+            db_max_connections: 10,
+            jwt_secret,
+            token_ttl: Duration::from_secs(3600),
+            cors_allowed_origins: Vec::new(),
+            skip_migrations: false,
+            shutdown_timeout: Duration::from_secs(30),
+            otlp_endpoint: None,
+            log_format: LogFormat::Text,
+            rate_limit_per_minute: 300,
+            rate_limit_burst: 60,
+            rate_limit_login_per_minute: 10,
+            rate_limit_login_burst: 5,
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    /// Lee `.env`, el TOML de `--config`/`CONFIG_FILE`, el entorno y los flags,
+    /// en orden creciente de prioridad
+    pub fn load() -> Result<Self, ConfigError> {
+        load_env();
+        Self::from_sources(env::args().skip(1), |key| env::var(key).ok())
+    }
+
+    pub fn from_sources(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let (config_file, flags) = parse_args(args)?;
+
+        // Valor y procedencia de cada clave; cada fuente pisa a la anterior
+        let mut raw: HashMap<&'static str, (String, String)> = HashMap::new();
+        if let Some(path) = config_file.or_else(|| env("CONFIG_FILE")) {
+            for (key, value) in read_toml(&path)? {
+                raw.insert(key, (value, path.clone()));
+            }
+        }
+        for key in KEYS {
+            if let Some(value) = env(&key.to_uppercase()) {
+                raw.insert(key, (value, key.to_uppercase()));
+            }
+        }
+        for (key, value) in flags {
+            raw.insert(key, (value, format!("--{}", flag_name(key))));
+        }
+
+        let required = |key: &'static str| {
+            raw.get(key).map(|(value, _)| value.clone()).ok_or_else(|| ConfigError::Missing {
+                key,
+                env: key.to_uppercase(),
+                flag: flag_name(key),
+            })
+        };
+        let mut config = Config::new(required("database_url")?, required("jwt_secret")?);
+
+        for (&key, (value, origin)) in &raw {
+            let invalid = |reason: &str| ConfigError::Invalid {
+                key,
+                value: match key {
+                    "jwt_secret" => "***".into(),
+                    "database_url" => redact_url(value),
+                    _ => value.clone(),
+                },
+                origin: origin.clone(),
+                reason: reason.into(),
+            };
+            match key {
+                "bind_addr" => config.bind_addr = value.parse().map_err(|_| invalid("expected host:port"))?,
+                "grpc_bind_addr" => {
+                    config.grpc_bind_addr = value.parse().map_err(|_| invalid("expected host:port"))?
+                }
+                "database_url" => {
+                    if !["sqlite:", "postgres:", "postgresql:"].iter().any(|s| value.starts_with(s)) {
+                        return Err(invalid("must start with sqlite:, postgres: or postgresql:"));
+                    }
+                }
+                "db_max_connections" => {
+                    config.db_max_connections = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| invalid("expected a positive integer"))?
+                }
+                "jwt_secret" => {
+                    if value.trim().is_empty() {
+                        return Err(invalid("must not be empty"));
+                    }
+                }
+                "token_ttl_secs" => {
+                    config.token_ttl = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .map(Duration::from_secs)
+                        .ok_or_else(|| invalid("expected a positive number of seconds"))?
+                }
+                "cors_allowed_origins" => {
+                    config.cors_allowed_origins = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|o| !o.is_empty())
+                        .map(String::from)
+                        .collect();
+                    let valid = |o: &String| {
+                        o == "*"
+                            || ((o.starts_with("http://") || o.starts_with("https://"))
+                                && !o.ends_with('/')
+                                && o.parse::<axum::http::HeaderValue>().is_ok())
+                    };
+                    if !config.cors_allowed_origins.iter().all(valid) {
+                        return Err(invalid("expected `*` or comma-separated origins like https://example.com"));
+                    }
+                }
+                "skip_migrations" => {
+                    config.skip_migrations = match value.to_ascii_lowercase().as_str() {
+                        "true" | "1" => true,
+                        "false" | "0" => false,
+                        _ => return Err(invalid("expected true or false")),
+                    }
+                }
+                // 0 es válido: se corta sin esperar a nadie
+                "shutdown_timeout_secs" => {
+                    config.shutdown_timeout = value
+                        .parse()
+                        .map(Duration::from_secs)
+                        .map_err(|_| invalid("expected a number of seconds"))?
+                }
+                // Sin TLS: el cliente gRPC se compila sin soporte para https
+                "otlp_endpoint" => {
+                    let valid = value.starts_with("http://") && value.parse::<axum::http::Uri>().is_ok();
+                    if !valid {
+                        return Err(invalid("expected an http:// URL such as http://localhost:4317"));
+                    }
+                    config.otlp_endpoint = Some(value.clone());
+                }
+                "log_format" => {
+                    config.log_format = match value.to_ascii_lowercase().as_str() {
+                        "text" => LogFormat::Text,
+                        "json" => LogFormat::Json,
+                        _ => return Err(invalid("expected text or json")),
+                    }
+                }
+                "rate_limit_per_minute" => {
+                    config.rate_limit_per_minute =
+                        value.parse().map_err(|_| invalid("expected a number of requests (0 disables the limit)"))?
+                }
+                "rate_limit_burst" => {
+                    config.rate_limit_burst = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| invalid("expected a positive integer"))?
+                }
+                "rate_limit_login_per_minute" => {
+                    config.rate_limit_login_per_minute =
+                        value.parse().map_err(|_| invalid("expected a number of requests (0 disables the limit)"))?
+                }
+                "rate_limit_login_burst" => {
+                    config.rate_limit_login_burst = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| invalid("expected a positive integer"))?
+                }
+                "trusted_proxies" => {
+                    config.trusted_proxies = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|ip| !ip.is_empty())
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| invalid("expected comma-separated IP addresses"))?
+                }
+                _ => unreachable!("clave no incluida en KEYS"),
+            }
+        }
+        Ok(config)
+    }
 }
 
-pub fn jwt_secret() -> String {
-    env::var("JWT_SECRET").expect("JWT_SECRET must be set")
+/// `url` sin usuario ni contraseña (`postgres://***@host/db`), para poder
+/// mostrarla en errores y logs
+pub fn redact_url(url: &str) -> String {
+    let start = url.find("://").map_or(0, |i| i + 3);
+    let authority = &url[start..];
+    let end = authority.find(['/', '?', '#']).unwrap_or(authority.len());
+    match authority[..end].rfind('@') {
+        Some(at) => format!("{}***{}", &url[..start], &authority[at..]),
+        None => url.to_string(),
+    }
+}
+
+fn flag_name(key: &str) -> String {
+    key.replace('_', "-")
+}
+
+/// Pares (clave de `KEYS`, valor sin interpretar) de una misma fuente
+type Settings = Vec<(&'static str, String)>;
+
+/// Separa `--config <ruta>` del resto de flags (`--clave valor` o `--clave=valor`)
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<(Option<String>, Settings), ConfigError> {
+    let mut config_file = None;
+    let mut flags = Vec::new();
+    let mut args = args.into_iter().peekable();
+
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            return Err(ConfigError::Unknown(arg));
+        };
+        let (name, inline) = match flag.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (flag.to_string(), None),
+        };
+        let key = match name.as_str() {
+            "config" => None,
+            _ => Some(
+                KEYS.into_iter()
+                    .find(|key| flag_name(key) == name)
+                    .ok_or_else(|| ConfigError::Unknown(arg.clone()))?,
+            ),
+        };
+        // Flag booleano: `--skip-migrations` equivale a `--skip-migrations=true`
+        let value = match inline {
+            Some(value) => Some(value),
+            None if name == "skip-migrations" => Some("true".into()),
+            None => args.next_if(|next| !next.starts_with("--")),
+        };
+        let Some(value) = value else {
+            return Err(ConfigError::Invalid {
+                key: "flag",
+                value: arg.clone(),
+                origin: "command line".into(),
+                reason: "missing value".into(),
+            });
+        };
+        match key {
+            Some(key) => flags.push((key, value)),
+            None => config_file = Some(value),
+        }
+    }
+    Ok((config_file, flags))
+}
+
+fn read_toml(path: &str) -> Result<Settings, ConfigError> {
+    let file_error = |reason: String| ConfigError::File { path: path.into(), reason };
+    let text = fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+    let doc: DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| file_error(e.message().into()))?;
+
+    let mut values = Vec::new();
+    for (name, item) in doc.iter() {
+        let key = KEYS
+            .into_iter()
+            .find(|key| *key == name)
+            .ok_or_else(|| ConfigError::Unknown(format!("{} in {}", name, path)))?;
+        let value = match item {
+            Item::Value(v) if v.as_str().is_some() => v.as_str().unwrap_or_default().to_string(),
+            Item::Value(v) if v.as_integer().is_some() => v.as_integer().unwrap_or_default().to_string(),
+            Item::Value(v) if v.as_bool().is_some() => v.as_bool().unwrap_or_default().to_string(),
+            // Listas: orígenes CORS, proxies de confianza
+            Item::Value(v) if v.as_array().is_some_and(|a| a.iter().all(|o| o.as_str().is_some())) => v
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|o| o.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+            other => return Err(file_error(format!("unsupported {} for {}", other.type_name(), name))),
+        };
+        values.push((key, value));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact_url, Config, ConfigError, LogFormat};
+    use std::{collections::HashMap, time::Duration};
+
+    fn load(args: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Config::from_sources(args.iter().map(|a| a.to_string()), |key| env.get(key).cloned())
+    }
+
+    const REQUIRED: [(&str, &str); 2] = [("DATABASE_URL", "sqlite::memory:"), ("JWT_SECRET", "s3cret")];
+
+    #[test]
+    fn defaults_apply_when_only_required_settings_are_given() {
+        let config = load(&[], &REQUIRED).unwrap();
+        assert_eq!(config.bind_addr.to_string(), "127.0.0.1:3000");
+        assert_eq!(config.db_max_connections, 10);
+        assert_eq!(config.token_ttl, Duration::from_secs(3600));
+        assert!(config.cors_allowed_origins.is_empty());
+        assert!(!config.skip_migrations);
+        assert_eq!(config.log_format, LogFormat::Text);
+        assert_eq!(config.otlp_endpoint, None);
+        assert_eq!((config.rate_limit_per_minute, config.rate_limit_burst), (300, 60));
+        assert_eq!((config.rate_limit_login_per_minute, config.rate_limit_login_burst), (10, 5));
+        assert!(config.trusted_proxies.is_empty());
+    }
+
+    #[test]
+    fn flags_override_environment_which_overrides_the_file() {
+        let path = std::env::temp_dir().join(format!("library_api_{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "bind_addr = \"0.0.0.0:8080\"\ndb_max_connections = 4\ntoken_ttl_secs = 60\nshutdown_timeout_secs = 5\ncors_allowed_origins = [\"https://a.example\", \"https://b.example\"]\n",
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+
+        let env = [REQUIRED[0], REQUIRED[1], ("DB_MAX_CONNECTIONS", "8"), ("LOG_FORMAT", "JSON"), ("RATE_LIMIT_PER_MINUTE", "0")];
+        let args = ["--config", file, "--token-ttl-secs=120", "--skip-migrations", "--trusted-proxies", "10.0.0.1, ::1"];
+        let config = load(&args, &env).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.bind_addr.to_string(), "0.0.0.0:8080");
+        assert_eq!(config.db_max_connections, 8);
+        assert_eq!(config.token_ttl, Duration::from_secs(120));
+        assert_eq!(config.cors_allowed_origins, ["https://a.example", "https://b.example"]);
+        assert!(config.skip_migrations);
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(5));
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.rate_limit_per_minute, 0);
+        assert_eq!(config.trusted_proxies, ["10.0.0.1".parse::<std::net::IpAddr>().unwrap(), "::1".parse().unwrap()]);
+    }
+
+    #[test]
+    fn invalid_settings_name_key_value_and_origin() {
+        let err = load(&[], &[("DATABASE_URL", "sqlite::memory:")]).unwrap_err();
+        assert_eq!(err.to_string(), "Missing required setting jwt_secret (set JWT_SECRET or --jwt-secret)");
+
+        let err = load(&["--db-max-connections", "0"], &REQUIRED).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "db_max_connections", ref origin, .. } if origin == "--db-max-connections"));
+
+        let env = [REQUIRED[0], REQUIRED[1], ("BIND_ADDR", "localhost")];
+        let err = load(&[], &env).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid bind_addr \"localhost\" (from BIND_ADDR): expected host:port");
+
+        let err = load(&["--cors-allowed-origins", "https://a.example/"], &REQUIRED).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "cors_allowed_origins", .. }));
+        let err = load(&["--otlp-endpoint", "https://collector:4317"], &REQUIRED).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "otlp_endpoint", .. }));
+        let err = load(&["--rate-limit-burst", "0"], &REQUIRED).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "rate_limit_burst", .. }));
+        let err = load(&["--trusted-proxies", "10.0.0.0/8"], &REQUIRED).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "trusted_proxies", .. }));
+        let err = load(&["--database-url", "mysql://db"], &REQUIRED).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "database_url", .. }));
+        assert!(matches!(load(&["--verbose"], &REQUIRED), Err(ConfigError::Unknown(_))));
+        assert!(matches!(load(&["--config", "/nonexistent.toml"], &REQUIRED), Err(ConfigError::File { .. })));
+    }
+
+    #[test]
+    fn secrets_are_not_echoed_in_errors() {
+        let err = load(&["--jwt-secret", " "], &REQUIRED).unwrap_err();
+        assert!(!err.to_string().contains("\" \""), "{}", err);
+
+        let err = load(&["--database-url", "mysql://app:hunter2@db:3306/library"], &REQUIRED).unwrap_err();
+        assert!(!err.to_string().contains("hunter2"), "{}", err);
+        assert!(err.to_string().contains("mysql://***@db:3306/library"), "{}", err);
+    }
+
+    #[test]
+    fn redacted_urls_keep_everything_but_the_credentials() {
+        assert_eq!(redact_url("postgres://app:s3cret@db/library?sslmode=require"), "postgres://***@db/library?sslmode=require");
+        assert_eq!(redact_url("postgres://db/library?user=a@b"), "postgres://db/library?user=a@b");
+        assert_eq!(redact_url("sqlite://./library.db"), "sqlite://./library.db");
+    }
 }
@@ -1,31 +1,57 @@
+use chrono::{DateTime, SubsecRound, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, Clone)]
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema, async_graphql::SimpleObject)]
 pub struct Book {
     pub id: String,
     pub title: String,
     pub author: String,
     pub published_year: Option<i32>,
-    pub created_at: String,
+    /// ISBN-10 o ISBN-13, tal como lo escribió el catalogador
+    pub isbn: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Última escritura; lo mantiene el repositorio
+    pub updated_at: DateTime<Utc>,
+    /// `sub` del JWT de la última escritura; `None` en libros anteriores a este campo
+    pub updated_by: Option<String>,
+    /// Se incrementa en cada actualización; base del `ETag`
+    pub version: i64,
+    /// Momento en que se movió a la papelera; solo aparece en `GET /books/trash`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[graphql(skip)]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Book {
     pub fn new(title: String, author: String, published_year: Option<i32>) -> Self {
+        let now = now();
         Self {
             id: Uuid::new_v4().to_string(),
             title,
             author,
             published_year,
-            created_at: chrono::Utc::now().to_rfc3339(),
+            isbn: None,
+            created_at: now,
+            updated_at: now,
+            updated_by: None,
+            version: 1,
+            deleted_at: None,
         }
     }
 }
 
+/// Instante actual con la precisión con la que se guarda (milisegundos), para
+/// que un libro recién escrito sea igual al que se vuelve a leer
+pub fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(3)
+}
+
 #[cfg(test)]
 mod tests {
     use super::Book;
-    use chrono::{Utc, Duration, DateTime};
+    use chrono::{Utc, Duration};
     use uuid::Uuid;
 
     #[test]
@@ -44,10 +70,8 @@ mod tests {
         let book = Book::new("Foo".into(), "Bar".into(), None);
         let after = Utc::now() + Duration::seconds(1);
 
-        // Parse el campo String a DateTime<Utc>
-        let ts: DateTime<Utc> = DateTime::parse_from_rfc3339(&book.created_at)
-            .expect("created_at debe ser RFC 3339")
-            .with_timezone(&Utc);
+        let ts = book.created_at;
+        assert_eq!(ts.timestamp_subsec_nanos() % 1_000_000, 0, "precisión de milisegundos");
 
         assert!(
             ts >= before && ts <= after,
@@ -0,0 +1,246 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use utoipa::ToSchema;
+
+use crate::domain::book::Book;
+
+/// Similitud mínima de títulos normalizados para sospechar un duplicado
+pub const TITLE_THRESHOLD: f64 = 0.85;
+/// Similitud mínima de autores normalizados para sospechar un duplicado
+pub const AUTHOR_THRESHOLD: f64 = 0.8;
+
+const LEADING_ARTICLES: [&str; 7] = ["the", "a", "an", "el", "la", "los", "las"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// Mismo ISBN una vez normalizado
+    Isbn,
+    /// Título y autor muy parecidos
+    SimilarTitleAndAuthor,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct DuplicateMatch {
+    pub book_id: String,
+    pub duplicate_id: String,
+    pub reason: DuplicateReason,
+    /// 1.0 para ISBN; media de similitud de título y autor en otro caso
+    pub score: f64,
+}
+
+/// Minúsculas, solo letras/dígitos, espacios colapsados y sin artículo inicial
+pub fn normalize(s: &str) -> String {
+    let cleaned: String = s
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .flat_map(char::to_lowercase)
+        .collect();
+    let mut words: Vec<&str> = cleaned.split_whitespace().collect();
+    if words.len() > 1 && LEADING_ARTICLES.contains(&words[0]) {
+        words.remove(0);
+    }
+    words.join(" ")
+}
+
+/// ISBN sin guiones ni espacios, con la `X` final en mayúscula
+pub fn normalize_isbn(isbn: &str) -> String {
+    isbn.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Claves con las que el almacenamiento busca candidatos a duplicado sin
+/// recorrer el catálogo: basta con compartir una de las dos. Así una errata
+/// en el apellido la salva el título y una en el título, el apellido; solo
+/// se escapan las parejas con erratas en las dos.
+///
+/// Apellido normalizado: la última palabra del autor
+pub fn author_key(author: &str) -> String {
+    normalize(author).rsplit(' ').next().unwrap_or_default().to_string()
+}
+
+/// Primera palabra del título normalizado, ya sin artículo
+pub fn title_key(title: &str) -> String {
+    normalize(title).split(' ').next().unwrap_or_default().to_string()
+}
+
+/// Si dos libros caen en el mismo bloque: mismo ISBN normalizado, mismo
+/// `author_key` o mismo `title_key`
+pub fn share_key(book: &Book, other: &Book) -> bool {
+    blocks(book).iter().any(|block| blocks(other).contains(block))
+}
+
+fn blocks(book: &Book) -> Vec<String> {
+    let mut blocks = vec![format!("a:{}", author_key(&book.author)), format!("t:{}", title_key(&book.title))];
+    blocks.extend(book.isbn.as_deref().map(|isbn| format!("i:{}", normalize_isbn(isbn))));
+    blocks
+}
+
+/// ISBN-10 (con `X` como dígito de control) o ISBN-13 con checksum correcto
+pub fn is_valid_isbn(isbn: &str) -> bool {
+    let isbn = normalize_isbn(isbn);
+    let digits: Vec<u32> = isbn
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'X' if i == 9 && isbn.len() == 10 => Some(10),
+            _ => c.to_digit(10),
+        })
+        .collect::<Option<_>>()
+        .unwrap_or_default();
+    match digits.len() {
+        10 => digits.iter().zip((1..=10).rev()).map(|(d, w)| d * w).sum::<u32>() % 11 == 0,
+        13 => digits.iter().zip([1, 3].iter().cycle()).map(|(d, w)| d * w).sum::<u32>() % 10 == 0,
+        _ => false,
+    }
+}
+
+/// Similitud en [0, 1] basada en la distancia de Levenshtein
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Compara dos libros y devuelve el motivo si parecen el mismo
+pub fn compare(book: &Book, other: &Book) -> Option<DuplicateMatch> {
+    let matched = |reason, score| DuplicateMatch {
+        book_id: book.id.clone(),
+        duplicate_id: other.id.clone(),
+        reason,
+        score,
+    };
+
+    if let (Some(a), Some(b)) = (&book.isbn, &other.isbn) {
+        if normalize_isbn(a) == normalize_isbn(b) {
+            return Some(matched(DuplicateReason::Isbn, 1.0));
+        }
+    }
+
+    let title = similarity(&normalize(&book.title), &normalize(&other.title));
+    let author = similarity(&normalize(&book.author), &normalize(&other.author));
+    if title >= TITLE_THRESHOLD && author >= AUTHOR_THRESHOLD {
+        return Some(matched(DuplicateReason::SimilarTitleAndAuthor, (title + author) / 2.0));
+    }
+    None
+}
+
+/// Posibles duplicados de `book` dentro de `catalog` (excluido él mismo);
+/// `catalog` basta con que sean los candidatos que da el repositorio
+pub fn find_duplicates(book: &Book, catalog: &[Book]) -> Vec<DuplicateMatch> {
+    catalog
+        .iter()
+        .filter(|other| other.id != book.id)
+        .filter_map(|other| compare(book, other))
+        .collect()
+}
+
+/// Todas las parejas sospechosas del catálogo, cada una una sola vez. Solo
+/// se comparan los libros de un mismo bloque (`share_key`): con un apellido
+/// frecuente los candidatos pueden ser casi todo el catálogo.
+pub fn duplicate_pairs(catalog: &[Book]) -> Vec<DuplicateMatch> {
+    let mut by_block: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, book) in catalog.iter().enumerate() {
+        for block in blocks(book) {
+            by_block.entry(block).or_default().push(i);
+        }
+    }
+    let pairs: BTreeSet<(usize, usize)> = by_block
+        .values()
+        .flat_map(|books| books.iter().enumerate().flat_map(|(n, &i)| books[n + 1..].iter().map(move |&j| (i, j))))
+        .collect();
+    pairs.into_iter().filter_map(|(i, j)| compare(&catalog[i], &catalog[j])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(title: &str, author: &str, isbn: Option<&str>) -> Book {
+        let mut b = Book::new(title.into(), author.into(), None);
+        b.isbn = isbn.map(Into::into);
+        b
+    }
+
+    #[test]
+    fn normalize_strips_punctuation_case_and_articles() {
+        assert_eq!(normalize("The Hobbit: or, There and Back Again"), "hobbit or there and back again");
+        assert_eq!(normalize("  Cien   años de soledad "), "cien años de soledad");
+        assert_eq!(normalize("The"), "the");
+    }
+
+    #[test]
+    fn keys_ignore_initials_punctuation_and_articles() {
+        assert_eq!(author_key("J.R.R. Tolkien"), "tolkien");
+        assert_eq!(author_key("J. R. R. Tolkien"), "tolkien");
+        assert_eq!(title_key("The Lord of the Rings"), "lord");
+        assert_ne!(author_key("Frank Herbert"), author_key("Brian Herbert Jr"));
+    }
+
+    #[test]
+    fn a_typo_in_one_key_still_shares_the_other() {
+        let a = book("The Lord of the Rings", "J.R.R. Tolkien", None);
+        let b = book("Lord of the Rings", "J.R.R. Tolkein", None);
+        let c = book("Lrod of the Rings", "J.R.R. Tolkien", None);
+        assert!(share_key(&a, &b) && share_key(&a, &c));
+        assert!(!share_key(&b, &c));
+        assert!(!share_key(&a, &book("Dune", "Frank Herbert", None)));
+        assert_eq!(duplicate_pairs(&[a, b, c]).len(), 2);
+    }
+
+    #[test]
+    fn isbn_match_ignores_formatting() {
+        let a = book("Dune", "Frank Herbert", Some("978-0-441-17271-9"));
+        let b = book("Dune (Deluxe)", "F. Herbert", Some("9780441172719"));
+        let m = compare(&a, &b).expect("mismo ISBN");
+        assert_eq!(m.reason, DuplicateReason::Isbn);
+    }
+
+    #[test]
+    fn isbn_checksums_are_verified() {
+        assert!(is_valid_isbn("978-0-441-17271-9"));
+        assert!(is_valid_isbn("0-8044-2957-X"));
+        assert!(!is_valid_isbn("978-0-441-17271-8"));
+        assert!(!is_valid_isbn("X-8044-2957-0"));
+        assert!(!is_valid_isbn("12345"));
+    }
+
+    #[test]
+    fn similar_titles_by_same_author_are_flagged() {
+        let a = book("The Lord of the Rings", "J.R.R. Tolkien", None);
+        let b = book("Lord of the Ring", "J. R. R. Tolkien", None);
+        let c = book("The Silmarillion", "J.R.R. Tolkien", None);
+
+        let found = find_duplicates(&a, &[a.clone(), b.clone(), c]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].duplicate_id, b.id);
+        assert_eq!(found[0].reason, DuplicateReason::SimilarTitleAndAuthor);
+    }
+
+    #[test]
+    fn pairs_are_reported_once() {
+        let a = book("Emma", "Jane Austen", None);
+        let b = book("Emma.", "Jane Austen", None);
+        assert_eq!(duplicate_pairs(&[a, b]).len(), 1);
+    }
+}
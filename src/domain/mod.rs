@@ -1 +1,2 @@
 pub mod book;
+pub mod duplicates;
@@ -1,14 +1,45 @@
 use axum::{
-    http::StatusCode,
+    http::{header::{CONTENT_TYPE, RETRY_AFTER}, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
+use std::collections::BTreeMap;
 use thiserror::Error;
+use utoipa::ToSchema;
+use validator::ValidationErrors;
 
-#[derive(Serialize)]
-struct ErrorBody {
-    error: String,
+use crate::{app::book_repository::RepoError, middleware::request_id};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Segundos sugeridos en `Retry-After` cuando la BD está ocupada
+pub const RETRY_AFTER_SECS: u64 = 1;
+
+/// Código del `AppError` que produjo la respuesta, en sus extensiones; lo
+/// lee la capa de métricas
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorCode(pub &'static str);
+
+/// Mensajes de validación agrupados por campo
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
+/// Cuerpo de error RFC 7807 (`application/problem+json`)
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// URI estable que identifica el tipo de problema
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// Código estable, pensado para que los clientes hagan `match`
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Campo → mensajes, solo en errores de validación
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
 }
 
 #[derive(Error, Debug)]
@@ -16,28 +47,240 @@ pub enum AppError {
     #[error("Not found: {0}")]
     NotFound(String),
 
-    #[error("Invalid input: {0}")]
-    Validation(String),
+    #[error("Invalid input: {}", flatten_errors(.0))]
+    Validation(FieldErrors),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
 
     #[error("Unauthorized")]
     Auth,
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    #[error("Unprocessable: {0}")]
+    Unprocessable(String),
+
+    #[error("Failed dependency: {0}")]
+    FailedDependency(String),
+
+    #[error("Service unavailable")]
+    Unavailable,
+
+    /// Segundos hasta que el cliente vuelva a tener cupo
+    #[error("Too many requests, retry in {0}s")]
+    RateLimited(u64),
+
     #[error(transparent)]
     Db(#[from] anyhow::Error),
 }
 
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_)             => StatusCode::NOT_FOUND,
+            AppError::Validation(_)           => StatusCode::BAD_REQUEST,
+            AppError::BadRequest(_)           => StatusCode::BAD_REQUEST,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Auth                    => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_)            => StatusCode::FORBIDDEN,
+            AppError::Conflict(_)             => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_)   => StatusCode::PRECONDITION_FAILED,
+            AppError::Unprocessable(_)        => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::FailedDependency(_)     => StatusCode::FAILED_DEPENDENCY,
+            AppError::Unavailable             => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::RateLimited(_)          => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Db(_)                   => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Código estable del error; también forma parte del `type` del problema
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_)             => "not_found",
+            AppError::Validation(_)           => "validation_failed",
+            AppError::BadRequest(_)           => "bad_request",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::Auth                    => "unauthorized",
+            AppError::Forbidden(_)            => "forbidden",
+            AppError::Conflict(_)             => "conflict",
+            AppError::PreconditionFailed(_)   => "precondition_failed",
+            AppError::Unprocessable(_)        => "unprocessable",
+            AppError::FailedDependency(_)     => "failed_dependency",
+            AppError::Unavailable             => "unavailable",
+            AppError::RateLimited(_)          => "rate_limited",
+            AppError::Db(_)                   => "internal",
+        }
+    }
+
+    /// Mensaje apto para el cliente: los errores internos no se exponen
+    pub fn public_message(&self) -> String {
+        match self {
+            AppError::Db(_) => "Internal error".into(),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn to_problem(&self) -> ErrorBody {
+        let status = self.status();
+        ErrorBody {
+            type_uri: format!("urn:library-api:problem:{}", self.code()),
+            title: status.canonical_reason().unwrap_or("Error").into(),
+            status: status.as_u16(),
+            detail: self.public_message(),
+            code: self.code().into(),
+            request_id: request_id::current(),
+            errors: match self {
+                AppError::Validation(errors) => Some(errors.clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl From<RepoError> for AppError {
+    fn from(e: RepoError) -> Self {
+        match e {
+            RepoError::NotFound(id) => AppError::NotFound(format!("Book {} not found", id)),
+            // El mensaje de la BD nombra tablas y restricciones: solo va al log
+            RepoError::Conflict(msg) => {
+                tracing::warn!("Repository conflict: {}", msg);
+                AppError::Conflict("Book already exists".into())
+            }
+            RepoError::VersionMismatch(id) => {
+                AppError::PreconditionFailed(format!("Book {} has been modified", id))
+            }
+            RepoError::ForeignKey(msg) | RepoError::Constraint(msg) => {
+                tracing::warn!("Repository constraint violation: {}", msg);
+                AppError::Unprocessable("Book violates a data constraint".into())
+            }
+            RepoError::Unavailable(msg) => {
+                tracing::warn!("DB unavailable: {}", msg);
+                AppError::Unavailable
+            }
+            RepoError::Other(e) => AppError::Db(e),
+        }
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(e: ValidationErrors) -> Self {
+        let errors = e
+            .field_errors()
+            .into_iter()
+            .map(|(field, errs)| {
+                let msgs = errs
+                    .iter()
+                    .map(|err| {
+                        err.message
+                            .as_deref()
+                            .unwrap_or("invalid") // si no hay mensaje, usamos "invalid"
+                            .to_string()
+                    })
+                    .collect();
+                (field.to_string(), msgs)
+            })
+            .collect();
+        AppError::Validation(errors)
+    }
+}
+
+fn flatten_errors(errors: &FieldErrors) -> String {
+    errors
+        .iter()
+        .flat_map(|(field, msgs)| msgs.iter().map(move |msg| format!("{}: {}", field, msg)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, msg) = match &self {
-            AppError::NotFound(_)   => (StatusCode::NOT_FOUND, self.to_string()),
-            AppError::Validation(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            AppError::Auth          => (StatusCode::UNAUTHORIZED, self.to_string()),
-            AppError::Db(_)         => {
-                tracing::error!("DB error: {:?}", self);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal error".into())
+        if let AppError::Db(_) = self {
+            tracing::error!("DB error: {:?}", self);
+        }
+        let problem = self.to_problem();
+        let mut res = (self.status(), [(CONTENT_TYPE, PROBLEM_JSON)], Json(problem)).into_response();
+        match self {
+            AppError::Unavailable => {
+                res.headers_mut().insert(RETRY_AFTER, RETRY_AFTER_SECS.into());
             }
+            AppError::RateLimited(secs) => {
+                res.headers_mut().insert(RETRY_AFTER, secs.into());
+            }
+            _ => {}
+        }
+        res.extensions_mut().insert(ErrorCode(self.code()));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppError;
+    use crate::handlers::book_handler::CreateBook;
+    use validator::Validate;
+
+    #[test]
+    fn validation_errors_are_grouped_by_field() {
+        let bad = CreateBook {
+            title: "".into(),
+            author: "".into(),
+            published_year: Some(-1),
+            isbn: Some("978-0".into()),
         };
-        let body = Json(ErrorBody { error: msg });
-        (status, body).into_response()
+        let err = AppError::from(bad.validate().expect_err("debe fallar validación"));
+
+        let problem = err.to_problem();
+        let errors = problem.errors.expect("debe incluir errores por campo");
+        assert_eq!(errors["title"], vec!["Title cannot be empty"]);
+        assert_eq!(errors["author"], vec!["Author cannot be empty"]);
+        assert_eq!(errors["published_year"], vec!["Published year must be positive"]);
+        assert_eq!(errors["isbn"], vec!["ISBN must be a valid ISBN-10 or ISBN-13"]);
+        assert_eq!(problem.code, "validation_failed");
+        assert_eq!(problem.status, 400);
+
+        let out = err.to_string();
+        assert!(out.contains("title: Title cannot be empty"));
+        assert!(!out.contains('\n'));
+    }
+
+    #[test]
+    fn internal_errors_are_not_exposed() {
+        let err = AppError::Db(anyhow::anyhow!("disk I/O error"));
+        let problem = err.to_problem();
+        assert_eq!(problem.status, 500);
+        assert_eq!(problem.detail, "Internal error");
+        assert!(problem.errors.is_none());
+    }
+
+    #[test]
+    fn repository_errors_map_to_http_statuses() {
+        use crate::app::book_repository::RepoError;
+        use axum::{http::StatusCode, response::IntoResponse};
+
+        let conflict = AppError::from(RepoError::Conflict("UNIQUE constraint failed: books.id".into()));
+        assert_eq!(conflict.status(), StatusCode::CONFLICT);
+        assert_eq!(conflict.to_problem().detail, "Conflict: Book already exists");
+
+        let fk = AppError::from(RepoError::ForeignKey("FOREIGN KEY constraint failed".into()));
+        assert_eq!(fk.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!fk.to_problem().detail.contains("FOREIGN KEY"));
+
+        let missing = AppError::from(RepoError::NotFound("42".into()));
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        let res = AppError::from(RepoError::Unavailable("database is locked".into())).into_response();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()["retry-after"], "1");
     }
 }
@@ -0,0 +1,81 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    Json,
+};
+
+use crate::error::AppError;
+
+/// `Json<T>` cuyo rechazo (cuerpo mal formado, `Content-Type` incorrecto,
+/// tipos que no encajan) es un `AppError` y sale como `problem+json`
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for JsonBody<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(JsonBody(value))
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::MissingJsonContentType(_) => {
+                AppError::UnsupportedMediaType("Expected Content-Type: application/json".into())
+            }
+            // Incluye el campo y la posición: "title: invalid type: integer `1`, expected a string at line 1 column 11"
+            JsonRejection::JsonDataError(e) => AppError::BadRequest(format!("Invalid JSON body: {}", source_message(&e))),
+            JsonRejection::JsonSyntaxError(e) => AppError::BadRequest(format!("Malformed JSON: {}", source_message(&e))),
+            other => AppError::BadRequest(other.body_text()),
+        }
+    }
+}
+
+/// Mensaje de serde sin el prefijo genérico de axum
+fn source_message(e: &(dyn std::error::Error + 'static)) -> String {
+    std::error::Error::source(e).map_or_else(|| e.to_string(), |source| source.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonBody;
+    use crate::error::AppError;
+    use axum::{body::Body, extract::FromRequest, http::Request};
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Payload {
+        title: String,
+    }
+
+    async fn extract(content_type: &str, body: &str) -> Result<JsonBody<Payload>, AppError> {
+        let req = Request::builder()
+            .method("POST")
+            .header("content-type", content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        JsonBody::<Payload>::from_request(req, &()).await
+    }
+
+    #[tokio::test]
+    async fn rejections_become_app_errors() {
+        assert!(extract("application/json", r#"{"title":"Dune"}"#).await.is_ok());
+
+        let err = extract("text/plain", r#"{"title":"Dune"}"#).await.err().unwrap();
+        assert!(matches!(err, AppError::UnsupportedMediaType(_)), "{:?}", err);
+
+        let err = extract("application/json", r#"{"title":"#).await.err().unwrap();
+        assert!(matches!(&err, AppError::BadRequest(msg) if msg.starts_with("Malformed JSON")), "{:?}", err);
+
+        let err = extract("application/json", r#"{"title":1}"#).await.err().unwrap();
+        assert!(matches!(&err, AppError::BadRequest(msg) if msg.contains("title: invalid type")), "{:?}", err);
+    }
+}
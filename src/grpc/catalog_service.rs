@@ -0,0 +1,237 @@
+// `tonic::Status` es grande, pero es el tipo de error que impone el trait generado
+#![allow(clippy::result_large_err)]
+
+use chrono::SecondsFormat;
+use std::{pin::Pin, sync::Arc};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{service::interceptor::InterceptedService, Request, Response, Status};
+use validator::Validate;
+
+use crate::{
+    app::book_repository::{BookFilter, BookRepository},
+    config::Config,
+    domain::book::Book,
+    error::AppError,
+    grpc::{
+        proto::{
+            self,
+            catalog_service_server::{CatalogService, CatalogServiceServer},
+        },
+        rate_limit::RateLimit,
+    },
+    handlers::book_handler::{CreateBook, UpdateBook},
+    middleware::{
+        auth::{authorize, Claims},
+        rate_limit::RateLimiter,
+    },
+};
+
+pub struct Catalog<R> {
+    repo: Arc<R>,
+    config: Arc<Config>,
+}
+
+impl<R: BookRepository + 'static> Catalog<R> {
+    pub fn new(repo: Arc<R>, config: Arc<Config>) -> Self {
+        Self { repo, config }
+    }
+
+    /// Servicio listo para montar en `tonic::transport::Server`, con el
+    /// límite de peticiones de `config`. Sus cubos son aparte de los de HTTP:
+    /// un cliente que use los dos servidores tiene el presupuesto en cada uno.
+    pub fn into_service(self) -> InterceptedService<CatalogServiceServer<Self>, RateLimit> {
+        let limiter = Arc::new(RateLimiter::from_config(&self.config));
+        limiter.spawn_sweeper();
+        let rate_limit = RateLimit::new(limiter, self.config.clone());
+        CatalogServiceServer::with_interceptor(self, rate_limit)
+    }
+}
+
+/// Libros que `StreamList` lee de cada vez
+const STREAM_PAGE_SIZE: i64 = 100;
+
+type BookStream = Pin<Box<dyn Stream<Item = Result<proto::Book, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl<R: BookRepository + 'static> CatalogService for Catalog<R> {
+    async fn get(
+        &self,
+        request: Request<proto::GetBookRequest>,
+    ) -> Result<Response<proto::Book>, Status> {
+        let id = request.into_inner().id;
+        let book = self
+            .repo
+            .get_by_id(&id)
+            .await
+            .map_err(AppError::from)?
+            .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
+        Ok(Response::new(book.into()))
+    }
+
+    async fn list(
+        &self,
+        _request: Request<proto::ListBooksRequest>,
+    ) -> Result<Response<proto::ListBooksResponse>, Status> {
+        let books = self.repo.get_all().await.map_err(AppError::from)?;
+        Ok(Response::new(books.into()))
+    }
+
+    async fn search(
+        &self,
+        request: Request<proto::SearchBooksRequest>,
+    ) -> Result<Response<proto::ListBooksResponse>, Status> {
+        let params = request.into_inner();
+        let filter = BookFilter { title: params.title, author: params.author, ..Default::default() };
+        let books = self
+            .repo
+            .search(&filter)
+            .await
+            .map_err(AppError::from)?;
+        Ok(Response::new(books.into()))
+    }
+
+    async fn create(
+        &self,
+        request: Request<proto::CreateBookRequest>,
+    ) -> Result<Response<proto::Book>, Status> {
+        let claims = authorize_metadata(&self.config, &request)?;
+        let req = request.into_inner();
+        let payload = CreateBook {
+            title: req.title,
+            author: req.author,
+            published_year: req.published_year,
+            isbn: req.isbn,
+        };
+        payload.validate().map_err(AppError::from)?;
+        let mut book = payload.into_book();
+        book.updated_by = Some(claims.sub);
+        let saved = self.repo.create(book).await.map_err(AppError::from)?;
+        Ok(Response::new(saved.into()))
+    }
+
+    async fn update(
+        &self,
+        request: Request<proto::UpdateBookRequest>,
+    ) -> Result<Response<proto::Book>, Status> {
+        let claims = authorize_metadata(&self.config, &request)?;
+        let req = request.into_inner();
+        let payload = UpdateBook {
+            title: req.title,
+            author: req.author,
+            published_year: req.published_year,
+            isbn: req.isbn,
+        };
+        payload.validate().map_err(AppError::from)?;
+        let mut book = self
+            .repo
+            .get_by_id(&req.id)
+            .await
+            .map_err(AppError::from)?
+            .ok_or_else(|| AppError::NotFound(format!("Book {} not found", req.id)))?;
+        payload.apply(&mut book);
+        book.updated_by = Some(claims.sub);
+        let updated = self.repo.update(book).await.map_err(AppError::from)?;
+        Ok(Response::new(updated.into()))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteBookRequest>,
+    ) -> Result<Response<proto::DeleteBookResponse>, Status> {
+        let claims = authorize_metadata(&self.config, &request)?;
+        let id = request.into_inner().id;
+        if !self.repo.delete(&id, None, &claims.sub).await.map_err(AppError::from)? {
+            return Err(AppError::NotFound(format!("Book {} not found", id)).into());
+        }
+        Ok(Response::new(proto::DeleteBookResponse {}))
+    }
+
+    type StreamListStream = BookStream;
+
+    async fn stream_list(
+        &self,
+        _request: Request<proto::ListBooksRequest>,
+    ) -> Result<Response<Self::StreamListStream>, Status> {
+        // Página a página: el canal lleno frena las lecturas si el cliente va lento
+        let (tx, rx) = mpsc::channel(STREAM_PAGE_SIZE as usize);
+        let repo = self.repo.clone();
+        tokio::spawn(async move {
+            let mut after: Option<String> = None;
+            loop {
+                let page = match repo.list_page(after.as_deref(), STREAM_PAGE_SIZE).await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        let _ = tx.send(Err(AppError::from(e).into())).await;
+                        return;
+                    }
+                };
+                let last_page = (page.len() as i64) < STREAM_PAGE_SIZE;
+                after = page.last().map(|b| b.id.clone());
+                for book in page {
+                    // El cliente cerró el stream
+                    if tx.send(Ok(book.into())).await.is_err() {
+                        return;
+                    }
+                }
+                if last_page {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+/// Mismo JWT que las rutas REST protegidas, leído de los metadatos gRPC
+fn authorize_metadata<T>(config: &Config, request: &Request<T>) -> Result<Claims, AppError> {
+    authorize(&request.metadata().clone().into_headers(), &config.jwt_secret)
+}
+
+impl From<AppError> for Status {
+    fn from(e: AppError) -> Self {
+        let message = e.public_message();
+        match e {
+            AppError::NotFound(_)             => Status::not_found(message),
+            AppError::Validation(_)           => Status::invalid_argument(message),
+            AppError::BadRequest(_)           => Status::invalid_argument(message),
+            AppError::UnsupportedMediaType(_) => Status::invalid_argument(message),
+            AppError::Auth                    => Status::unauthenticated(message),
+            AppError::Forbidden(_)            => Status::permission_denied(message),
+            AppError::Conflict(_)             => Status::already_exists(message),
+            AppError::PreconditionFailed(_)   => Status::aborted(message),
+            AppError::Unprocessable(_)        => Status::failed_precondition(message),
+            AppError::FailedDependency(_)     => Status::aborted(message),
+            AppError::Unavailable             => Status::unavailable(message),
+            AppError::RateLimited(_)          => Status::resource_exhausted(message),
+            AppError::Db(_)                   => {
+                tracing::error!("DB error: {:?}", e);
+                Status::internal(message)
+            }
+        }
+    }
+}
+
+impl From<Book> for proto::Book {
+    fn from(b: Book) -> Self {
+        Self {
+            id: b.id,
+            title: b.title,
+            author: b.author,
+            published_year: b.published_year,
+            isbn: b.isbn,
+            created_at: b.created_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            updated_at: b.updated_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            updated_by: b.updated_by,
+            version: b.version,
+        }
+    }
+}
+
+impl From<Vec<Book>> for proto::ListBooksResponse {
+    fn from(books: Vec<Book>) -> Self {
+        Self {
+            books: books.into_iter().map(Into::into).collect(),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+// src/grpc/mod.rs
+pub mod catalog_service;
+pub mod rate_limit;
+pub mod telemetry;
+
+pub mod proto {
+    tonic::include_proto!("library.catalog.v1");
+}
@@ -0,0 +1,44 @@
+//! Límite de peticiones del servidor gRPC, con el mismo cubo de fichas que
+//! las rutas HTTP: por `sub` si la llamada trae un JWT válido en los
+//! metadatos y, si no, por la IP de la conexión.
+
+// `tonic::Status` es grande, pero es el tipo de error que impone `Interceptor`
+#![allow(clippy::result_large_err)]
+
+use std::sync::Arc;
+use tonic::{metadata::MetadataValue, service::Interceptor, Request, Status};
+
+use crate::{
+    config::Config,
+    error::AppError,
+    middleware::rate_limit::{ceil_secs, RateLimiter},
+};
+
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+    config: Arc<Config>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: Arc<RateLimiter>, config: Arc<Config>) -> Self {
+        Self { limiter, config }
+    }
+}
+
+impl Interceptor for RateLimit {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        let peer = request.remote_addr().map(|addr| addr.ip());
+        let key = self.limiter.client_key(peer, &headers, &self.config.jwt_secret);
+        match self.limiter.check(&key) {
+            Some(quota) if !quota.allowed => {
+                let retry_after = ceil_secs(quota.retry_after);
+                let mut status = Status::from(AppError::RateLimited(retry_after));
+                status.metadata_mut().insert("retry-after", MetadataValue::from(retry_after));
+                Err(status)
+            }
+            _ => Ok(request),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Capa tower para el servidor tonic: el equivalente de `trace_request` y
+//! `track_metrics` en HTTP. Abre el span de servidor de cada llamada y la
+//! mide hasta que termina de enviarse la respuesta, stream incluido.
+
+use http_body::{Body, Frame};
+use pin_project_lite::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+use tonic::{
+    codegen::http::{HeaderMap, Request, Response},
+    Code,
+};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{field::Empty, Instrument, Span};
+
+use crate::{metrics::Metrics, telemetry};
+
+const GRPC_STATUS: &str = "grpc-status";
+
+#[derive(Clone)]
+pub struct GrpcTelemetryLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl GrpcTelemetryLayer {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for GrpcTelemetryLayer {
+    type Service = GrpcTelemetry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcTelemetry { inner, metrics: self.metrics.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcTelemetry<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for GrpcTelemetry<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<TrackedBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        // El clon listo es `self.inner`: se usa ese y se deja el nuevo en su sitio
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let path = req.uri().path().to_string();
+        let (service, method) = path.trim_start_matches('/').split_once('/').unwrap_or(("", ""));
+        let span = tracing::info_span!(
+            "grpc",
+            otel.name = %path.trim_start_matches('/'),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            rpc.system = "grpc",
+            rpc.service = service,
+            rpc.method = method,
+            rpc.grpc.status_code = Empty,
+        );
+        telemetry::set_remote_parent(&span, req.headers());
+        let mut call = Call { metrics: self.metrics.clone(), method: path, start: Instant::now(), span, code: None };
+
+        Box::pin(async move {
+            let result = inner.call(req).instrument(call.span.clone()).await;
+            match result {
+                Ok(res) => {
+                    // Un error antes del primer mensaje llega sin cuerpo, con el estado en las cabeceras
+                    call.observe_headers(res.headers());
+                    Ok(res.map(|inner| TrackedBody { inner, call }))
+                }
+                Err(e) => {
+                    call.set_code(Code::Unknown);
+                    Err(e)
+                }
+            }
+        })
+    }
+}
+
+/// Una llamada en curso; se registra al soltarla, cuando ya no queda nada
+/// que enviar o el cliente se ha ido
+struct Call {
+    metrics: Arc<Metrics>,
+    method: String,
+    start: Instant,
+    span: Span,
+    /// `None` hasta ver `grpc-status`
+    code: Option<Code>,
+}
+
+impl Call {
+    fn observe_headers(&mut self, headers: &HeaderMap) {
+        if let Some(status) = headers.get(GRPC_STATUS) {
+            self.set_code(Code::from_bytes(status.as_bytes()));
+        }
+    }
+
+    fn set_code(&mut self, code: Code) {
+        self.code = Some(code);
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        // Sin estado final, el stream se cortó antes de terminar
+        let code = self.code.unwrap_or(Code::Cancelled);
+        self.span.record("rpc.grpc.status_code", code as i32);
+        // Los códigos que la convención de OpenTelemetry trata como fallo del servidor
+        if matches!(
+            code,
+            Code::Unknown | Code::DeadlineExceeded | Code::Unimplemented | Code::Internal | Code::Unavailable | Code::DataLoss
+        ) {
+            self.span.record("otel.status_code", "ERROR");
+        }
+        // Una ruta inventada por el cliente abriría una serie nueva
+        let method = if code == Code::Unimplemented { "unmatched" } else { &self.method };
+        self.metrics.observe_grpc(method, &format!("{:?}", code), self.start.elapsed());
+    }
+}
+
+pin_project! {
+    /// Cuerpo de la respuesta que lee `grpc-status` de los trailers
+    pub struct TrackedBody<B> {
+        #[pin]
+        inner: B,
+        call: Call,
+    }
+}
+
+impl<B: Body> Body for TrackedBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = this.inner.poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &frame {
+            if let Some(trailers) = frame.trailers_ref() {
+                this.call.observe_headers(trailers);
+            }
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GrpcTelemetryLayer;
+    use crate::{
+        config::Config,
+        grpc::{
+            catalog_service::Catalog,
+            proto::{catalog_service_client::CatalogServiceClient, GetBookRequest},
+        },
+        infra::memory_book_repository::InMemoryBookRepository,
+        metrics::{metered_book_repository::MeteredBookRepository, Metrics},
+        telemetry::Recorder,
+    };
+    use opentelemetry::{trace::SpanId, Value};
+    use std::{sync::Arc, time::Duration};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // `current_thread`: el subscriber por defecto es del hilo y el servidor corre en él
+    #[tokio::test(flavor = "current_thread")]
+    async fn calls_open_a_server_span_around_the_repository_spans() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.layer()));
+
+        let metrics = Arc::new(Metrics::new());
+        let repo = Arc::new(MeteredBookRepository::new(Arc::new(InMemoryBookRepository::new()), metrics.clone()));
+        let config = Arc::new(Config::new("sqlite::memory:".into(), "secret".into()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .layer(GrpcTelemetryLayer::new(metrics))
+                .add_service(Catalog::new(repo, config).into_service())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = CatalogServiceClient::connect(format!("http://{}", addr)).await.unwrap();
+        let mut req = tonic::Request::new(GetBookRequest { id: "missing".into() });
+        req.metadata_mut().insert("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".parse().unwrap());
+        assert_eq!(client.get(req).await.unwrap_err().code(), tonic::Code::NotFound);
+
+        // El span se cierra al soltar la respuesta, algo después de que llegue al cliente
+        let mut spans = recorder.spans();
+        for _ in 0..50 {
+            if spans.iter().any(|s| s.name == "library.catalog.v1.CatalogService/Get") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            spans = recorder.spans();
+        }
+        let named = |name: &str| spans.iter().find(|s| s.name == name).unwrap_or_else(|| panic!("{}", name));
+        let call = named("library.catalog.v1.CatalogService/Get");
+        let lookup = named("BookRepository.get_by_id");
+
+        assert_eq!(call.span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(call.parent_span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+        assert_eq!(lookup.parent_span_id, call.span_context.span_id());
+        let status = call.attributes.iter().find(|kv| kv.key.as_str() == "rpc.grpc.status_code").unwrap();
+        assert_eq!(status.value, Value::I64(5));
+    }
+}
@@ -0,0 +1,24 @@
+use axum::{Extension, Json};
+use std::sync::Arc;
+
+use crate::{
+    app::migrations::{MigrationStatus, SchemaMigrations},
+    error::AppError,
+};
+
+#[utoipa::path(
+    get,
+    path = "/admin/migrations",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Migraciones aplicadas, pendientes y desconocidas para este binario", body = MigrationStatus),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 403, description = "Requiere rol `admin`", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn migrations(
+    Extension(schema): Extension<Arc<dyn SchemaMigrations>>,
+) -> Result<Json<MigrationStatus>, AppError> {
+    Ok(Json(schema.status().await?))
+}
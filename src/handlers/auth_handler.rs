@@ -1,37 +1,53 @@
-use axum::Json;
-use serde::{Deserialize, Serialize};
+use axum::{extract::State, Json};
+use serde::Deserialize;
 use jsonwebtoken::{encode, EncodingKey, Header};
 use chrono::Utc;
-use crate::{config::jwt_secret, error::AppError};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use crate::{
+    config::Config,
+    error::AppError,
+    extract::JsonBody,
+    middleware::auth::{Claims, Role},
+};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct Login {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Claims {
-    sub: String,
-    exp: usize,
-}
-
-pub async fn login(Json(payload): Json<Login>) -> Result<Json<String>, AppError> {
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "auth",
+    request_body = Login,
+    responses(
+        (status = 200, description = "JWT firmado", body = String),
+        (status = 401, description = "Credenciales inválidas", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 429, description = "Demasiados intentos desde esta IP", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn login(
+    State(config): State<Arc<Config>>,
+    JsonBody(payload): JsonBody<Login>,
+) -> Result<Json<String>, AppError> {
     if payload.username != "admin" || payload.password != "password" {
         return Err(AppError::Auth);
     }
 
+    let ttl = chrono::Duration::from_std(config.token_ttl).map_err(|e| AppError::Db(e.into()))?;
     let expiration = Utc::now()
-        .checked_add_signed(chrono::Duration::hours(1))
+        .checked_add_signed(ttl)
         .expect("valid timestamp")
         .timestamp() as usize;
 
-    let claims = Claims { sub: payload.username.clone(), exp: expiration };
+    let claims = Claims { sub: payload.username.clone(), exp: expiration, role: Role::Admin };
 
     let token = encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(jwt_secret().as_ref()),
+        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
     )
         .map_err(|e| AppError::Db(e.into()))?;
 
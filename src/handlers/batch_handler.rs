@@ -0,0 +1,215 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    app::book_repository::{BookRepository, BookWrite},
+    domain::book::Book,
+    error::{AppError, ErrorBody},
+    extract::JsonBody,
+    handlers::book_handler::CreateBook,
+    middleware::auth::Claims,
+};
+
+/// Máximo de operaciones aceptadas en un lote
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+#[derive(Deserialize, ToSchema, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    /// Todo o nada: si una operación falla se deshacen todas
+    #[default]
+    Atomic,
+    /// Cada operación se confirma por separado
+    BestEffort,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Create {
+        body: CreateBook,
+    },
+    /// Reemplazo completo, como `PUT /books/{id}`
+    Update {
+        id: String,
+        body: CreateBook,
+        /// Equivalente a `If-Match`
+        #[serde(default)]
+        version: Option<i64>,
+    },
+    Delete {
+        id: String,
+        /// Equivalente a `If-Match`
+        #[serde(default)]
+        version: Option<i64>,
+    },
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchRequest {
+    #[serde(default)]
+    pub mode: BatchMode,
+    pub operations: Vec<BatchOperation>,
+}
+
+/// Resultado de una operación, con el mismo status y cuerpo que la ruta individual
+#[derive(Serialize, ToSchema)]
+pub struct BatchItemResult {
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Book>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchResponse {
+    /// `false` si el lote atómico se deshizo
+    pub committed: bool,
+    pub results: Vec<BatchItemResult>,
+}
+
+impl BatchItemResult {
+    fn ok(status: StatusCode, body: Option<Book>) -> Self {
+        Self { status: status.as_u16(), body, error: None }
+    }
+
+    fn err(e: AppError) -> Self {
+        if let AppError::Db(_) = e {
+            tracing::error!("DB error: {:?}", e);
+        }
+        Self { status: e.status().as_u16(), body: None, error: Some(e.to_problem()) }
+    }
+
+    fn rolled_back() -> Self {
+        Self::err(AppError::FailedDependency(
+            "Batch rolled back because another operation failed".into(),
+        ))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/books/batch",
+    tag = "books",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Repetir la petición con la misma clave devuelve la respuesta original")),
+    request_body = BatchRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Resultado por operación", body = BatchResponse),
+        (status = 400, description = "Lote vacío o demasiado grande", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn batch_books<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<BatchRequest>,
+) -> Result<Json<BatchResponse>, AppError> {
+    // Un lote vacío no tiene nada que confirmar: seguramente es un error del cliente
+    if payload.operations.is_empty() || payload.operations.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "A batch needs between 1 and {} operations",
+            MAX_BATCH_SIZE
+        )));
+    }
+    let atomic = payload.mode == BatchMode::Atomic;
+
+    // Los updates reemplazan libros existentes: se leen todos de una vez
+    let update_ids: Vec<String> = payload
+        .operations
+        .iter()
+        .filter_map(|op| match op {
+            BatchOperation::Update { id, .. } => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut existing: HashMap<String, Book> = repo
+        .get_by_ids(&update_ids)
+        .await?
+        .into_iter()
+        .map(|b| (b.id.clone(), b))
+        .collect();
+
+    let prepared: Vec<Result<(BookWrite, StatusCode), AppError>> = payload
+        .operations
+        .into_iter()
+        .map(|op| prepare(op, &claims.sub, &mut existing))
+        .collect();
+
+    if atomic && prepared.iter().any(Result::is_err) {
+        let results = prepared
+            .into_iter()
+            .map(|p| match p {
+                Ok(_) => BatchItemResult::rolled_back(),
+                Err(e) => BatchItemResult::err(e),
+            })
+            .collect();
+        return Ok(Json(BatchResponse { committed: false, results }));
+    }
+
+    let mut statuses = Vec::new();
+    let mut writes = Vec::new();
+    for (write, status) in prepared.iter().filter_map(|p| p.as_ref().ok()) {
+        writes.push(write.clone());
+        statuses.push(*status);
+    }
+    let batch = repo.write_batch(writes, atomic).await?;
+    let mut executed = batch.results.into_iter().zip(statuses);
+
+    let results = prepared
+        .into_iter()
+        .map(|p| match p {
+            Err(e) => BatchItemResult::err(e),
+            Ok(_) => match executed.next() {
+                Some((Ok(_), _)) if !batch.committed => BatchItemResult::rolled_back(),
+                Some((Ok(book), status)) => BatchItemResult::ok(status, book),
+                Some((Err(e), _)) => BatchItemResult::err(e.into()),
+                // Un lote atómico se detiene en el primer error
+                None => BatchItemResult::rolled_back(),
+            },
+        })
+        .collect();
+
+    Ok(Json(BatchResponse { committed: batch.committed, results }))
+}
+
+/// Valida una operación y la traduce a la escritura que ejecutará el repositorio
+fn prepare(
+    op: BatchOperation,
+    actor: &str,
+    existing: &mut HashMap<String, Book>,
+) -> Result<(BookWrite, StatusCode), AppError> {
+    match op {
+        BatchOperation::Create { body } => {
+            body.validate()?;
+            let mut book = body.into_book();
+            book.updated_by = Some(actor.to_string());
+            Ok((BookWrite::Create(book), StatusCode::CREATED))
+        }
+        BatchOperation::Update { id, body, version } => {
+            body.validate()?;
+            let mut book = existing
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
+            if version.is_some_and(|v| v != book.version) {
+                return Err(AppError::PreconditionFailed(format!("Book {} has been modified", id)));
+            }
+            body.replace(&mut book);
+            book.updated_by = Some(actor.to_string());
+            // Un segundo update del mismo libro en el lote parte de esta versión
+            let mut next = book.clone();
+            next.version += 1;
+            existing.insert(id, next);
+            Ok((BookWrite::Update(book), StatusCode::OK))
+        }
+        BatchOperation::Delete { id, version } => Ok((
+            BookWrite::Delete { id, expected_version: version, actor: actor.to_string() },
+            StatusCode::NO_CONTENT,
+        )),
+    }
+}
@@ -1,19 +1,33 @@
 use axum::{
-    Json,
+    Extension, Json,
+    body::Bytes,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, LOCATION},
+    },
+    response::{IntoResponse, Redirect, Response},
 };
+use json_patch::{Patch, PatchErrorKind};
 use serde::Deserialize;
-use std::{sync::Arc, borrow::Cow};
-use validator::{Validate, ValidationErrors};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use async_graphql::InputObject;
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
 use crate::{
-    domain::book::Book,
-    app::book_repository::BookRepository,
+    domain::{book::Book, duplicates::{find_duplicates, is_valid_isbn}},
+    app::book_repository::{BookFilter, BookRepository},
     error::AppError,
+    extract::JsonBody,
+    handlers::duplicates_handler::POSSIBLE_DUPLICATES,
+    middleware::auth::Claims,
 };
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ToSchema, InputObject)]
+#[graphql(name = "CreateBookInput")]
 pub struct CreateBook {
     #[validate(length(min = 1, message = "Title cannot be empty"))]
     pub title: String,
@@ -23,9 +37,13 @@ pub struct CreateBook {
 
     #[validate(range(min = 0, message = "Published year must be positive"))]
     pub published_year: Option<i32>,
+
+    #[validate(custom(function = "validate_isbn"))]
+    pub isbn: Option<String>,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ToSchema, InputObject)]
+#[graphql(name = "UpdateBookInput")]
 pub struct UpdateBook {
     #[validate(length(min = 1, message = "Title cannot be empty"))]
     pub title: Option<String>,
@@ -35,14 +53,85 @@ pub struct UpdateBook {
 
     #[validate(range(min = 0, message = "Published year must be positive"))]
     pub published_year: Option<i32>,
+
+    #[validate(custom(function = "validate_isbn"))]
+    pub isbn: Option<String>,
+}
+
+fn validate_isbn(isbn: &str) -> Result<(), ValidationError> {
+    if is_valid_isbn(isbn) {
+        return Ok(());
+    }
+    let mut err = ValidationError::new("isbn");
+    err.message = Some("ISBN must be a valid ISBN-10 or ISBN-13".into());
+    Err(err)
+}
+
+impl CreateBook {
+    /// Libro nuevo con los campos del payload
+    pub(crate) fn into_book(self) -> Book {
+        let mut book = Book::new(self.title, self.author, self.published_year);
+        book.isbn = self.isbn;
+        book
+    }
+
+    /// Reemplaza los campos editables de `book`; `published_year` ausente lo deja en `null`
+    pub(crate) fn replace(self, book: &mut Book) {
+        book.title = self.title;
+        book.author = self.author;
+        book.published_year = self.published_year;
+        book.isbn = self.isbn;
+    }
+}
+
+impl UpdateBook {
+    /// Copia sobre `book` los campos presentes en el payload
+    pub(crate) fn apply(self, book: &mut Book) {
+        if let Some(title) = self.title {
+            book.title = title;
+        }
+        if let Some(author) = self.author {
+            book.author = author;
+        }
+        if self.published_year.is_some() {
+            book.published_year = self.published_year;
+        }
+        if self.isbn.is_some() {
+            book.isbn = self.isbn;
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct SearchParams {
+    /// Coincidencia parcial sobre el título
     pub title: Option<String>,
+    /// Coincidencia parcial sobre el autor
     pub author: Option<String>,
+    /// RFC 3339; solo libros creados o modificados desde ese instante
+    pub updated_since: Option<DateTime<Utc>>,
+    /// RFC 3339; libros creados en ese instante o después
+    pub created_from: Option<DateTime<Utc>>,
+    /// RFC 3339; libros creados antes de ese instante
+    pub created_to: Option<DateTime<Utc>>,
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct DeleteParams {
+    /// Responder 204 también si el libro no existe
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/books",
+    tag = "books",
+    responses(
+        (status = 200, description = "Todos los libros", body = [Book]),
+        (status = 500, description = "Error interno", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
 pub async fn get_books<R: BookRepository>(
     State(repo): State<Arc<R>>,
 ) -> Result<Json<Vec<Book>>, AppError> {
@@ -50,106 +139,341 @@ pub async fn get_books<R: BookRepository>(
     Ok(Json(books))
 }
 
+#[utoipa::path(
+    get,
+    path = "/books/{id}",
+    tag = "books",
+    params(
+        ("id" = String, Path, description = "Id del libro"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag conocido por el cliente"),
+    ),
+    responses(
+        (status = 200, description = "Libro encontrado", body = Book,
+            headers(("ETag" = String, description = "Versión del libro"))),
+        (status = 308, description = "El libro se fusionó en otro",
+            headers(("Location" = String, description = "Ruta del libro que lo absorbió"))),
+        (status = 304, description = "El ETag enviado sigue vigente"),
+        (status = 404, description = "Libro inexistente", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
 pub async fn get_book<R: BookRepository>(
     State(repo): State<Arc<R>>,
     Path(id): Path<String>,
-) -> Result<Json<Book>, AppError> {
-    let book = repo
-        .get_by_id(&id)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
-    Ok(Json(book))
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let Some(book) = repo.get_by_id(&id).await? else {
+        // Los ids de libros fusionados siguen resolviendo al libro resultante
+        return match repo.resolve_redirect(&id).await? {
+            Some(new_id) => Ok(Redirect::permanent(&format!("/books/{}", new_id)).into_response()),
+            None => Err(AppError::NotFound(format!("Book {} not found", id))),
+        };
+    };
+    if headers.get(IF_NONE_MATCH).is_some_and(|h| etag_matches(h, &book, true)) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag(&book))]).into_response());
+    }
+    Ok(([(ETAG, etag(&book))], Json(book)).into_response())
 }
 
+#[utoipa::path(
+    post,
+    path = "/books",
+    tag = "books",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Repetir la petición con la misma clave devuelve la respuesta original")),
+    request_body = CreateBook,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "Libro creado", body = Book,
+            headers(
+                ("Location" = String, description = "Ruta del libro creado"),
+                ("ETag" = String, description = "Versión del libro"),
+                ("X-Possible-Duplicates" = String, description = "Ids de libros parecidos ya catalogados, separados por comas"),
+            )),
+        (status = 400, description = "Payload inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 409, description = "Petición con la misma clave en curso", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 422, description = "Clave reutilizada con otro cuerpo", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
 pub async fn post_book<R: BookRepository>(
     State(repo): State<Arc<R>>,
-    Json(payload): Json<CreateBook>,
-) -> Result<(StatusCode, Json<Book>), AppError> {
-    if let Err(e) = payload.validate() {
-        return Err(AppError::Validation(flatten_errors(e)));
-    }
-    let book = Book::new(payload.title, payload.author, payload.published_year);
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<CreateBook>,
+) -> Result<Response, AppError> {
+    payload.validate()?;
+    let mut book = payload.into_book();
+    book.updated_by = Some(claims.sub);
+    // Se crea igualmente: el aviso deja la decisión al catalogador
+    let duplicates = find_duplicates(&book, &repo.duplicate_candidates(&book).await?);
     let saved = repo.create(book).await?;
-    Ok((StatusCode::CREATED, Json(saved)))
+
+    let location = format!("/books/{}", saved.id);
+    let mut res = (StatusCode::CREATED, [(ETAG, etag(&saved))], Json(saved)).into_response();
+    if let Ok(value) = HeaderValue::from_str(&location) {
+        res.headers_mut().insert(LOCATION, value);
+    }
+    if !duplicates.is_empty() {
+        let ids: Vec<&str> = duplicates.iter().map(|d| d.duplicate_id.as_str()).collect();
+        if let Ok(value) = HeaderValue::from_str(&ids.join(",")) {
+            res.headers_mut().insert(POSSIBLE_DUPLICATES.clone(), value);
+        }
+    }
+    Ok(res)
 }
 
+#[utoipa::path(
+    put,
+    path = "/books/{id}",
+    tag = "books",
+    params(
+        ("id" = String, Path, description = "Id del libro"),
+        ("If-Match" = Option<String>, Header, description = "ETag leído previamente"),
+    ),
+    request_body = CreateBook,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Libro reemplazado", body = Book,
+            headers(("ETag" = String, description = "Nueva versión del libro"))),
+        (status = 400, description = "Payload inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "Libro inexistente", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 412, description = "El libro cambió desde que se leyó", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
 pub async fn put_book<R: BookRepository>(
     State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
-    Json(payload): Json<UpdateBook>,
-) -> Result<Json<Book>, AppError> {
-    if let Err(e) = payload.validate() {
-        return Err(AppError::Validation(flatten_errors(e)));
-    }
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<CreateBook>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
     let mut book = repo
         .get_by_id(&id)
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
-    if let Some(title) = payload.title {
-        book.title = title;
+    check_if_match(&headers, &book)?;
+    payload.replace(&mut book);
+    book.updated_by = Some(claims.sub);
+    // `update` es condicional a la versión leída: una escritura concurrente da 412
+    let updated = repo.update(book).await?;
+    Ok(([(ETAG, etag(&updated))], Json(updated)))
+}
+
+pub const MERGE_PATCH_JSON: &str = "application/merge-patch+json";
+pub const JSON_PATCH_JSON: &str = "application/json-patch+json";
+
+/// Campos de `Book` que un patch puede tocar
+const PATCHABLE_FIELDS: [&str; 4] = ["title", "author", "published_year", "isbn"];
+
+#[utoipa::path(
+    patch,
+    path = "/books/{id}",
+    tag = "books",
+    params(
+        ("id" = String, Path, description = "Id del libro"),
+        ("If-Match" = Option<String>, Header, description = "ETag leído previamente"),
+    ),
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "JSON Merge Patch (RFC 7396), o JSON Patch (RFC 6902) con `Content-Type: application/json-patch+json`",
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Libro modificado", body = Book,
+            headers(("ETag" = String, description = "Nueva versión del libro"))),
+        (status = 400, description = "Patch mal formado o resultado inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "Libro inexistente", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 409, description = "Falló una operación `test`", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 412, description = "El libro cambió desde que se leyó", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 415, description = "Content-Type no soportado", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 422, description = "El patch no se puede aplicar", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn patch_book<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let media_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(';').next())
+        .map(|h| h.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    if media_type != MERGE_PATCH_JSON && media_type != JSON_PATCH_JSON {
+        return Err(AppError::UnsupportedMediaType(format!(
+            "Expected {} or {}",
+            MERGE_PATCH_JSON, JSON_PATCH_JSON
+        )));
     }
-    if let Some(author) = payload.author {
-        book.author = author;
+    let patch: Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Malformed patch: {}", e)))?;
+
+    let mut book = repo
+        .get_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
+    check_if_match(&headers, &book)?;
+
+    let mut doc = json!({
+        "title": book.title,
+        "author": book.author,
+        "published_year": book.published_year,
+        "isbn": book.isbn,
+    });
+    if media_type == MERGE_PATCH_JSON {
+        json_patch::merge(&mut doc, &patch);
+    } else {
+        let patch: Patch = serde_json::from_value(patch)
+            .map_err(|e| AppError::BadRequest(format!("Malformed patch: {}", e)))?;
+        json_patch::patch(&mut doc, &patch).map_err(|e| match e.kind {
+            PatchErrorKind::TestFailed => AppError::Conflict(e.to_string()),
+            _ => AppError::Unprocessable(e.to_string()),
+        })?;
     }
-    if payload.published_year.is_some() {
-        book.published_year = payload.published_year;
+
+    // El resultado se valida con las mismas reglas que `CreateBook`
+    if let Some(field) = doc
+        .as_object()
+        .and_then(|o| o.keys().find(|k| !PATCHABLE_FIELDS.contains(&k.as_str())))
+    {
+        return Err(AppError::Unprocessable(format!("Field {} cannot be patched", field)));
     }
+    let patched: CreateBook = serde_json::from_value(doc)
+        .map_err(|e| AppError::BadRequest(format!("Patched book is invalid: {}", e)))?;
+    patched.validate()?;
+    patched.replace(&mut book);
+    book.updated_by = Some(claims.sub);
+
     let updated = repo.update(book).await?;
-    Ok(Json(updated))
+    Ok(([(ETAG, etag(&updated))], Json(updated)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/books/{id}",
+    tag = "books",
+    params(
+        ("id" = String, Path, description = "Id del libro"),
+        ("If-Match" = Option<String>, Header, description = "ETag leído previamente"),
+        DeleteParams,
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Libro eliminado (o inexistente con `idempotent=true`)"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "Libro inexistente", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 412, description = "El libro cambió desde que se leyó", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
 pub async fn delete_book<R: BookRepository>(
     State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
+    Query(params): Query<DeleteParams>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, AppError> {
-    repo.delete(&id).await?;
+    // Se lee antes para comprobar el `If-Match` contra la versión actual
+    let deleted = match repo.get_by_id(&id).await? {
+        Some(book) => {
+            check_if_match(&headers, &book)?;
+            let expected_version = headers.get(IF_MATCH).map(|_| book.version);
+            repo.delete(&id, expected_version, &claims.sub).await?
+        }
+        None => false,
+    };
+    if !deleted && !params.idempotent {
+        return Err(AppError::NotFound(format!("Book {} not found", id)));
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    get,
+    path = "/books/search",
+    tag = "books",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Libros que coinciden", body = [Book]),
+        (status = 400, description = "Fecha mal formada o rango invertido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 500, description = "Error interno", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
 pub async fn search_books<R: BookRepository>(
     State(repo): State<Arc<R>>,
     Query(params): Query<SearchParams>,
 ) -> Result<Json<Vec<Book>>, AppError> {
-    let title = params.title.as_deref();
-    let author = params.author.as_deref();
-    let books = repo.search(title, author).await?;
+    if let (Some(from), Some(to)) = (params.created_from, params.created_to) {
+        if from >= to {
+            return Err(AppError::BadRequest("created_from must be earlier than created_to".into()));
+        }
+    }
+    let filter = BookFilter {
+        title: params.title,
+        author: params.author,
+        updated_since: params.updated_since,
+        created_from: params.created_from,
+        created_to: params.created_to,
+    };
+    let books = repo.search(&filter).await?;
     Ok(Json(books))
 }
 
-fn flatten_errors(e: ValidationErrors) -> String {
-    e.field_errors()
-        .iter()
-        .flat_map(|(field, errs)| {
-            errs.iter().map(move |err| {
-                let msg = err
-                    .message
-                    .clone() // clonamos el Option<Cow<str>>
-                    .unwrap_or_else(|| Cow::Borrowed("invalid"))    // si no hay mensaje, usamos "invalid"
-                    .into_owned(); // obtenemos un String
-                format!("{}: {}", field, msg)
-            })
+pub(crate) fn etag(book: &Book) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", book.version)).expect("un número es un ETag válido")
+}
+
+/// `true` si alguna etiqueta es `*` o la versión actual. `If-Match` usa la
+/// comparación fuerte (RFC 9110 §13.1.1): una etiqueta `W/` nunca coincide;
+/// `If-None-Match` usa la débil y la acepta.
+fn etag_matches(header: &HeaderValue, book: &Book, weak: bool) -> bool {
+    let current = etag(book);
+    let current = current.to_str().unwrap_or_default();
+    header.to_str().is_ok_and(|tags| {
+        tags.split(',').map(str::trim).any(|tag| {
+            let tag = match tag.strip_prefix("W/") {
+                Some(opaque) if weak => opaque,
+                Some(_) => return false,
+                None => tag,
+            };
+            tag == "*" || tag == current
         })
-        .collect::<Vec<_>>()
-        .join(", ")
+    })
+}
+
+pub(crate) fn check_if_match(headers: &HeaderMap, book: &Book) -> Result<(), AppError> {
+    match headers.get(IF_MATCH) {
+        Some(h) if !etag_matches(h, book, false) => Err(AppError::PreconditionFailed(format!(
+            "Book {} has been modified",
+            book.id
+        ))),
+        _ => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{CreateBook, flatten_errors};
-    use validator::Validate;
+    use super::{etag_matches, Book};
+    use axum::http::HeaderValue;
 
     #[test]
-    fn flatten_errors_formats_validator_messages() {
-        let bad = CreateBook {
-            title: "".into(),
-            author: "".into(),
-            published_year: Some(-1),
-        };
-        let errs = bad.validate().expect_err("debe fallar validación");
-        let out = flatten_errors(errs);
-        assert!(out.contains("title: Title cannot be empty"));
-        assert!(out.contains("author: Author cannot be empty"));
-        assert!(out.contains("published_year: Published year must be positive"));
-        assert!(!out.contains('\n'));
+    fn if_match_compares_strongly_and_if_none_match_weakly() {
+        let book = Book::new("Dune".into(), "Frank Herbert".into(), None);
+        let tag = |v: String| HeaderValue::from_str(&v).unwrap();
+        let strong = tag(format!("\"{}\"", book.version));
+        let weak = tag(format!("W/\"{}\"", book.version));
+
+        assert!(etag_matches(&strong, &book, false));
+        assert!(!etag_matches(&weak, &book, false));
+        assert!(etag_matches(&tag(format!("\"0\", {}", strong.to_str().unwrap())), &book, false));
+        assert!(etag_matches(&HeaderValue::from_static("*"), &book, false));
+
+        assert!(etag_matches(&weak, &book, true));
+        assert!(etag_matches(&strong, &book, true));
+        assert!(!etag_matches(&HeaderValue::from_static("W/\"999\""), &book, true));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,123 @@
+use axum::{response::Html, Json};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{
+    app::{audit_log, health, migrations},
+    domain::{book::Book, duplicates},
+    error::ErrorBody,
+    handlers::{
+        admin_handler, auth_handler, batch_handler, book_handler, duplicates_handler, health_handler,
+        history_handler, metrics_handler, trash_handler,
+    },
+};
+
+/// Especificación OpenAPI generada a partir de los handlers
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "library_api", description = "API REST para gestionar una biblioteca"),
+    paths(
+        auth_handler::login,
+        book_handler::get_books,
+        book_handler::get_book,
+        book_handler::post_book,
+        book_handler::put_book,
+        book_handler::patch_book,
+        book_handler::delete_book,
+        book_handler::search_books,
+        batch_handler::batch_books,
+        duplicates_handler::get_duplicates,
+        duplicates_handler::merge_book,
+        history_handler::book_history,
+        history_handler::revert_book,
+        trash_handler::list_trash,
+        trash_handler::restore_book,
+        trash_handler::purge_book,
+        admin_handler::migrations,
+        health_handler::healthz,
+        health_handler::readyz,
+        health_handler::status,
+        metrics_handler::metrics,
+    ),
+    components(schemas(
+        Book,
+        ErrorBody,
+        auth_handler::Login,
+        book_handler::CreateBook,
+        book_handler::UpdateBook,
+        batch_handler::BatchMode,
+        batch_handler::BatchOperation,
+        batch_handler::BatchRequest,
+        batch_handler::BatchItemResult,
+        batch_handler::BatchResponse,
+        duplicates::DuplicateReason,
+        duplicates::DuplicateMatch,
+        duplicates_handler::MergeBook,
+        audit_log::RevisionAction,
+        audit_log::BookRevision,
+        migrations::AppliedMigration,
+        migrations::PendingMigration,
+        migrations::MigrationStatus,
+        health::PoolStats,
+        health::StorageStats,
+        health_handler::Liveness,
+        health_handler::Readiness,
+        health_handler::ServiceStatus,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Obtención de tokens JWT"),
+        (name = "books", description = "Catálogo de libros"),
+        (name = "trash", description = "Papelera de libros borrados; solo administradores"),
+        (name = "admin", description = "Operación del servicio; solo administradores"),
+        (name = "health", description = "Sondas de vida y disponibilidad y métricas para el orquestador"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Swagger UI servido desde CDN apuntando a `/openapi.json`
+pub async fn docs() -> Html<&'static str> {
+    Html(DOCS_HTML)
+}
+
+const DOCS_HTML: &str = r##"<!doctype html>
+<html>
+<head>
+  <meta charset="utf-8" />
+  <title>library_api docs</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
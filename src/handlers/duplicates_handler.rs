@@ -0,0 +1,97 @@
+use axum::{
+    extract::{Path, State},
+    http::{header::ETAG, HeaderMap, HeaderName},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::{
+    app::book_repository::BookRepository,
+    domain::duplicates::{duplicate_pairs, DuplicateMatch},
+    error::AppError,
+    extract::JsonBody,
+    handlers::book_handler::{check_if_match, etag},
+    middleware::auth::Claims,
+};
+
+/// Cabecera de `POST /books` con los ids de libros parecidos ya existentes
+pub static POSSIBLE_DUPLICATES: HeaderName = HeaderName::from_static("x-possible-duplicates");
+
+#[derive(Deserialize, ToSchema)]
+pub struct MergeBook {
+    /// Libro que se absorbe; su id redirigirá al libro destino
+    pub source_id: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/books/duplicates",
+    tag = "books",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Parejas de libros que parecen el mismo", body = [DuplicateMatch]),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 500, description = "Error interno", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn get_duplicates<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+) -> Result<Json<Vec<DuplicateMatch>>, AppError> {
+    let candidates = repo.all_duplicate_candidates().await?;
+    Ok(Json(duplicate_pairs(&candidates)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/books/{id}/merge",
+    tag = "books",
+    params(
+        ("id" = String, Path, description = "Id del libro destino, el que se conserva"),
+        ("If-Match" = Option<String>, Header, description = "ETag leído previamente del destino"),
+    ),
+    request_body = MergeBook,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Libro resultante de la fusión", body = Book,
+            headers(("ETag" = String, description = "Nueva versión del libro"))),
+        (status = 400, description = "Origen y destino son el mismo libro", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "Origen o destino inexistente", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 412, description = "El destino cambió desde que se leyó", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn merge_book<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<MergeBook>,
+) -> Result<impl IntoResponse, AppError> {
+    if payload.source_id == id {
+        return Err(AppError::BadRequest("A book cannot be merged into itself".into()));
+    }
+    let mut target = repo
+        .get_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
+    check_if_match(&headers, &target)?;
+    let source = repo
+        .get_by_id(&payload.source_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Book {} not found", payload.source_id)))?;
+
+    // El destino conserva sus datos y solo hereda los que le faltan
+    if target.published_year.is_none() {
+        target.published_year = source.published_year;
+    }
+    if target.isbn.is_none() {
+        target.isbn = source.isbn;
+    }
+    target.updated_by = Some(claims.sub);
+
+    let merged = repo.merge(&source.id, target).await?;
+    Ok(([(ETAG, etag(&merged))], Json(merged)))
+}
@@ -0,0 +1,182 @@
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    http::GraphiQLSource,
+    Context, EmptySubscription, ErrorExtensions, Guard, Object, Schema,
+};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::Html,
+    Json,
+};
+use std::{collections::HashMap, sync::Arc};
+use validator::Validate;
+
+use crate::{
+    app::book_repository::{BookFilter, BookRepository, RepoError},
+    config::Config,
+    domain::book::Book,
+    error::AppError,
+    extract::JsonBody,
+    handlers::book_handler::{CreateBook, UpdateBook},
+    middleware::auth::{authorize, Claims},
+};
+
+/// Profundidad máxima de anidamiento aceptada en una consulta
+pub const MAX_DEPTH: usize = 8;
+/// Complejidad máxima (número ponderado de campos) aceptada en una consulta
+pub const MAX_COMPLEXITY: usize = 256;
+
+pub type LibrarySchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+type Repo = Arc<dyn BookRepository>;
+
+/// Construye el schema GraphQL sobre cualquier `BookRepository`
+pub fn build_schema(repo: Repo) -> LibrarySchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(DataLoader::new(BookLoader(repo.clone()), tokio::spawn))
+        .data(repo)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+pub async fn graphql(
+    State(schema): State<LibrarySchema>,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    JsonBody(request): JsonBody<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let mut request = request;
+    if let Ok(claims) = authorize(&headers, &config.jwt_secret) {
+        request = request.data(claims);
+    }
+    Json(schema.execute(request).await)
+}
+
+/// GraphiQL apuntando a `/graphql`
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+/// Mismo criterio que el middleware `auth` de las rutas REST protegidas
+struct RequireAuth;
+
+impl Guard for RequireAuth {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        match ctx.data_opt::<Claims>() {
+            Some(_) => Ok(()),
+            None => Err(to_graphql_error(AppError::Auth)),
+        }
+    }
+}
+
+/// Agrupa los `book(id:)` de una misma consulta en un solo `get_by_ids`
+pub struct BookLoader(Repo);
+
+impl Loader<String> for BookLoader {
+    type Value = Book;
+    type Error = Arc<RepoError>;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Book>, Self::Error> {
+        let books = self.0.get_by_ids(keys).await.map_err(Arc::new)?;
+        Ok(books.into_iter().map(|b| (b.id.clone(), b)).collect())
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn books(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Book>> {
+        let repo = ctx.data_unchecked::<Repo>();
+        repo.get_all().await.map_err(repo_error)
+    }
+
+    async fn book(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<Book>> {
+        let loader = ctx.data_unchecked::<DataLoader<BookLoader>>();
+        loader
+            .load_one(id)
+            .await
+            .map_err(|e| match Arc::try_unwrap(e) {
+                Ok(e) => repo_error(e),
+                Err(e) => to_graphql_error(AppError::Db(anyhow::anyhow!("{}", e))),
+            })
+    }
+
+    async fn search_books(
+        &self,
+        ctx: &Context<'_>,
+        title: Option<String>,
+        author: Option<String>,
+    ) -> async_graphql::Result<Vec<Book>> {
+        let repo = ctx.data_unchecked::<Repo>();
+        let filter = BookFilter { title, author, ..Default::default() };
+        repo.search(&filter)
+            .await
+            .map_err(repo_error)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    #[graphql(guard = "RequireAuth")]
+    async fn create_book(&self, ctx: &Context<'_>, input: CreateBook) -> async_graphql::Result<Book> {
+        input.validate().map_err(|e| to_graphql_error(e.into()))?;
+        let repo = ctx.data_unchecked::<Repo>();
+        let mut book = input.into_book();
+        book.updated_by = Some(ctx.data_unchecked::<Claims>().sub.clone());
+        repo.create(book).await.map_err(repo_error)
+    }
+
+    #[graphql(guard = "RequireAuth")]
+    async fn update_book(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        input: UpdateBook,
+    ) -> async_graphql::Result<Book> {
+        input.validate().map_err(|e| to_graphql_error(e.into()))?;
+        let repo = ctx.data_unchecked::<Repo>();
+        let mut book = repo
+            .get_by_id(&id)
+            .await
+            .map_err(repo_error)?
+            .ok_or_else(|| to_graphql_error(AppError::NotFound(format!("Book {} not found", id))))?;
+        input.apply(&mut book);
+        book.updated_by = Some(ctx.data_unchecked::<Claims>().sub.clone());
+        repo.update(book).await.map_err(repo_error)
+    }
+
+    /// `false` si el libro no existía
+    #[graphql(guard = "RequireAuth")]
+    async fn delete_book(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<bool> {
+        let repo = ctx.data_unchecked::<Repo>();
+        let actor = &ctx.data_unchecked::<Claims>().sub;
+        repo.delete(&id, None, actor).await.map_err(repo_error)
+    }
+}
+
+/// Traduce un `AppError` a un error GraphQL con el mismo `code` que el problem+json REST
+fn to_graphql_error(e: AppError) -> async_graphql::Error {
+    if let AppError::Db(_) = e {
+        tracing::error!("DB error: {:?}", e);
+    }
+    let problem = e.to_problem();
+    async_graphql::Error::new(problem.detail).extend_with(|_, ext| {
+        ext.set("code", problem.code);
+        if let Some(errors) = problem.errors {
+            let errors: async_graphql::indexmap::IndexMap<_, _> = errors
+                .into_iter()
+                .map(|(field, msgs)| (async_graphql::Name::new(field), msgs.into()))
+                .collect();
+            ext.set("errors", async_graphql::Value::Object(errors));
+        }
+    })
+}
+
+fn repo_error(e: RepoError) -> async_graphql::Error {
+    to_graphql_error(e.into())
+}
@@ -0,0 +1,116 @@
+use axum::{http::StatusCode, Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{sync::Arc, time::Instant};
+use utoipa::ToSchema;
+
+use crate::{
+    app::{
+        health::{StorageHealth, StorageStats},
+        migrations::SchemaMigrations,
+    },
+    error::AppError,
+};
+
+/// Momento en que se montó la app, para calcular el uptime
+#[derive(Clone, Copy)]
+pub struct StartedAt(pub Instant);
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Liveness {
+    /// Siempre `ok`
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    /// `ready` o `unavailable`
+    pub status: String,
+    /// `ok` o `unreachable`
+    pub database: String,
+    /// `current`, `pending`, `ahead` o `unknown` si no se pudo consultar
+    pub migrations: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ServiceStatus {
+    pub version: String,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    pub storage: StorageStats,
+    pub migrations_current: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "El proceso está vivo", body = Liveness))
+)]
+pub async fn healthz() -> Json<Liveness> {
+    Json(Liveness { status: "ok".into() })
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "BD alcanzable y esquema al día", body = Readiness),
+        (status = 503, description = "No debe recibir tráfico todavía", body = Readiness),
+    )
+)]
+pub async fn readyz(
+    Extension(health): Extension<Arc<dyn StorageHealth>>,
+    Extension(schema): Extension<Arc<dyn SchemaMigrations>>,
+) -> (StatusCode, Json<Readiness>) {
+    // La sonda es pública: el detalle de los errores solo va al log
+    let database = match health.ping().await {
+        Ok(()) => "ok",
+        Err(e) => {
+            tracing::warn!("Readiness: database unreachable: {}", e);
+            "unreachable"
+        }
+    };
+    let migrations = match schema.status().await {
+        Ok(status) if status.is_current() => "current",
+        Ok(status) if !status.unknown.is_empty() => "ahead",
+        Ok(_) => "pending",
+        Err(e) => {
+            tracing::warn!("Readiness: cannot read migrations: {}", e);
+            "unknown"
+        }
+    };
+
+    let ready = database == "ok" && migrations == "current";
+    let code = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let status = if ready { "ready" } else { "unavailable" };
+    (code, Json(Readiness { status: status.into(), database: database.into(), migrations: migrations.into() }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Versión, uptime, tamaño de la BD y uso del pool", body = ServiceStatus),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 403, description = "Requiere rol `admin`", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 503, description = "BD no disponible", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn status(
+    Extension(StartedAt(started)): Extension<StartedAt>,
+    Extension(health): Extension<Arc<dyn StorageHealth>>,
+    Extension(schema): Extension<Arc<dyn SchemaMigrations>>,
+) -> Result<Json<ServiceStatus>, AppError> {
+    let uptime = started.elapsed();
+    Ok(Json(ServiceStatus {
+        version: env!("CARGO_PKG_VERSION").into(),
+        started_at: Utc::now() - uptime,
+        uptime_secs: uptime.as_secs(),
+        storage: health.stats().await?,
+        migrations_current: schema.status().await?.is_current(),
+    }))
+}
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    http::{header::ETAG, HeaderMap},
+    response::IntoResponse,
+    Extension, Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    app::{
+        audit_log::{AuditLog, BookRevision},
+        book_repository::BookRepository,
+    },
+    error::AppError,
+    handlers::book_handler::{check_if_match, etag},
+    middleware::auth::Claims,
+};
+
+#[utoipa::path(
+    get,
+    path = "/books/{id}/history",
+    tag = "books",
+    params(("id" = String, Path, description = "Id del libro")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Revisiones del libro, de la más antigua a la más reciente", body = [BookRevision]),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "Libro sin historial", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn book_history(
+    Extension(audit): Extension<Arc<dyn AuditLog>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<BookRevision>>, AppError> {
+    let history = audit.history(&id).await?;
+    if history.is_empty() {
+        return Err(AppError::NotFound(format!("Book {} has no history", id)));
+    }
+    Ok(Json(history))
+}
+
+#[utoipa::path(
+    post,
+    path = "/books/{id}/history/{revision}/revert",
+    tag = "books",
+    params(
+        ("id" = String, Path, description = "Id del libro"),
+        ("revision" = i64, Path, description = "Revisión cuyo estado `after` se recupera"),
+        ("If-Match" = Option<String>, Header, description = "ETag leído previamente"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Libro con los datos de la revisión", body = Book,
+            headers(("ETag" = String, description = "Nueva versión del libro"))),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "Libro o revisión inexistente", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 412, description = "El libro cambió desde que se leyó", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 422, description = "La revisión es un borrado", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn revert_book<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Extension(audit): Extension<Arc<dyn AuditLog>>,
+    Extension(claims): Extension<Claims>,
+    Path((id, revision)): Path<(String, i64)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let snapshot = audit
+        .get(&id, revision)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Revision {} of book {} not found", revision, id)))?
+        .after
+        .ok_or_else(|| AppError::Unprocessable(format!(
+            "Revision {} deleted the book; revert to an earlier revision",
+            revision
+        )))?;
+    // Un libro en la papelera hay que restaurarlo antes
+    let mut book = repo
+        .get_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Book {} not found", id)))?;
+    check_if_match(&headers, &book)?;
+
+    book.title = snapshot.title;
+    book.author = snapshot.author;
+    book.published_year = snapshot.published_year;
+    book.isbn = snapshot.isbn;
+    book.updated_by = Some(claims.sub);
+
+    let updated = repo.revert(book).await?;
+    Ok(([(ETAG, etag(&updated))], Json(updated)))
+}
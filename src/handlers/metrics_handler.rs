@@ -0,0 +1,86 @@
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse, Extension};
+use std::sync::Arc;
+
+use crate::{
+    app::{book_repository::BookRepository, health::StorageHealth},
+    metrics::{Gauge, Metrics},
+};
+
+/// Versión del formato de texto que espera Prometheus
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Repositorio sin `MeteredBookRepository` por encima: contar libros en cada
+/// scrape no debe sumar muestras a `repository_call_duration_seconds`
+#[derive(Clone)]
+pub struct UnmeteredBooks(pub Arc<dyn BookRepository>);
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Métricas en formato de texto de Prometheus; con la BD caída, `storage_up` vale 0 y faltan los gauges de libros y pool", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn metrics(
+    Extension(metrics): Extension<Arc<Metrics>>,
+    Extension(UnmeteredBooks(repo)): Extension<UnmeteredBooks>,
+    Extension(health): Extension<Arc<dyn StorageHealth>>,
+) -> impl IntoResponse {
+    // Los contadores se sirven siempre: son los que explican una caída de la BD
+    let mut gauges = Vec::new();
+    let mut storage_up = true;
+
+    match repo.count().await {
+        Ok(counts) => {
+            for (state, value) in [("active", counts.active), ("trashed", counts.trashed)] {
+                gauges.push(Gauge {
+                    name: "library_books",
+                    help: "Libros en el catálogo",
+                    labels: vec![("state", state.into())],
+                    value: value as f64,
+                });
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Metrics: cannot count books: {}", e);
+            storage_up = false;
+        }
+    }
+
+    match health.stats().await {
+        // El backend en memoria no tiene pool
+        Ok(stats) => {
+            if let Some(pool) = stats.pool {
+                let in_use = pool.size.saturating_sub(pool.idle);
+                for (state, value) in [("idle", pool.idle), ("in_use", in_use)] {
+                    gauges.push(Gauge {
+                        name: "db_pool_connections",
+                        help: "Conexiones abiertas del pool",
+                        labels: vec![("state", state.into())],
+                        value: value as f64,
+                    });
+                }
+                gauges.push(Gauge {
+                    name: "db_pool_max_connections",
+                    help: "Tamaño máximo del pool",
+                    labels: Vec::new(),
+                    value: pool.max as f64,
+                });
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Metrics: cannot read storage stats: {}", e);
+            storage_up = false;
+        }
+    }
+
+    gauges.push(Gauge {
+        name: "storage_up",
+        help: "1 si la BD respondió a las consultas de este scrape",
+        labels: Vec::new(),
+        value: if storage_up { 1.0 } else { 0.0 },
+    });
+
+    ([(CONTENT_TYPE, TEXT_FORMAT)], metrics.render(&gauges))
+}
@@ -1,2 +1,11 @@
 pub mod book_handler;
-pub mod auth_handler;
\ No newline at end of file
+pub mod auth_handler;
+pub mod docs_handler;
+pub mod graphql_handler;
+pub mod batch_handler;
+pub mod duplicates_handler;
+pub mod trash_handler;
+pub mod history_handler;
+pub mod admin_handler;
+pub mod health_handler;
+pub mod metrics_handler;
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+use crate::{
+    app::book_repository::BookRepository,
+    domain::book::Book,
+    error::AppError,
+    middleware::auth::Claims,
+};
+
+#[derive(Deserialize, IntoParams)]
+pub struct TrashParams {
+    /// RFC 3339; solo libros borrados en ese instante o después
+    pub deleted_since: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/books/trash",
+    tag = "trash",
+    params(TrashParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Libros en la papelera, con `deleted_at`; `updated_by` es quien los borró", body = [Book]),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 403, description = "Requiere rol `admin`", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn list_trash<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Query(params): Query<TrashParams>,
+) -> Result<Json<Vec<Book>>, AppError> {
+    let books = repo.list_trash(params.deleted_since).await?;
+    Ok(Json(books))
+}
+
+#[utoipa::path(
+    post,
+    path = "/books/trash/{id}/restore",
+    tag = "trash",
+    params(("id" = String, Path, description = "Id del libro")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Libro recuperado", body = Book),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 403, description = "Requiere rol `admin`", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "El libro no está en la papelera", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn restore_book<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<Json<Book>, AppError> {
+    let book = repo
+        .restore(&id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Book {} is not in the trash", id)))?;
+    Ok(Json(book))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/books/trash/{id}",
+    tag = "trash",
+    params(("id" = String, Path, description = "Id del libro")),
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Libro borrado definitivamente"),
+        (status = 401, description = "Token ausente o inválido", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 403, description = "Requiere rol `admin`", body = ErrorBody, content_type = "application/problem+json"),
+        (status = 404, description = "El libro no está en la papelera", body = ErrorBody, content_type = "application/problem+json"),
+    )
+)]
+pub async fn purge_book<R: BookRepository>(
+    State(repo): State<Arc<R>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if !repo.purge(&id, &claims.sub).await? {
+        return Err(AppError::NotFound(format!("Book {} is not in the trash", id)));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,411 @@
+//! Contrato de `BookRepository` comprobado igual en todas las implementaciones.
+//! Cada una lo incluye en sus tests con `conformance_suite!(<repo>)`, donde
+//! `<repo>` es una expresión `Option<impl BookRepository>`; `None` omite los
+//! casos (p. ej. Postgres sin `TEST_POSTGRES_URL`).
+
+use crate::{
+    app::book_repository::{BookCounts, BookFilter, BookRepository, BookWrite, RepoError},
+    domain::{
+        book::Book,
+        duplicates::{duplicate_pairs, find_duplicates, DuplicateReason},
+    },
+};
+use chrono::{Duration, Utc};
+
+macro_rules! conformance_suite {
+    ($repo:expr) => {
+        $crate::infra::conformance::conformance_suite!(@cases $repo;
+            duplicate_id_is_a_conflict,
+            update_of_missing_book_is_not_found,
+            stale_version_is_rejected,
+            delete_reports_whether_the_book_existed,
+            atomic_batch_rolls_back_on_failure,
+            best_effort_batch_keeps_going_after_a_failure,
+            merge_removes_source_and_redirects_its_id,
+            deleted_books_go_to_the_trash,
+            restoring_a_merged_book_drops_its_redirect,
+            get_by_ids_skips_missing_and_deleted_books,
+            list_page_walks_active_books_by_id,
+            duplicate_candidates_share_isbn_author_or_title_key,
+            misspelled_authors_are_still_duplicate_candidates,
+            writes_track_updated_at_and_updated_by,
+            search_matches_partially_and_ignores_ascii_case,
+            search_treats_percent_and_underscore_as_wildcards,
+            search_filters_by_creation_range,
+            search_orders_by_creation_then_id,
+            count_separates_active_and_trashed_books,
+        );
+    };
+    (@cases $repo:expr; $($case:ident),* $(,)?) => {
+        mod conformance {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $(
+                #[tokio::test]
+                async fn $case() {
+                    if let Some(repo) = $repo {
+                        $crate::infra::conformance::$case(repo).await;
+                    }
+                }
+            )*
+        }
+    };
+}
+pub(crate) use conformance_suite;
+
+fn book(title: &str, author: &str) -> Book {
+    Book::new(title.into(), author.into(), None)
+}
+
+/// Libro creado hace `days` días
+fn book_from(days: i64, title: &str) -> Book {
+    let mut book = book(title, "Frank Herbert");
+    book.created_at -= Duration::days(days);
+    book
+}
+
+async fn titles(repo: &impl BookRepository, filter: BookFilter) -> Vec<String> {
+    repo.search(&filter).await.unwrap().into_iter().map(|b| b.title).collect()
+}
+
+pub async fn duplicate_id_is_a_conflict(repo: impl BookRepository) {
+    let book = Book::new("Dune".into(), "Frank Herbert".into(), Some(1965));
+    repo.create(book.clone()).await.unwrap();
+
+    let err = repo.create(book).await.unwrap_err();
+    assert!(matches!(err, RepoError::Conflict(_)), "{:?}", err);
+}
+
+pub async fn update_of_missing_book_is_not_found(repo: impl BookRepository) {
+    let err = repo.update(book("Dune", "Frank Herbert")).await.unwrap_err();
+    assert!(matches!(err, RepoError::NotFound(_)), "{:?}", err);
+}
+
+pub async fn stale_version_is_rejected(repo: impl BookRepository) {
+    let book = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+
+    let updated = repo.update(book.clone()).await.unwrap();
+    assert_eq!(updated.version, book.version + 1);
+    let stored = repo.get_by_id(&book.id).await.unwrap().unwrap();
+    assert_eq!(stored.version, updated.version);
+    assert_eq!(stored.created_at, book.created_at);
+
+    // `book` conserva la versión anterior
+    let err = repo.update(book.clone()).await.unwrap_err();
+    assert!(matches!(err, RepoError::VersionMismatch(_)), "{:?}", err);
+    let err = repo.delete(&book.id, Some(book.version), "admin").await.unwrap_err();
+    assert!(matches!(err, RepoError::VersionMismatch(_)), "{:?}", err);
+
+    assert!(repo.delete(&book.id, Some(updated.version), "admin").await.unwrap());
+    assert!(repo.get_by_id(&book.id).await.unwrap().is_none());
+}
+
+pub async fn delete_reports_whether_the_book_existed(repo: impl BookRepository) {
+    let book = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+
+    assert!(repo.delete(&book.id, None, "admin").await.unwrap());
+    assert!(!repo.delete(&book.id, None, "admin").await.unwrap());
+    assert!(!repo.delete(&book.id, Some(book.version), "admin").await.unwrap());
+}
+
+pub async fn atomic_batch_rolls_back_on_failure(repo: impl BookRepository) {
+    let existing = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+    let fresh = book("Emma", "Jane Austen");
+
+    let writes = vec![
+        BookWrite::Create(fresh.clone()),
+        BookWrite::Create(existing.clone()), // id duplicado
+        BookWrite::Delete { id: existing.id.clone(), expected_version: None, actor: "admin".into() },
+    ];
+
+    let batch = repo.write_batch(writes, true).await.unwrap();
+    assert!(!batch.committed);
+    assert_eq!(batch.results.len(), 2, "se detiene en el primer error");
+    assert!(matches!(batch.results[1], Err(RepoError::Conflict(_))));
+    assert!(repo.get_by_id(&fresh.id).await.unwrap().is_none());
+    assert!(repo.get_by_id(&existing.id).await.unwrap().is_some());
+}
+
+pub async fn best_effort_batch_keeps_going_after_a_failure(repo: impl BookRepository) {
+    let existing = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+    let fresh = book("Emma", "Jane Austen");
+
+    let writes = vec![
+        BookWrite::Create(fresh.clone()),
+        BookWrite::Create(existing.clone()), // id duplicado
+        BookWrite::Delete { id: "missing".into(), expected_version: None, actor: "admin".into() },
+        BookWrite::Update(existing.clone()),
+    ];
+
+    let batch = repo.write_batch(writes, false).await.unwrap();
+    assert!(batch.committed);
+    assert!(batch.results[0].is_ok());
+    assert!(matches!(batch.results[1], Err(RepoError::Conflict(_))));
+    assert!(matches!(batch.results[2], Err(RepoError::NotFound(_))));
+    assert_eq!(batch.results[3].as_ref().unwrap().as_ref().unwrap().version, existing.version + 1);
+    assert!(repo.get_by_id(&fresh.id).await.unwrap().is_some());
+}
+
+pub async fn merge_removes_source_and_redirects_its_id(repo: impl BookRepository) {
+    let first = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+    let second = repo
+        .create(Book::new("Dune.".into(), "Frank Herbert".into(), Some(1965)))
+        .await
+        .unwrap();
+    let target = repo.create(book("Dune", "F. Herbert")).await.unwrap();
+
+    repo.merge(&first.id, second.clone()).await.unwrap();
+    let second = repo.get_by_id(&second.id).await.unwrap().unwrap();
+    let merged = repo.merge(&second.id, target.clone()).await.unwrap();
+    assert_eq!(merged.version, target.version + 1);
+
+    assert!(repo.get_by_id(&first.id).await.unwrap().is_none());
+    assert!(repo.get_by_id(&second.id).await.unwrap().is_none());
+    // La redirección del primer libro se actualiza al nuevo destino
+    assert_eq!(repo.resolve_redirect(&first.id).await.unwrap(), Some(target.id.clone()));
+    assert_eq!(repo.resolve_redirect(&second.id).await.unwrap(), Some(target.id.clone()));
+    assert!(repo.resolve_redirect(&target.id).await.unwrap().is_none());
+
+    let err = repo.merge(&first.id, merged.clone()).await.unwrap_err();
+    assert!(matches!(err, RepoError::NotFound(_)), "{:?}", err);
+    // El fallo no deja el destino a medio actualizar
+    assert_eq!(repo.get_by_id(&target.id).await.unwrap().unwrap().version, merged.version);
+}
+
+pub async fn deleted_books_go_to_the_trash(repo: impl BookRepository) {
+    let book = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+    assert!(repo.delete(&book.id, None, "cataloguer").await.unwrap());
+
+    assert!(repo.get_by_id(&book.id).await.unwrap().is_none());
+    assert!(repo.get_all().await.unwrap().is_empty());
+    let filter = BookFilter { title: Some("Dune".into()), ..Default::default() };
+    assert!(repo.search(&filter).await.unwrap().is_empty());
+    let err = repo.update(book.clone()).await.unwrap_err();
+    assert!(matches!(err, RepoError::NotFound(_)), "{:?}", err);
+
+    // El borrado cuenta como cambio: lo ve quien sincroniza por `updated_at`
+    let trash = repo.list_trash(None).await.unwrap();
+    assert_eq!(trash.len(), 1);
+    let deleted_at = trash[0].deleted_at.expect("borrado lógico");
+    assert_eq!(trash[0].updated_at, deleted_at);
+    assert!(trash[0].updated_at >= book.updated_at);
+    assert_eq!(trash[0].updated_by.as_deref(), Some("cataloguer"));
+    assert_eq!(repo.list_trash(Some(deleted_at)).await.unwrap().len(), 1);
+    let later = deleted_at + Duration::milliseconds(1);
+    assert!(repo.list_trash(Some(later)).await.unwrap().is_empty());
+
+    let restored = repo.restore(&book.id, "admin").await.unwrap().expect("estaba en la papelera");
+    assert!(restored.deleted_at.is_none());
+    assert!(restored.updated_at >= deleted_at);
+    assert_eq!(restored.updated_by.as_deref(), Some("admin"));
+    assert!(repo.restore(&book.id, "admin").await.unwrap().is_none());
+
+    // Solo se purgan libros que ya están en la papelera
+    assert!(!repo.purge(&book.id, "admin").await.unwrap());
+    repo.delete(&book.id, None, "admin").await.unwrap();
+    assert!(repo.purge(&book.id, "admin").await.unwrap());
+    assert!(repo.list_trash(None).await.unwrap().is_empty());
+    assert!(repo.restore(&book.id, "admin").await.unwrap().is_none());
+}
+
+pub async fn restoring_a_merged_book_drops_its_redirect(repo: impl BookRepository) {
+    let source = repo.create(book("Dune.", "Frank Herbert")).await.unwrap();
+    let target = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+
+    repo.merge(&source.id, target.clone()).await.unwrap();
+    assert_eq!(repo.list_trash(None).await.unwrap().len(), 1);
+
+    assert!(repo.restore(&source.id, "admin").await.unwrap().is_some());
+    assert!(repo.resolve_redirect(&source.id).await.unwrap().is_none());
+    assert_eq!(repo.get_all().await.unwrap().len(), 2);
+}
+
+pub async fn get_by_ids_skips_missing_and_deleted_books(repo: impl BookRepository) {
+    let kept = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+    let deleted = repo.create(book("Emma", "Jane Austen")).await.unwrap();
+    repo.delete(&deleted.id, None, "admin").await.unwrap();
+
+    let ids = [kept.id.clone(), deleted.id, "missing".into()];
+    let found = repo.get_by_ids(&ids).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, kept.id);
+    assert!(repo.get_by_ids(&[]).await.unwrap().is_empty());
+}
+
+pub async fn list_page_walks_active_books_by_id(repo: impl BookRepository) {
+    let mut ids = Vec::new();
+    for title in ["Dune", "Emma", "Ulysses", "Walden", "Beloved"] {
+        ids.push(repo.create(book(title, "Anon")).await.unwrap().id);
+    }
+    let deleted = ids.remove(2);
+    repo.delete(&deleted, None, "admin").await.unwrap();
+    ids.sort();
+
+    let mut seen = Vec::new();
+    let mut after = None;
+    loop {
+        let page = repo.list_page(after.as_deref(), 2).await.unwrap();
+        assert!(page.len() <= 2);
+        let Some(last) = page.last() else { break };
+        after = Some(last.id.clone());
+        seen.extend(page.into_iter().map(|b| b.id));
+    }
+    assert_eq!(seen, ids);
+}
+
+pub async fn duplicate_candidates_share_isbn_author_or_title_key(repo: impl BookRepository) {
+    let with_isbn = |title: &str, author: &str, isbn: &str| {
+        let mut b = book(title, author);
+        b.isbn = Some(isbn.into());
+        b
+    };
+    let lotr = repo
+        .create(with_isbn("The Lord of the Rings", "J.R.R. Tolkien", "978-0-618-64015-7"))
+        .await
+        .unwrap();
+    let same_keys = repo.create(book("Lord of the Rings", "J. R. R. Tolkien")).await.unwrap();
+    let same_isbn = repo.create(with_isbn("LOTR", "Tolkien", "9780618640157")).await.unwrap();
+    let deleted = repo.create(book("The Lord of the Rings", "Tolkien")).await.unwrap();
+    repo.delete(&deleted.id, None, "admin").await.unwrap();
+    let same_title = repo.create(book("Lord Jim", "Joseph Conrad")).await.unwrap();
+    repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+
+    // Mismo instante de creación en algunos motores: se comparan ordenados
+    let ids = |books: Vec<Book>| {
+        let mut ids: Vec<_> = books.into_iter().map(|b| b.id).collect();
+        ids.sort();
+        ids
+    };
+    let sorted = |mut ids: Vec<String>| {
+        ids.sort();
+        ids
+    };
+    // `same_isbn` comparte además el apellido; `same_title`, solo la primera palabra
+    let candidates = ids(repo.duplicate_candidates(&lotr).await.unwrap());
+    assert_eq!(candidates, sorted(vec![same_keys.id.clone(), same_isbn.id.clone(), same_title.id.clone()]));
+
+    // Un libro aún sin guardar también se puede comparar
+    let draft = book("The Hobbit", "Tolkien");
+    let expected = sorted(vec![lotr.id.clone(), same_keys.id.clone(), same_isbn.id.clone()]);
+    assert_eq!(ids(repo.duplicate_candidates(&draft).await.unwrap()), expected);
+
+    // Con las dos claves editadas deja de ser candidato
+    let mut renamed = same_keys.clone();
+    renamed.title = "The Left Hand of Darkness".into();
+    renamed.author = "Ursula K. Le Guin".into();
+    repo.update(renamed).await.unwrap();
+    assert_eq!(ids(repo.all_duplicate_candidates().await.unwrap()), sorted(vec![lotr.id, same_isbn.id, same_title.id]));
+}
+
+pub async fn misspelled_authors_are_still_duplicate_candidates(repo: impl BookRepository) {
+    let original = repo.create(book("The Lord of the Rings", "J.R.R. Tolkien")).await.unwrap();
+    let misspelled = repo.create(book("Lord of the Rings", "J.R.R. Tolkein")).await.unwrap();
+    repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+
+    let candidates = repo.duplicate_candidates(&misspelled).await.unwrap();
+    let found = find_duplicates(&misspelled, &candidates);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].duplicate_id, original.id);
+
+    let pairs = duplicate_pairs(&repo.all_duplicate_candidates().await.unwrap());
+    assert_eq!(pairs.len(), 1);
+    assert_eq!(pairs[0].reason, DuplicateReason::SimilarTitleAndAuthor);
+}
+
+pub async fn writes_track_updated_at_and_updated_by(repo: impl BookRepository) {
+    let book = repo.create(book_from(2, "Dune")).await.unwrap();
+    assert_eq!(book.updated_at, book.created_at);
+    assert!(book.updated_by.is_none());
+    repo.create(book_from(2, "Dune Messiah")).await.unwrap();
+
+    let mut edited = book.clone();
+    edited.updated_by = Some("ana".into());
+    let edited = repo.update(edited).await.unwrap();
+    assert!(edited.updated_at > book.updated_at);
+    let stored = repo.get_by_id(&book.id).await.unwrap().unwrap();
+    assert_eq!(stored.updated_by.as_deref(), Some("ana"));
+    assert_eq!(stored.updated_at, edited.updated_at);
+
+    let filter = BookFilter { updated_since: Some(Utc::now() - Duration::days(1)), ..Default::default() };
+    assert_eq!(titles(&repo, filter).await, ["Dune"]);
+}
+
+pub async fn search_matches_partially_and_ignores_ascii_case(repo: impl BookRepository) {
+    repo.create(book_from(3, "The Hobbit")).await.unwrap();
+    let mut silmarillion = book_from(2, "The Silmarillion");
+    silmarillion.author = "Christopher Tolkien".into();
+    repo.create(silmarillion).await.unwrap();
+    repo.create(book_from(1, "Dune")).await.unwrap();
+
+    let filter = |title: Option<&str>, author: Option<&str>| BookFilter {
+        title: title.map(Into::into),
+        author: author.map(Into::into),
+        ..Default::default()
+    };
+    assert_eq!(titles(&repo, filter(Some("hOBB"), None)).await, ["The Hobbit"]);
+    assert_eq!(titles(&repo, filter(Some("the"), None)).await, ["The Hobbit", "The Silmarillion"]);
+    assert_eq!(titles(&repo, filter(Some("the"), Some("HERBERT"))).await, ["The Hobbit"]);
+    assert_eq!(titles(&repo, filter(None, Some("tolkien"))).await, ["The Silmarillion"]);
+    assert!(titles(&repo, filter(Some("hobbits"), None)).await.is_empty());
+    assert_eq!(titles(&repo, filter(None, None)).await.len(), 3);
+}
+
+pub async fn search_treats_percent_and_underscore_as_wildcards(repo: impl BookRepository) {
+    repo.create(book_from(2, "The Hobbit")).await.unwrap();
+    repo.create(book_from(1, "100% Dune")).await.unwrap();
+
+    let title = |t: &str| BookFilter { title: Some(t.into()), ..Default::default() };
+    assert_eq!(titles(&repo, title("h_bb")).await, ["The Hobbit"]);
+    assert_eq!(titles(&repo, title("the%bit")).await, ["The Hobbit"]);
+    assert_eq!(titles(&repo, title("%")).await, ["The Hobbit", "100% Dune"]);
+}
+
+pub async fn search_filters_by_creation_range(repo: impl BookRepository) {
+    for (days, title) in [(30, "Dune"), (10, "Dune Messiah"), (1, "Children of Dune")] {
+        repo.create(book_from(days, title)).await.unwrap();
+    }
+    let ago = |days| Some(Utc::now() - Duration::days(days));
+
+    let filter = BookFilter { created_from: ago(20), created_to: ago(5), ..Default::default() };
+    assert_eq!(titles(&repo, filter).await, ["Dune Messiah"]);
+
+    // Ordenados por fecha de alta
+    let filter = BookFilter { created_from: ago(20), ..Default::default() };
+    assert_eq!(titles(&repo, filter).await, ["Dune Messiah", "Children of Dune"]);
+
+    // `created_to` es exclusivo
+    let dune = repo.search(&BookFilter { title: Some("Dune".into()), ..Default::default() }).await.unwrap();
+    let filter = BookFilter { created_to: Some(dune[0].created_at), ..Default::default() };
+    assert!(titles(&repo, filter).await.is_empty());
+}
+
+pub async fn search_orders_by_creation_then_id(repo: impl BookRepository) {
+    let newest = repo.create(book_from(1, "Dune")).await.unwrap();
+    let mut a = book_from(5, "Emma");
+    let mut b = book_from(5, "Walden");
+    b.created_at = a.created_at;
+    // Mismo instante de alta: decide el id
+    if a.id > b.id {
+        std::mem::swap(&mut a.id, &mut b.id);
+    }
+    repo.create(b.clone()).await.unwrap();
+    repo.create(a.clone()).await.unwrap();
+
+    let found = repo.search(&BookFilter::default()).await.unwrap();
+    let ids: Vec<&str> = found.iter().map(|b| b.id.as_str()).collect();
+    assert_eq!(ids, [a.id.as_str(), b.id.as_str(), newest.id.as_str()]);
+}
+
+pub async fn count_separates_active_and_trashed_books(repo: impl BookRepository) {
+    assert_eq!(repo.count().await.unwrap(), BookCounts::default());
+
+    let dune = repo.create(book("Dune", "Frank Herbert")).await.unwrap();
+    repo.create(book("Emma", "Jane Austen")).await.unwrap();
+    repo.create(book("Ulysses", "James Joyce")).await.unwrap();
+    repo.delete(&dune.id, None, "admin").await.unwrap();
+    assert_eq!(repo.count().await.unwrap(), BookCounts { active: 2, trashed: 1 });
+
+    repo.purge(&dune.id, "admin").await.unwrap();
+    assert_eq!(repo.count().await.unwrap(), BookCounts { active: 2, trashed: 0 });
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use sqlx::Pool;
+
+use crate::{
+    app::{
+        book_repository::RepoError,
+        health::{PoolStats, StorageHealth, StorageStats},
+    },
+    infra::{migrations::NoDatabase, Database},
+};
+
+fn pool_stats<DB: sqlx::Database>(pool: &Pool<DB>) -> PoolStats {
+    PoolStats {
+        size: pool.size(),
+        idle: pool.num_idle() as u32,
+        max: pool.options().get_max_connections(),
+    }
+}
+
+#[async_trait]
+impl StorageHealth for Database {
+    async fn ping(&self) -> Result<(), RepoError> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("SELECT 1").execute(pool).await?;
+            }
+            Database::Postgres(pool) => {
+                sqlx::query("SELECT 1").execute(pool).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StorageStats, RepoError> {
+        let stats = match self {
+            Database::Sqlite(pool) => StorageStats {
+                backend: "sqlite".into(),
+                // Incluye las páginas libres: es lo que ocupa el fichero
+                size_bytes: Some(
+                    sqlx::query_scalar("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()")
+                        .fetch_one(pool)
+                        .await?,
+                ),
+                pool: Some(pool_stats(pool)),
+            },
+            Database::Postgres(pool) => StorageStats {
+                backend: "postgres".into(),
+                size_bytes: Some(
+                    sqlx::query_scalar("SELECT pg_database_size(current_database())")
+                        .fetch_one(pool)
+                        .await?,
+                ),
+                pool: Some(pool_stats(pool)),
+            },
+        };
+        Ok(stats)
+    }
+}
+
+#[async_trait]
+impl StorageHealth for NoDatabase {
+    async fn ping(&self) -> Result<(), RepoError> {
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StorageStats, RepoError> {
+        Ok(StorageStats { backend: "memory".into(), size_bytes: None, pool: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{app::health::StorageHealth, infra::Database};
+
+    #[tokio::test]
+    async fn sqlite_reports_size_and_pool_usage() {
+        let db = Database::connect("sqlite::memory:", 1).await.unwrap();
+        db.migrate(true).await.unwrap();
+
+        db.ping().await.unwrap();
+        let stats = db.stats().await.unwrap();
+        assert_eq!(stats.backend, "sqlite");
+        assert!(stats.size_bytes.unwrap() > 0);
+        let pool = stats.pool.unwrap();
+        assert_eq!((pool.size, pool.max), (1, 1));
+
+        db.close().await;
+        assert!(db.ping().await.is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use crate::app::{
+    audit_log::{AuditLog, BookRevision},
+    book_repository::RepoError,
+};
+use async_trait::async_trait;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// `AuditLog` en memoria, compañero de `InMemoryBookRepository`
+#[derive(Default)]
+pub struct InMemoryAuditLog {
+    revisions: Mutex<Vec<BookRevision>>,
+}
+
+impl InMemoryAuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn revisions(&self) -> MutexGuard<'_, Vec<BookRevision>> {
+        self.revisions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Solo para `InMemoryBookRepository`, que la llama al confirmar cada
+    /// escritura sin soltar su propio cerrojo
+    pub(crate) fn record(&self, mut revision: BookRevision) -> BookRevision {
+        let mut revisions = self.revisions();
+        // Como un autoincremento: único en todo el registro, no solo por libro
+        revision.id = revisions.len() as i64 + 1;
+        revisions.push(revision.clone());
+        revision
+    }
+}
+
+#[async_trait]
+impl AuditLog for InMemoryAuditLog {
+    async fn history(&self, book_id: &str) -> Result<Vec<BookRevision>, RepoError> {
+        Ok(self.revisions().iter().filter(|r| r.book_id == book_id).cloned().collect())
+    }
+
+    async fn get(&self, book_id: &str, revision_id: i64) -> Result<Option<BookRevision>, RepoError> {
+        Ok(self
+            .revisions()
+            .iter()
+            .find(|r| r.book_id == book_id && r.id == revision_id)
+            .cloned())
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    app::{
+        audit_log::{BookRevision, RevisionAction},
+        book_repository::{BatchResult, BookCounts, BookFilter, BookRepository, BookWrite, RepoError},
+    },
+    domain::{
+        book::{self, Book},
+        duplicates::share_key,
+    },
+    infra::memory_audit_log::InMemoryAuditLog,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// Repositorio sin base de datos, con el mismo contrato que los de SQL. Para
+/// tests y desarrollo: los datos se pierden al cerrar el proceso.
+#[derive(Default)]
+pub struct InMemoryBookRepository {
+    state: Mutex<State>,
+    audit: Arc<InMemoryAuditLog>,
+}
+
+/// Libros en orden de alta (también los de la papelera) y redirecciones
+#[derive(Default, Clone)]
+struct State {
+    books: Vec<Book>,
+    redirects: HashMap<String, String>,
+    /// Revisiones de las escrituras aún sin confirmar; se descartan con la copia
+    pending: Vec<BookRevision>,
+}
+
+impl InMemoryBookRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Las revisiones van a `audit`, que es el que lee el historial
+    pub fn with_audit_log(audit: Arc<InMemoryAuditLog>) -> Self {
+        Self { state: Mutex::default(), audit }
+    }
+
+    /// Pasa las revisiones pendientes al registro sin soltar el cerrojo del
+    /// estado: nadie ve la escritura sin su revisión
+    fn commit(&self, state: &mut State) {
+        for revision in state.pending.drain(..) {
+            self.audit.record(revision);
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // Ninguna operación deja el estado a medias, así que un pánico ajeno no lo invalida
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl BookRepository for InMemoryBookRepository {
+    async fn get_all(&self) -> Result<Vec<Book>, RepoError> {
+        Ok(self.state().active().cloned().collect())
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, RepoError> {
+        Ok(self.state().find_active(id).cloned())
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Book>, RepoError> {
+        Ok(self.state().active().filter(|b| ids.contains(&b.id)).cloned().collect())
+    }
+
+    async fn list_page(&self, after: Option<&str>, limit: i64) -> Result<Vec<Book>, RepoError> {
+        let mut books: Vec<Book> = self
+            .state()
+            .active()
+            .filter(|b| after.is_none_or(|after| b.id.as_str() > after))
+            .cloned()
+            .collect();
+        books.sort_by(|a, b| a.id.cmp(&b.id));
+        books.truncate(limit.max(0) as usize);
+        Ok(books)
+    }
+
+    async fn create(&self, book: Book) -> Result<Book, RepoError> {
+        let mut state = self.state();
+        let saved = state.insert(book)?;
+        self.commit(&mut state);
+        Ok(saved)
+    }
+
+    async fn update(&self, book: Book) -> Result<Book, RepoError> {
+        let mut state = self.state();
+        let updated = state.update(book, RevisionAction::Update)?;
+        self.commit(&mut state);
+        Ok(updated)
+    }
+
+    async fn revert(&self, book: Book) -> Result<Book, RepoError> {
+        let mut state = self.state();
+        let updated = state.update(book, RevisionAction::Revert)?;
+        self.commit(&mut state);
+        Ok(updated)
+    }
+
+    async fn delete(&self, id: &str, expected_version: Option<i64>, actor: &str) -> Result<bool, RepoError> {
+        let mut state = self.state();
+        let deleted = state.delete(id, expected_version, actor)?;
+        self.commit(&mut state);
+        Ok(deleted)
+    }
+
+    async fn write_batch(&self, writes: Vec<BookWrite>, atomic: bool) -> Result<BatchResult, RepoError> {
+        let mut state = self.state();
+        // Se trabaja sobre una copia que solo sustituye al estado si se confirma
+        let mut tx = state.clone();
+        let mut results = Vec::with_capacity(writes.len());
+
+        for write in writes {
+            let mut savepoint = tx.clone();
+            let result = savepoint.apply(write);
+            if result.is_ok() {
+                tx = savepoint;
+            }
+            let failed = result.is_err();
+            results.push(result);
+            if atomic && failed {
+                return Ok(BatchResult { results, committed: false });
+            }
+        }
+
+        *state = tx;
+        self.commit(&mut state);
+        Ok(BatchResult { results, committed: true })
+    }
+
+    async fn merge(&self, source_id: &str, target: Book) -> Result<Book, RepoError> {
+        let mut state = self.state();
+        let mut tx = state.clone();
+        let target_id = target.id.clone();
+        let actor = target.updated_by.clone().unwrap_or_default();
+        let merged = tx.update(target, RevisionAction::Merge)?;
+        if !tx.delete(source_id, None, &actor)? {
+            return Err(RepoError::NotFound(source_id.to_string()));
+        }
+        // Evita cadenas: lo que apuntaba al origen pasa a apuntar al destino
+        for new_id in tx.redirects.values_mut().filter(|new_id| *new_id == source_id) {
+            new_id.clone_from(&target_id);
+        }
+        tx.redirects.insert(source_id.to_string(), target_id);
+        *state = tx;
+        self.commit(&mut state);
+        Ok(merged)
+    }
+
+    async fn list_trash(&self, deleted_since: Option<DateTime<Utc>>) -> Result<Vec<Book>, RepoError> {
+        let mut books: Vec<Book> = self
+            .state()
+            .books
+            .iter()
+            .filter(|b| b.deleted_at.is_some_and(|at| deleted_since.is_none_or(|since| at >= since)))
+            .cloned()
+            .collect();
+        books.sort_by_key(|b| std::cmp::Reverse(b.deleted_at));
+        Ok(books)
+    }
+
+    async fn restore(&self, id: &str, actor: &str) -> Result<Option<Book>, RepoError> {
+        let mut state = self.state();
+        let Some(book) = state.books.iter_mut().find(|b| b.id == id && b.deleted_at.is_some()) else {
+            return Ok(None);
+        };
+        let before = book.clone();
+        book.deleted_at = None;
+        book.updated_at = book::now();
+        book.updated_by = Some(actor.to_string());
+        let book = book.clone();
+        // Un libro fusionado que se recupera deja de redirigir al destino
+        state.redirects.remove(id);
+        state.pending.push(BookRevision::new(RevisionAction::Restore, actor, Some(before), Some(book.clone())));
+        self.commit(&mut state);
+        Ok(Some(book))
+    }
+
+    async fn purge(&self, id: &str, actor: &str) -> Result<bool, RepoError> {
+        let mut state = self.state();
+        let Some(position) = state.books.iter().position(|b| b.id == id && b.deleted_at.is_some()) else {
+            return Ok(false);
+        };
+        let before = state.books.remove(position);
+        state.pending.push(BookRevision::new(RevisionAction::Purge, actor, Some(before), None));
+        self.commit(&mut state);
+        Ok(true)
+    }
+
+    async fn resolve_redirect(&self, id: &str) -> Result<Option<String>, RepoError> {
+        Ok(self.state().redirects.get(id).cloned())
+    }
+
+    async fn search(&self, filter: &BookFilter) -> Result<Vec<Book>, RepoError> {
+        let mut books: Vec<Book> = self
+            .state()
+            .active()
+            .filter(|b| filter.title.as_deref().is_none_or(|t| contains_like(&b.title, t)))
+            .filter(|b| filter.author.as_deref().is_none_or(|a| contains_like(&b.author, a)))
+            .filter(|b| filter.updated_since.is_none_or(|since| b.updated_at >= since))
+            .filter(|b| filter.created_from.is_none_or(|from| b.created_at >= from))
+            .filter(|b| filter.created_to.is_none_or(|to| b.created_at < to))
+            .cloned()
+            .collect();
+        books.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(books)
+    }
+
+    async fn duplicate_candidates(&self, book: &Book) -> Result<Vec<Book>, RepoError> {
+        let mut books: Vec<Book> = self
+            .state()
+            .active()
+            .filter(|other| other.id != book.id && share_key(book, other))
+            .cloned()
+            .collect();
+        books.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(books)
+    }
+
+    async fn all_duplicate_candidates(&self) -> Result<Vec<Book>, RepoError> {
+        let state = self.state();
+        let mut books: Vec<Book> = state
+            .active()
+            .filter(|book| state.active().any(|other| other.id != book.id && share_key(book, other)))
+            .cloned()
+            .collect();
+        books.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(books)
+    }
+
+    async fn count(&self) -> Result<BookCounts, RepoError> {
+        let state = self.state();
+        let active = state.active().count() as i64;
+        Ok(BookCounts { active, trashed: state.books.len() as i64 - active })
+    }
+}
+
+impl State {
+    fn active(&self) -> impl Iterator<Item = &Book> {
+        self.books.iter().filter(|b| b.deleted_at.is_none())
+    }
+
+    fn find_active(&self, id: &str) -> Option<&Book> {
+        self.active().find(|b| b.id == id)
+    }
+
+    fn insert(&mut self, mut book: Book) -> Result<Book, RepoError> {
+        // Como la clave primaria en SQL: tampoco se reutiliza el id de un libro en la papelera
+        if self.books.iter().any(|b| b.id == book.id) {
+            return Err(RepoError::Conflict(format!("Book {} already exists", book.id)));
+        }
+        book.updated_at = book.created_at;
+        self.books.push(book.clone());
+        let actor = book.updated_by.clone().unwrap_or_default();
+        self.pending.push(BookRevision::new(RevisionAction::Create, &actor, None, Some(book.clone())));
+        Ok(book)
+    }
+
+    fn update(&mut self, mut book: Book, action: RevisionAction) -> Result<Book, RepoError> {
+        let Some(stored) = self.books.iter_mut().find(|b| b.id == book.id && b.deleted_at.is_none()) else {
+            return Err(RepoError::NotFound(book.id));
+        };
+        if stored.version != book.version {
+            return Err(RepoError::VersionMismatch(book.id));
+        }
+        let before = stored.clone();
+        book.updated_at = book::now();
+        book.version += 1;
+        stored.title.clone_from(&book.title);
+        stored.author.clone_from(&book.author);
+        stored.published_year = book.published_year;
+        stored.isbn.clone_from(&book.isbn);
+        stored.updated_at = book.updated_at;
+        stored.updated_by.clone_from(&book.updated_by);
+        stored.version = book.version;
+        let actor = book.updated_by.clone().unwrap_or_default();
+        self.pending.push(BookRevision::new(action, &actor, Some(before), Some(book.clone())));
+        Ok(book)
+    }
+
+    fn delete(&mut self, id: &str, expected_version: Option<i64>, actor: &str) -> Result<bool, RepoError> {
+        let Some(stored) = self.books.iter_mut().find(|b| b.id == id && b.deleted_at.is_none()) else {
+            return Ok(false);
+        };
+        if expected_version.is_some_and(|v| v != stored.version) {
+            return Err(RepoError::VersionMismatch(id.to_string()));
+        }
+        let before = stored.clone();
+        let now = book::now();
+        stored.deleted_at = Some(now);
+        stored.updated_at = now;
+        stored.updated_by = Some(actor.to_string());
+        self.pending.push(BookRevision::new(RevisionAction::Delete, actor, Some(before), None));
+        Ok(true)
+    }
+
+    fn apply(&mut self, write: BookWrite) -> Result<Option<Book>, RepoError> {
+        match write {
+            BookWrite::Create(book) => self.insert(book).map(Some),
+            BookWrite::Update(book) => self.update(book, RevisionAction::Update).map(Some),
+            BookWrite::Delete { id, expected_version, actor } => match self.delete(&id, expected_version, &actor)? {
+                true => Ok(None),
+                false => Err(RepoError::NotFound(id)),
+            },
+        }
+    }
+}
+
+/// `value LIKE '%' || needle || '%'` con las reglas de SQLite: `%` y `_` son
+/// comodines y solo se ignoran las mayúsculas ASCII
+fn contains_like(value: &str, needle: &str) -> bool {
+    let value: Vec<char> = value.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let pattern: Vec<char> = std::iter::once('%')
+        .chain(needle.chars().map(|c| c.to_ascii_lowercase()))
+        .chain(std::iter::once('%'))
+        .collect();
+
+    // matches[j]: el prefijo de `value` procesado encaja con pattern[..j]
+    let mut matches = vec![false; pattern.len() + 1];
+    matches[0] = true;
+    for j in 1..=pattern.len() {
+        matches[j] = matches[j - 1] && pattern[j - 1] == '%';
+    }
+    for c in value {
+        let mut next = vec![false; pattern.len() + 1];
+        for j in 1..=pattern.len() {
+            next[j] = match pattern[j - 1] {
+                '%' => next[j - 1] || matches[j],
+                '_' => matches[j - 1],
+                p => matches[j - 1] && p == c,
+            };
+        }
+        matches = next;
+    }
+    matches[pattern.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contains_like, InMemoryBookRepository};
+    use crate::infra::conformance::conformance_suite;
+
+    #[test]
+    fn like_matching_follows_sqlite() {
+        assert!(contains_like("The Hobbit", "hobb"));
+        assert!(contains_like("The Hobbit", ""));
+        assert!(contains_like("The Hobbit", "h_bbit"));
+        assert!(contains_like("The Hobbit", "the%bit"));
+        assert!(!contains_like("The Hobbit", "hobbits"));
+        // Fuera de ASCII se distinguen mayúsculas, como en SQLite sin ICU
+        assert!(!contains_like("Ética", "ética"));
+    }
+
+    conformance_suite!(Some(InMemoryBookRepository::new()));
+}
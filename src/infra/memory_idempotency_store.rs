@@ -0,0 +1,74 @@
+use crate::app::{
+    book_repository::RepoError,
+    idempotency_store::{IdempotencyStore, Reservation, StoredResponse},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// `IdempotencyStore` en memoria, compañero de `InMemoryBookRepository`
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore {
+    keys: Mutex<HashMap<(String, String), Entry>>,
+}
+
+struct Entry {
+    request_hash: String,
+    created_at: DateTime<Utc>,
+    response: Option<StoredResponse>,
+}
+
+impl InMemoryIdempotencyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn keys(&self) -> MutexGuard<'_, HashMap<(String, String), Entry>> {
+        self.keys.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        not_before: DateTime<Utc>,
+        lease_before: DateTime<Utc>,
+    ) -> Result<Reservation, RepoError> {
+        let mut keys = self.keys();
+        keys.retain(|_, entry| entry.created_at >= not_before);
+
+        match keys.get(&(scope.to_string(), key.to_string())) {
+            // Reserva abandonada: se reclama como si fuera nueva
+            Some(entry) if entry.response.is_some() || entry.created_at >= lease_before => Ok(Reservation::Existing {
+                request_hash: entry.request_hash.clone(),
+                response: entry.response.clone(),
+            }),
+            _ => {
+                keys.insert(
+                    (scope.to_string(), key.to_string()),
+                    Entry { request_hash: request_hash.to_string(), created_at: Utc::now(), response: None },
+                );
+                Ok(Reservation::Reserved)
+            }
+        }
+    }
+
+    async fn complete(&self, scope: &str, key: &str, response: &StoredResponse) -> Result<(), RepoError> {
+        if let Some(entry) = self.keys().get_mut(&(scope.to_string(), key.to_string())) {
+            entry.response = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), RepoError> {
+        self.keys().remove(&(scope.to_string(), key.to_string()));
+        Ok(())
+    }
+}
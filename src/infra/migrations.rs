@@ -0,0 +1,276 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::migrate::{MigrateError, Migrator};
+use thiserror::Error;
+
+use crate::{
+    app::{
+        book_repository::RepoError,
+        migrations::{AppliedMigration, MigrationStatus, PendingMigration, SchemaMigrations},
+    },
+    domain::duplicates::{author_key, normalize_isbn, title_key},
+    infra::Database,
+};
+
+/// Migraciones embebidas en el binario, una carpeta por motor
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    /// Otra versión más nueva ya migró la BD: arrancar podría corromper datos
+    #[error("Database schema is ahead of this binary (unknown migrations {0:?}); deploy a newer version")]
+    Ahead(Vec<i64>),
+
+    #[error(transparent)]
+    Migrate(#[from] MigrateError),
+
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+}
+
+/// Migración que añade `author_key` y `title_key`; antes de ella no hay
+/// columnas que rellenar
+const DUPLICATE_KEYS_MIGRATION: i64 = 20251029090000;
+
+/// Fila de `_sqlx_migrations`: versión, descripción, fecha, éxito y duración en ns
+type MigrationRow = (i64, String, DateTime<Utc>, bool, i64);
+
+impl Database {
+    fn migrator(&self) -> &'static Migrator {
+        match self {
+            Database::Sqlite(_) => &SQLITE_MIGRATOR,
+            Database::Postgres(_) => &POSTGRES_MIGRATOR,
+        }
+    }
+
+    /// Comprueba que la BD no va por delante del binario y, con `apply`,
+    /// aplica las migraciones pendientes. Devuelve el estado resultante.
+    pub async fn migrate(&self, apply: bool) -> Result<MigrationStatus, MigrationError> {
+        let mut status = self.status().await?;
+        if !status.unknown.is_empty() {
+            return Err(MigrationError::Ahead(status.unknown));
+        }
+        if apply && !status.pending.is_empty() {
+            match self {
+                Database::Sqlite(pool) => SQLITE_MIGRATOR.run(pool).await?,
+                Database::Postgres(pool) => POSTGRES_MIGRATOR.run(pool).await?,
+            }
+            status = self.status().await?;
+        }
+        // En cada arranque y no solo al migrar: la BD puede venir migrada con
+        // `sqlx migrate run` o de un arranque cuyo relleno falló a medias.
+        // Sin filas por rellenar es una consulta por un índice.
+        if !status.pending.iter().any(|m| m.version <= DUPLICATE_KEYS_MIGRATION) {
+            self.backfill_duplicate_keys().await?;
+        }
+        Ok(status)
+    }
+
+    /// Rellena `isbn_key`, `author_key` y `title_key` de los libros anteriores
+    /// a la migración que las añadió: SQL no sabe normalizar como
+    /// `domain::duplicates`. Solo toca filas sin `author_key`, así que
+    /// repetirlo no cambia nada.
+    async fn backfill_duplicate_keys(&self) -> Result<(), RepoError> {
+        const CHUNK: i64 = 500;
+        type Row = (String, String, String, Option<String>);
+        loop {
+            let rows: Vec<Row> = match self {
+                Database::Sqlite(pool) => {
+                    sqlx::query_as("SELECT id, title, author, isbn FROM books WHERE author_key IS NULL LIMIT ?")
+                        .bind(CHUNK)
+                        .fetch_all(pool)
+                        .await?
+                }
+                Database::Postgres(pool) => {
+                    sqlx::query_as("SELECT id, title, author, isbn FROM books WHERE author_key IS NULL LIMIT $1")
+                        .bind(CHUNK)
+                        .fetch_all(pool)
+                        .await?
+                }
+            };
+            if rows.is_empty() {
+                return Ok(());
+            }
+            for (id, title, author, isbn) in rows {
+                let isbn_key = isbn.as_deref().map(normalize_isbn);
+                let (author_key, title_key) = (author_key(&author), title_key(&title));
+                match self {
+                    Database::Sqlite(pool) => {
+                        sqlx::query("UPDATE books SET isbn_key = ?1, author_key = ?2, title_key = ?3 WHERE id = ?4")
+                            .bind(isbn_key)
+                            .bind(author_key)
+                            .bind(title_key)
+                            .bind(id)
+                            .execute(pool)
+                            .await?;
+                    }
+                    Database::Postgres(pool) => {
+                        sqlx::query("UPDATE books SET isbn_key = $1, author_key = $2, title_key = $3 WHERE id = $4")
+                            .bind(isbn_key)
+                            .bind(author_key)
+                            .bind(title_key)
+                            .bind(id)
+                            .execute(pool)
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Migraciones registradas; ninguna si la tabla de sqlx aún no existe
+    async fn applied(&self) -> Result<Vec<MigrationRow>, RepoError> {
+        const SELECT: &str =
+            "SELECT version, description, installed_on, success, execution_time FROM _sqlx_migrations ORDER BY version";
+        let rows = match self {
+            Database::Sqlite(pool) => {
+                let exists: bool = sqlx::query_scalar(
+                    "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+                )
+                    .fetch_one(pool)
+                    .await?;
+                match exists {
+                    true => sqlx::query_as(SELECT).fetch_all(pool).await?,
+                    false => Vec::new(),
+                }
+            }
+            Database::Postgres(pool) => {
+                let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+                    .fetch_one(pool)
+                    .await?;
+                match exists {
+                    true => sqlx::query_as(SELECT).fetch_all(pool).await?,
+                    false => Vec::new(),
+                }
+            }
+        };
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl SchemaMigrations for Database {
+    async fn status(&self) -> Result<MigrationStatus, RepoError> {
+        let applied = self.applied().await?;
+        // Las `.down.sql` también están en el Migrator; solo cuentan las de subida
+        let known: Vec<_> = self
+            .migrator()
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .collect();
+
+        let pending = known
+            .iter()
+            .filter(|m| !applied.iter().any(|(version, ..)| *version == m.version))
+            .map(|m| PendingMigration { version: m.version, description: m.description.to_string() })
+            .collect();
+        let unknown = applied
+            .iter()
+            .map(|(version, ..)| *version)
+            .filter(|version| !known.iter().any(|m| m.version == *version))
+            .collect();
+        let applied = applied
+            .into_iter()
+            .map(|(version, description, installed_on, success, execution_time)| AppliedMigration {
+                version,
+                description,
+                installed_on,
+                success,
+                execution_ms: execution_time / 1_000_000,
+            })
+            .collect();
+
+        Ok(MigrationStatus {
+            backend: match self {
+                Database::Sqlite(_) => "sqlite",
+                Database::Postgres(_) => "postgres",
+            }
+            .to_string(),
+            applied,
+            pending,
+            unknown,
+        })
+    }
+}
+
+/// Backend sin BD (en memoria): nunca hay nada que migrar ni que sondear
+pub struct NoDatabase;
+
+#[async_trait]
+impl SchemaMigrations for NoDatabase {
+    async fn status(&self) -> Result<MigrationStatus, RepoError> {
+        Ok(MigrationStatus { backend: "memory".into(), ..Default::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MigrationError, SQLITE_MIGRATOR};
+    use crate::{app::migrations::SchemaMigrations, infra::Database};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn database() -> Database {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        Database::Sqlite(pool)
+    }
+
+    #[tokio::test]
+    async fn migrate_applies_pending_migrations_unless_opted_out() {
+        let db = database().await;
+        let known = SQLITE_MIGRATOR.iter().filter(|m| m.migration_type.is_up_migration()).count();
+
+        let status = db.migrate(false).await.unwrap();
+        assert!(status.applied.is_empty());
+        assert_eq!(status.pending.len(), known);
+        assert!(!status.is_current());
+
+        let status = db.migrate(true).await.unwrap();
+        assert_eq!(status.applied.len(), known);
+        assert!(status.pending.is_empty());
+        assert!(status.is_current());
+        assert_eq!(status.backend, "sqlite");
+    }
+
+    #[tokio::test]
+    async fn books_from_before_the_duplicate_keys_are_backfilled() {
+        let db = database().await;
+        db.migrate(true).await.unwrap();
+        let Database::Sqlite(pool) = &db else { unreachable!() };
+        // Como la dejaría un binario anterior a la migración: sin claves
+        sqlx::query(
+            "INSERT INTO books (id, title, author, isbn, created_at, version, updated_at) VALUES ('1', 'The Hobbit', 'J.R.R. Tolkien', '978-0-618-26030-0', '2025-01-01T00:00:00.000Z', 1, '2025-01-01T00:00:00.000Z')",
+        )
+            .execute(pool)
+            .await
+            .unwrap();
+
+        // Basta con arrancar, aunque no haya nada que migrar o no se quiera
+        db.migrate(false).await.unwrap();
+        let keys: (Option<String>, Option<String>, Option<String>) =
+            sqlx::query_as("SELECT isbn_key, author_key, title_key FROM books").fetch_one(pool).await.unwrap();
+        assert_eq!(keys, (Some("9780618260300".into()), Some("tolkien".into()), Some("hobbit".into())));
+    }
+
+    #[tokio::test]
+    async fn database_ahead_of_the_binary_is_refused() {
+        let Database::Sqlite(pool) = database().await else { unreachable!() };
+        SQLITE_MIGRATOR.run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (29991231000000, 'future', 1, x'00', 0)",
+        )
+            .execute(&pool)
+            .await
+            .unwrap();
+        let db = Database::Sqlite(pool);
+
+        assert_eq!(db.status().await.unwrap().unknown, [29991231000000]);
+        // Se rechaza también sin aplicar migraciones
+        let err = db.migrate(false).await.unwrap_err();
+        assert!(matches!(err, MigrationError::Ahead(ref v) if v == &[29991231000000]), "{:?}", err);
+    }
+}
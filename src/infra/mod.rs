@@ -1 +1,160 @@
 pub mod sqlite_book_repository;
+pub mod sqlite_idempotency_store;
+pub mod sqlite_audit_log;
+pub mod postgres_book_repository;
+pub mod postgres_idempotency_store;
+pub mod postgres_audit_log;
+pub mod memory_book_repository;
+pub mod memory_idempotency_store;
+pub mod memory_audit_log;
+pub mod migrations;
+pub mod health;
+pub mod statement;
+
+#[cfg(test)]
+pub(crate) mod conformance;
+
+use sqlx::{
+    error::ErrorKind,
+    postgres::{PgPool, PgPoolOptions},
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+};
+use std::{str::FromStr, sync::Arc};
+
+use crate::app::{book_repository::RepoError, Backend};
+
+use self::{
+    memory_audit_log::InMemoryAuditLog, memory_book_repository::InMemoryBookRepository,
+    memory_idempotency_store::InMemoryIdempotencyStore, migrations::NoDatabase,
+    postgres_audit_log::PostgresAuditLog, postgres_book_repository::PostgresBookRepository,
+    postgres_idempotency_store::PostgresIdempotencyStore, sqlite_audit_log::SqliteAuditLog,
+    sqlite_book_repository::SqliteBookRepository, sqlite_idempotency_store::SqliteIdempotencyStore,
+};
+
+/// Backend elegido según el esquema de `DATABASE_URL`
+#[derive(Clone)]
+pub enum Database {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+impl Database {
+    /// `sqlite:` abre SQLite; `postgres:` o `postgresql:`, Postgres
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        match url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("sqlite") => {
+                // Las migraciones se aplican al arrancar, así que el fichero puede no existir aún
+                let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+                let pool = SqlitePoolOptions::new().max_connections(max_connections).connect_with(options).await?;
+                Ok(Database::Sqlite(pool))
+            }
+            Some("postgres" | "postgresql") => {
+                let pool = PgPoolOptions::new().max_connections(max_connections).connect(url).await?;
+                Ok(Database::Postgres(pool))
+            }
+            // Sin repetir la URL: puede llevar la contraseña
+            _ => Err(sqlx::Error::Configuration(
+                "DATABASE_URL must start with sqlite:, postgres: or postgresql:".into(),
+            )),
+        }
+    }
+
+    /// Cierra el pool esperando a que se devuelvan las conexiones prestadas.
+    /// En SQLite deja el WAL volcado y el fichero sin bloqueos.
+    pub async fn close(&self) {
+        match self {
+            Database::Sqlite(pool) => pool.close().await,
+            Database::Postgres(pool) => pool.close().await,
+        }
+    }
+}
+
+impl From<sqlx::Error> for RepoError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::PoolTimedOut => RepoError::Unavailable(e.to_string()),
+            sqlx::Error::Database(db) => {
+                let msg = db.message().to_string();
+                match db.kind() {
+                    ErrorKind::UniqueViolation => RepoError::Conflict(msg),
+                    ErrorKind::ForeignKeyViolation => RepoError::ForeignKey(msg),
+                    ErrorKind::NotNullViolation | ErrorKind::CheckViolation => {
+                        RepoError::Constraint(msg)
+                    }
+                    _ if is_busy(db.code().as_deref()) => RepoError::Unavailable(msg),
+                    _ => RepoError::Other(e.into()),
+                }
+            }
+            _ => RepoError::Other(e.into()),
+        }
+    }
+}
+
+pub fn sqlite_backend(pool: SqlitePool) -> Backend<SqliteBookRepository> {
+    Backend {
+        books: Arc::new(SqliteBookRepository { pool: pool.clone() }),
+        idempotency_store: Arc::new(SqliteIdempotencyStore { pool: pool.clone() }),
+        audit_log: Arc::new(SqliteAuditLog { pool: pool.clone() }),
+        migrations: Arc::new(Database::Sqlite(pool.clone())),
+        health: Arc::new(Database::Sqlite(pool)),
+    }
+}
+
+pub fn postgres_backend(pool: PgPool) -> Backend<PostgresBookRepository> {
+    Backend {
+        books: Arc::new(PostgresBookRepository { pool: pool.clone() }),
+        idempotency_store: Arc::new(PostgresIdempotencyStore { pool: pool.clone() }),
+        audit_log: Arc::new(PostgresAuditLog { pool: pool.clone() }),
+        migrations: Arc::new(Database::Postgres(pool.clone())),
+        health: Arc::new(Database::Postgres(pool)),
+    }
+}
+
+/// Todo en memoria; para tests y desarrollo
+pub fn memory_backend() -> Backend<InMemoryBookRepository> {
+    // El repositorio escribe las revisiones en el mismo registro que se consulta
+    let audit_log = Arc::new(InMemoryAuditLog::new());
+    Backend {
+        books: Arc::new(InMemoryBookRepository::with_audit_log(audit_log.clone())),
+        idempotency_store: Arc::new(InMemoryIdempotencyStore::new()),
+        audit_log,
+        migrations: Arc::new(NoDatabase),
+        health: Arc::new(NoDatabase),
+    }
+}
+
+/// Errores reintentables: SQLITE_BUSY (5) / SQLITE_LOCKED (6), incluidos sus
+/// códigos extendidos, y los SQLSTATE de Postgres de serialización,
+/// interbloqueo, bloqueo no disponible y servidor arrancando o saturado
+fn is_busy(code: Option<&str>) -> bool {
+    match code {
+        Some("40001" | "40P01" | "55P03" | "57P03" | "53300") => true,
+        // Los SQLSTATE tienen 5 caracteres; los códigos de SQLite son más cortos
+        Some(c) if c.len() < 5 => c.parse::<i32>().is_ok_and(|c| matches!(c & 0xff, 5 | 6)),
+        Some(_) => false,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_busy, Database};
+
+    #[test]
+    fn busy_and_locked_codes_are_detected() {
+        assert!(is_busy(Some("5")));
+        assert!(is_busy(Some("517"))); // SQLITE_BUSY_SNAPSHOT
+        assert!(is_busy(Some("6")));
+        assert!(!is_busy(Some("2067"))); // SQLITE_CONSTRAINT_UNIQUE
+        assert!(is_busy(Some("40P01"))); // deadlock_detected
+        assert!(!is_busy(Some("23505"))); // unique_violation
+        assert!(!is_busy(None));
+    }
+
+    #[tokio::test]
+    async fn unknown_database_scheme_is_rejected() {
+        let err = Database::connect("mysql://localhost/library", 1).await.err().unwrap();
+        assert!(matches!(err, sqlx::Error::Configuration(_)));
+        assert!(matches!(Database::connect("sqlite::memory:", 1).await, Ok(Database::Sqlite(_))));
+    }
+}
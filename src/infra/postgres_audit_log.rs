@@ -0,0 +1,72 @@
+use crate::app::{
+    audit_log::{AuditLog, BookRevision, RevisionAction},
+    book_repository::RepoError,
+};
+use crate::infra::{postgres_book_repository::DB, statement::TraceStatement};
+use async_trait::async_trait;
+use sqlx::{postgres::PgRow, types::Json, PgConnection, PgPool, Row};
+
+pub struct PostgresAuditLog {
+    pub pool: PgPool,
+}
+
+/// Guarda la revisión en la transacción de la escritura que la produce y la
+/// devuelve con su `id`
+pub(crate) async fn insert_revision(
+    conn: &mut PgConnection,
+    mut revision: BookRevision,
+) -> Result<BookRevision, RepoError> {
+    revision.id = sqlx::query_scalar(
+        r#"
+        INSERT INTO book_revisions (book_id, action, actor, recorded_at, before, after)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+        .bind(&revision.book_id)
+        .bind(revision.action.as_str())
+        .bind(&revision.actor)
+        .bind(revision.recorded_at)
+        .bind(revision.before.as_ref().map(Json))
+        .bind(revision.after.as_ref().map(Json))
+        .fetch_one(&mut *conn)
+        .traced(DB, "INSERT book_revisions")
+        .await?;
+    Ok(revision)
+}
+
+#[async_trait]
+impl AuditLog for PostgresAuditLog {
+    async fn history(&self, book_id: &str) -> Result<Vec<BookRevision>, RepoError> {
+        let rows = sqlx::query("SELECT * FROM book_revisions WHERE book_id = $1 ORDER BY id")
+            .bind(book_id)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT book_revisions")
+            .await?;
+        rows.iter().map(from_row).collect()
+    }
+
+    async fn get(&self, book_id: &str, revision_id: i64) -> Result<Option<BookRevision>, RepoError> {
+        let row = sqlx::query("SELECT * FROM book_revisions WHERE book_id = $1 AND id = $2")
+            .bind(book_id)
+            .bind(revision_id)
+            .fetch_optional(&self.pool)
+            .traced(DB, "SELECT book_revisions")
+            .await?;
+        row.as_ref().map(from_row).transpose()
+    }
+}
+
+fn from_row(row: &PgRow) -> Result<BookRevision, RepoError> {
+    let action: String = row.try_get("action")?;
+    Ok(BookRevision {
+        id: row.try_get("id")?,
+        book_id: row.try_get("book_id")?,
+        action: RevisionAction::parse(&action)
+            .ok_or_else(|| RepoError::Other(anyhow::anyhow!("Unknown revision action {}", action)))?,
+        actor: row.try_get("actor")?,
+        recorded_at: row.try_get("recorded_at")?,
+        before: row.try_get::<Option<Json<_>>, _>("before")?.map(|j| j.0),
+        after: row.try_get::<Option<Json<_>>, _>("after")?.map(|j| j.0),
+    })
+}
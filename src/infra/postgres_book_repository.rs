@@ -0,0 +1,481 @@
+use crate::{
+    app::{
+        audit_log::{BookRevision, RevisionAction},
+        book_repository::{BatchResult, BookCounts, BookFilter, BookRepository, BookWrite, RepoError},
+    },
+    domain::{
+        book::{self, Book},
+        duplicates::{author_key, normalize_isbn, title_key},
+    },
+    infra::{postgres_audit_log::insert_revision, statement::TraceStatement},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Connection, PgConnection, PgPool, Postgres, QueryBuilder};
+
+/// `db.system` de los spans de cada sentencia
+pub(crate) const DB: &str = "postgresql";
+
+/// Mismo contrato que `SqliteBookRepository`; las fechas son `TIMESTAMPTZ`
+pub struct PostgresBookRepository {
+    pub pool: PgPool,
+}
+
+#[async_trait]
+impl BookRepository for PostgresBookRepository {
+    async fn get_all(&self) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE deleted_at IS NULL")
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, RepoError> {
+        let mut conn = self.pool.acquire().await?;
+        select_book(&mut conn, id).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Book>, RepoError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let books = sqlx::query_as::<_, Book>(
+            "SELECT * FROM books WHERE deleted_at IS NULL AND id = ANY($1)",
+        )
+            .bind(ids)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn list_page(&self, after: Option<&str>, limit: i64) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
+            "SELECT * FROM books WHERE deleted_at IS NULL AND ($1::TEXT IS NULL OR id > $1) ORDER BY id LIMIT $2",
+        )
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn create(&self, book: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let saved = insert_book(&mut tx, book).await?;
+        tx.commit().await?;
+        Ok(saved)
+    }
+
+    async fn update(&self, book: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let updated = update_book(&mut tx, book, RevisionAction::Update).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn revert(&self, book: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let updated = update_book(&mut tx, book, RevisionAction::Revert).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete(&self, id: &str, expected_version: Option<i64>, actor: &str) -> Result<bool, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = delete_book(&mut tx, id, expected_version, actor).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn write_batch(&self, writes: Vec<BookWrite>, atomic: bool) -> Result<BatchResult, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(writes.len());
+
+        for write in writes {
+            // En modo best-effort cada escritura va en su propio savepoint; en
+            // Postgres además es obligatorio, porque un error aborta la transacción
+            let result = if atomic {
+                apply_write(&mut tx, write).await
+            } else {
+                let mut savepoint = Connection::begin(&mut *tx).await?;
+                let result = apply_write(&mut savepoint, write).await;
+                match result {
+                    Ok(_) => savepoint.commit().await?,
+                    Err(_) => savepoint.rollback().await?,
+                }
+                result
+            };
+            let failed = result.is_err();
+            results.push(result);
+            if atomic && failed {
+                tx.rollback().await?;
+                return Ok(BatchResult { results, committed: false });
+            }
+        }
+
+        tx.commit().await?;
+        Ok(BatchResult { results, committed: true })
+    }
+
+    async fn merge(&self, source_id: &str, target: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let target_id = target.id.clone();
+        let actor = target.updated_by.clone().unwrap_or_default();
+        let merged = update_book(&mut tx, target, RevisionAction::Merge).await?;
+        if !delete_book(&mut tx, source_id, None, &actor).await? {
+            return Err(RepoError::NotFound(source_id.to_string()));
+        }
+        // Evita cadenas: lo que apuntaba al origen pasa a apuntar al destino
+        sqlx::query("UPDATE book_redirects SET new_id = $1 WHERE new_id = $2")
+            .bind(&target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .traced(DB, "UPDATE book_redirects")
+            .await?;
+        sqlx::query("INSERT INTO book_redirects (old_id, new_id, created_at) VALUES ($1, $2, $3)")
+            .bind(source_id)
+            .bind(&target_id)
+            .bind(book::now())
+            .execute(&mut *tx)
+            .traced(DB, "INSERT book_redirects")
+            .await?;
+        tx.commit().await?;
+        Ok(merged)
+    }
+
+    async fn list_trash(&self, deleted_since: Option<DateTime<Utc>>) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
+            "SELECT * FROM books WHERE deleted_at IS NOT NULL AND ($1::TIMESTAMPTZ IS NULL OR deleted_at >= $1) ORDER BY deleted_at DESC",
+        )
+            .bind(deleted_since)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn restore(&self, id: &str, actor: &str) -> Result<Option<Book>, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = lock_trashed(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        let result = sqlx::query(
+            "UPDATE books SET deleted_at = NULL, updated_at = $2, updated_by = $3 WHERE id = $1 AND deleted_at IS NOT NULL",
+        )
+            .bind(id)
+            .bind(book::now())
+            .bind(actor)
+            .execute(&mut *tx)
+            .traced(DB, "UPDATE books")
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        // Un libro fusionado que se recupera deja de redirigir al destino
+        sqlx::query("DELETE FROM book_redirects WHERE old_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .traced(DB, "DELETE book_redirects")
+            .await?;
+        let book = select_book(&mut tx, id).await?;
+        insert_revision(&mut tx, BookRevision::new(RevisionAction::Restore, actor, Some(before), book.clone())).await?;
+        tx.commit().await?;
+        Ok(book)
+    }
+
+    async fn purge(&self, id: &str, actor: &str) -> Result<bool, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = lock_trashed(&mut tx, id).await? else {
+            return Ok(false);
+        };
+        sqlx::query("DELETE FROM books WHERE id = $1 AND deleted_at IS NOT NULL")
+            .bind(id)
+            .execute(&mut *tx)
+            .traced(DB, "DELETE books")
+            .await?;
+        insert_revision(&mut tx, BookRevision::new(RevisionAction::Purge, actor, Some(before), None)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn resolve_redirect(&self, id: &str) -> Result<Option<String>, RepoError> {
+        let new_id = sqlx::query_scalar::<_, String>("SELECT new_id FROM book_redirects WHERE old_id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .traced(DB, "SELECT book_redirects")
+            .await?;
+        Ok(new_id)
+    }
+
+    async fn search(&self, filter: &BookFilter) -> Result<Vec<Book>, RepoError> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM books WHERE deleted_at IS NULL");
+
+        // ILIKE sin carácter de escape: lo más parecido a `LIKE` en SQLite,
+        // salvo que aquí también se ignoran las mayúsculas fuera de ASCII
+        if let Some(t) = &filter.title {
+            query.push(" AND title ILIKE '%' || ").push_bind(t).push(" || '%' ESCAPE ''");
+        }
+        if let Some(a) = &filter.author {
+            query.push(" AND author ILIKE '%' || ").push_bind(a).push(" || '%' ESCAPE ''");
+        }
+        if let Some(since) = filter.updated_since {
+            query.push(" AND updated_at >= ").push_bind(since);
+        }
+        if let Some(from) = filter.created_from {
+            query.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = filter.created_to {
+            query.push(" AND created_at < ").push_bind(to);
+        }
+        query.push(" ORDER BY created_at, id");
+
+        let books = query.build_query_as::<Book>().fetch_all(&self.pool).traced(DB, "SELECT books").await?;
+        Ok(books)
+    }
+
+    async fn duplicate_candidates(&self, book: &Book) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
+            r#"
+            SELECT * FROM books
+             WHERE deleted_at IS NULL
+               AND id <> $1
+               AND (author_key = $2 OR title_key = $3 OR isbn_key = $4)
+             ORDER BY created_at, id
+            "#,
+        )
+            .bind(&book.id)
+            .bind(author_key(&book.author))
+            .bind(title_key(&book.title))
+            .bind(book.isbn.as_deref().map(normalize_isbn))
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn all_duplicate_candidates(&self) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
+            r#"
+            SELECT * FROM books b
+             WHERE deleted_at IS NULL
+               AND (EXISTS (SELECT 1 FROM books o
+                             WHERE o.author_key = b.author_key AND o.id <> b.id AND o.deleted_at IS NULL)
+                    OR EXISTS (SELECT 1 FROM books o
+                                WHERE o.title_key = b.title_key AND o.id <> b.id AND o.deleted_at IS NULL)
+                    OR EXISTS (SELECT 1 FROM books o
+                                WHERE o.isbn_key = b.isbn_key AND o.id <> b.id AND o.deleted_at IS NULL))
+             ORDER BY created_at, id
+            "#,
+        )
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn count(&self) -> Result<BookCounts, RepoError> {
+        let (active, trashed): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*) - COUNT(deleted_at), COUNT(deleted_at) FROM books",
+        )
+            .fetch_one(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(BookCounts { active, trashed })
+    }
+}
+
+async fn select_book(conn: &mut PgConnection, id: &str) -> Result<Option<Book>, RepoError> {
+    let book = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE id = $1 AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .traced(DB, "SELECT books")
+        .await?;
+    Ok(book)
+}
+
+/// El libro activo tal como está antes de escribirlo, bloqueado hasta el
+/// final de la transacción para que su revisión no se cruce con otra
+async fn lock_book(conn: &mut PgConnection, id: &str) -> Result<Option<Book>, RepoError> {
+    let book = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .traced(DB, "SELECT books")
+        .await?;
+    Ok(book)
+}
+
+/// Como `lock_book`, para un libro de la papelera
+async fn lock_trashed(conn: &mut PgConnection, id: &str) -> Result<Option<Book>, RepoError> {
+    let book = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .traced(DB, "SELECT books")
+        .await?;
+    Ok(book)
+}
+
+async fn insert_book(conn: &mut PgConnection, mut book: Book) -> Result<Book, RepoError> {
+    book.updated_at = book.created_at;
+    sqlx::query(
+        r#"
+        INSERT INTO books (id, title, author, published_year, isbn, created_at, version, updated_at, updated_by,
+                           isbn_key, author_key, title_key)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        "#,
+    )
+        .bind(&book.id)
+        .bind(&book.title)
+        .bind(&book.author)
+        .bind(book.published_year)
+        .bind(&book.isbn)
+        .bind(book.created_at)
+        .bind(book.version)
+        .bind(book.updated_at)
+        .bind(&book.updated_by)
+        .bind(book.isbn.as_deref().map(normalize_isbn))
+        .bind(author_key(&book.author))
+        .bind(title_key(&book.title))
+        .execute(&mut *conn)
+        .traced(DB, "INSERT books")
+        .await?;
+    let actor = book.updated_by.clone().unwrap_or_default();
+    insert_revision(conn, BookRevision::new(RevisionAction::Create, &actor, None, Some(book.clone()))).await?;
+    Ok(book)
+}
+
+async fn update_book(conn: &mut PgConnection, mut book: Book, action: RevisionAction) -> Result<Book, RepoError> {
+    let before = lock_book(conn, &book.id).await?;
+    book.updated_at = book::now();
+    let result = sqlx::query(
+        r#"
+        UPDATE books
+           SET title = $1,
+               author = $2,
+               published_year = $3,
+               isbn = $4,
+               updated_at = $7,
+               updated_by = $8,
+               isbn_key = $9,
+               author_key = $10,
+               title_key = $11,
+               version = version + 1
+         WHERE id = $5
+           AND version = $6
+           AND deleted_at IS NULL
+        "#,
+    )
+        .bind(&book.title)
+        .bind(&book.author)
+        .bind(book.published_year)
+        .bind(&book.isbn)
+        .bind(&book.id)
+        .bind(book.version)
+        .bind(book.updated_at)
+        .bind(&book.updated_by)
+        .bind(book.isbn.as_deref().map(normalize_isbn))
+        .bind(author_key(&book.author))
+        .bind(title_key(&book.title))
+        .execute(&mut *conn)
+        .traced(DB, "UPDATE books")
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(missing_or_stale(conn, book.id).await);
+    }
+    book.version += 1;
+    let actor = book.updated_by.clone().unwrap_or_default();
+    insert_revision(conn, BookRevision::new(action, &actor, before, Some(book.clone()))).await?;
+    Ok(book)
+}
+
+async fn delete_book(
+    conn: &mut PgConnection,
+    id: &str,
+    expected_version: Option<i64>,
+    actor: &str,
+) -> Result<bool, RepoError> {
+    let before = lock_book(conn, id).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE books
+           SET deleted_at = $3, updated_at = $3, updated_by = $4
+         WHERE id = $1
+           AND deleted_at IS NULL
+           AND ($2::BIGINT IS NULL OR version = $2)
+        "#,
+    )
+        .bind(id)
+        .bind(expected_version)
+        .bind(book::now())
+        .bind(actor)
+        .execute(&mut *conn)
+        .traced(DB, "UPDATE books")
+        .await?;
+    if let (true, Some(before)) = (result.rows_affected() > 0, before) {
+        insert_revision(conn, BookRevision::new(RevisionAction::Delete, actor, Some(before), None)).await?;
+        return Ok(true);
+    }
+    match expected_version {
+        Some(_) => match missing_or_stale(conn, id.to_string()).await {
+            RepoError::NotFound(_) => Ok(false),
+            e => Err(e),
+        },
+        None => Ok(false),
+    }
+}
+
+async fn apply_write(conn: &mut PgConnection, write: BookWrite) -> Result<Option<Book>, RepoError> {
+    match write {
+        BookWrite::Create(book) => insert_book(conn, book).await.map(Some),
+        BookWrite::Update(book) => update_book(conn, book, RevisionAction::Update).await.map(Some),
+        BookWrite::Delete { id, expected_version, actor } => {
+            match delete_book(conn, &id, expected_version, &actor).await? {
+                true => Ok(None),
+                false => Err(RepoError::NotFound(id)),
+            }
+        }
+    }
+}
+
+/// Tras un UPDATE condicional sin filas afectadas, distingue entre libro
+/// inexistente y versión desactualizada
+async fn missing_or_stale(conn: &mut PgConnection, id: String) -> RepoError {
+    match select_book(conn, &id).await {
+        Ok(Some(_)) => RepoError::VersionMismatch(id),
+        Ok(None) => RepoError::NotFound(id),
+        Err(e) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostgresBookRepository;
+    use crate::infra::conformance::conformance_suite;
+    use sqlx::{postgres::PgPoolOptions, Executor};
+
+    /// Esquema nuevo en `TEST_POSTGRES_URL`; `None` (test omitido) si no está definida
+    async fn repo() -> Option<PostgresBookRepository> {
+        let url = std::env::var("TEST_POSTGRES_URL").ok()?;
+        let schema = format!("test_{}", uuid::Uuid::new_v4().simple());
+        let search_path = format!("SET search_path TO {}", schema);
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .after_connect(move |conn, _| {
+                let search_path = search_path.clone();
+                Box::pin(async move {
+                    conn.execute(search_path.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await
+            .unwrap();
+        pool.execute(format!("CREATE SCHEMA {}", schema).as_str()).await.unwrap();
+        sqlx::migrate!("./migrations/postgres").run(&pool).await.unwrap();
+        Some(PostgresBookRepository { pool })
+    }
+
+    conformance_suite!(repo().await);
+}
@@ -0,0 +1,119 @@
+use crate::app::{
+    book_repository::RepoError,
+    idempotency_store::{decode_headers, encode_headers, IdempotencyStore, Reservation, StoredResponse},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Row};
+
+pub struct PostgresIdempotencyStore {
+    pub pool: PgPool,
+}
+
+#[async_trait]
+impl IdempotencyStore for PostgresIdempotencyStore {
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        not_before: DateTime<Utc>,
+        lease_before: DateTime<Utc>,
+    ) -> Result<Reservation, RepoError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+            .bind(not_before)
+            .execute(&self.pool)
+            .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (scope, key, request_hash, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+            .bind(scope)
+            .bind(key)
+            .bind(request_hash)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        if inserted.rows_affected() == 1 {
+            return Ok(Reservation::Reserved);
+        }
+
+        // Reserva abandonada: se reclama como si fuera nueva
+        let reclaimed = sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+               SET request_hash = $1,
+                   created_at = $2
+             WHERE scope = $3
+               AND key = $4
+               AND status IS NULL
+               AND created_at < $5
+            "#,
+        )
+            .bind(request_hash)
+            .bind(Utc::now())
+            .bind(scope)
+            .bind(key)
+            .bind(lease_before)
+            .execute(&self.pool)
+            .await?;
+        if reclaimed.rows_affected() == 1 {
+            return Ok(Reservation::Reserved);
+        }
+
+        let row = sqlx::query(
+            "SELECT request_hash, status, headers, body FROM idempotency_keys WHERE scope = $1 AND key = $2",
+        )
+            .bind(scope)
+            .bind(key)
+            .fetch_one(&self.pool)
+            .await?;
+        let status: Option<i32> = row.try_get("status")?;
+        let response = match status {
+            Some(status) => Some(StoredResponse {
+                status: status as u16,
+                headers: decode_headers(row.try_get("headers")?)?,
+                body: row.try_get::<Option<Vec<u8>>, _>("body")?.unwrap_or_default(),
+            }),
+            None => None,
+        };
+        Ok(Reservation::Existing {
+            request_hash: row.try_get("request_hash")?,
+            response,
+        })
+    }
+
+    async fn complete(&self, scope: &str, key: &str, response: &StoredResponse) -> Result<(), RepoError> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+               SET status = $1,
+                   headers = $2,
+                   body = $3
+             WHERE scope = $4
+               AND key = $5
+            "#,
+        )
+            .bind(response.status as i32)
+            .bind(encode_headers(&response.headers)?)
+            .bind(&response.body)
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), RepoError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2")
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
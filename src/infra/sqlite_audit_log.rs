@@ -0,0 +1,137 @@
+use crate::app::{
+    audit_log::{AuditLog, BookRevision, RevisionAction},
+    book_repository::RepoError,
+};
+use crate::infra::{
+    sqlite_book_repository::{to_db, DB},
+    statement::TraceStatement,
+};
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection, SqlitePool};
+
+pub struct SqliteAuditLog {
+    pub pool: SqlitePool,
+}
+
+/// Guarda la revisión en la transacción de la escritura que la produce y la
+/// devuelve con su `id`
+pub(crate) async fn insert_revision(
+    conn: &mut SqliteConnection,
+    mut revision: BookRevision,
+) -> Result<BookRevision, RepoError> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO book_revisions (book_id, action, actor, recorded_at, before, after)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        "#,
+    )
+        .bind(&revision.book_id)
+        .bind(revision.action.as_str())
+        .bind(&revision.actor)
+        .bind(to_db(revision.recorded_at))
+        .bind(to_json(&revision.before)?)
+        .bind(to_json(&revision.after)?)
+        .execute(&mut *conn)
+        .traced(DB, "INSERT book_revisions")
+        .await?;
+    revision.id = result.last_insert_rowid();
+    Ok(revision)
+}
+
+#[async_trait]
+impl AuditLog for SqliteAuditLog {
+    async fn history(&self, book_id: &str) -> Result<Vec<BookRevision>, RepoError> {
+        let rows = sqlx::query("SELECT * FROM book_revisions WHERE book_id = ? ORDER BY id")
+            .bind(book_id)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT book_revisions")
+            .await?;
+        rows.iter().map(from_row).collect()
+    }
+
+    async fn get(&self, book_id: &str, revision_id: i64) -> Result<Option<BookRevision>, RepoError> {
+        let row = sqlx::query("SELECT * FROM book_revisions WHERE book_id = ? AND id = ?")
+            .bind(book_id)
+            .bind(revision_id)
+            .fetch_optional(&self.pool)
+            .traced(DB, "SELECT book_revisions")
+            .await?;
+        row.as_ref().map(from_row).transpose()
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &Option<T>) -> Result<Option<String>, RepoError> {
+    value
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| RepoError::Other(e.into()))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: Option<String>) -> Result<Option<T>, RepoError> {
+    value
+        .map(|v| serde_json::from_str(&v))
+        .transpose()
+        .map_err(|e| RepoError::Other(e.into()))
+}
+
+fn from_row(row: &SqliteRow) -> Result<BookRevision, RepoError> {
+    let action: String = row.try_get("action")?;
+    Ok(BookRevision {
+        id: row.try_get("id")?,
+        book_id: row.try_get("book_id")?,
+        action: RevisionAction::parse(&action)
+            .ok_or_else(|| RepoError::Other(anyhow::anyhow!("Unknown revision action {}", action)))?,
+        actor: row.try_get("actor")?,
+        recorded_at: row.try_get("recorded_at")?,
+        before: from_json(row.try_get("before")?)?,
+        after: from_json(row.try_get("after")?)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_revision, SqliteAuditLog};
+    use crate::{
+        app::audit_log::{AuditLog, BookRevision, RevisionAction},
+        domain::book::Book,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn revisions_round_trip_in_order() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+
+        let book = Book::new("Dune".into(), "Frank Herbert".into(), None);
+        let mut renamed = book.clone();
+        renamed.title = "Dune Messiah".into();
+
+        insert_revision(&mut conn, BookRevision::new(RevisionAction::Create, "admin", None, Some(book.clone())))
+            .await
+            .unwrap();
+        let update = insert_revision(
+            &mut conn,
+            BookRevision::new(RevisionAction::Update, "admin", Some(book.clone()), Some(renamed)),
+        )
+            .await
+            .unwrap();
+        drop(conn);
+        let log = SqliteAuditLog { pool };
+
+        let history = log.history(&book.id).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].action, RevisionAction::Create);
+        assert!(history[0].before.is_none());
+        assert_eq!(history[1].after.as_ref().unwrap().title, "Dune Messiah");
+
+        let fetched = log.get(&book.id, update.id).await.unwrap().unwrap();
+        assert_eq!(fetched.before.unwrap().title, "Dune");
+        assert!(log.get("otro", update.id).await.unwrap().is_none());
+    }
+}
@@ -1,10 +1,20 @@
 use crate::{
-    app::book_repository::BookRepository,
-    domain::book::Book,
+    app::{
+        audit_log::{BookRevision, RevisionAction},
+        book_repository::{BatchResult, BookCounts, BookFilter, BookRepository, BookWrite, RepoError},
+    },
+    domain::{
+        book::{self, Book},
+        duplicates::{author_key, normalize_isbn, title_key},
+    },
+    infra::{sqlite_audit_log::insert_revision, statement::TraceStatement},
 };
 use async_trait::async_trait;
-use sqlx::SqlitePool;
-use anyhow::Error;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+
+/// `db.system` de los spans de cada sentencia
+pub(crate) const DB: &str = "sqlite";
 
 pub struct SqliteBookRepository {
     pub pool: SqlitePool,
@@ -12,95 +22,516 @@ pub struct SqliteBookRepository {
 
 #[async_trait]
 impl BookRepository for SqliteBookRepository {
-    async fn get_all(&self) -> Result<Vec<Book>, Error> {
-        let books = sqlx::query_as::<_, Book>("SELECT * FROM books")
+    async fn get_all(&self) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE deleted_at IS NULL")
             .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
             .await?;
         Ok(books)
     }
 
-    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, Error> {
-        let book = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE id = ?")
+    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, RepoError> {
+        let mut conn = self.pool.acquire().await?;
+        select_book(&mut conn, id).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Book>, RepoError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let sql = format!("SELECT * FROM books WHERE deleted_at IS NULL AND id IN ({})", placeholders);
+
+        let mut query = sqlx::query_as::<_, Book>(&sql);
+        for id in ids {
+            query = query.bind(id);
+        }
+
+        let books = query.fetch_all(&self.pool).traced(DB, "SELECT books").await?;
+        Ok(books)
+    }
+
+    async fn list_page(&self, after: Option<&str>, limit: i64) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
+            "SELECT * FROM books WHERE deleted_at IS NULL AND (?1 IS NULL OR id > ?1) ORDER BY id LIMIT ?2",
+        )
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn create(&self, book: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let saved = insert_book(&mut tx, book).await?;
+        tx.commit().await?;
+        Ok(saved)
+    }
+
+    async fn update(&self, book: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let updated = update_book(&mut tx, book, RevisionAction::Update).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn revert(&self, book: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let updated = update_book(&mut tx, book, RevisionAction::Revert).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete(&self, id: &str, expected_version: Option<i64>, actor: &str) -> Result<bool, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = delete_book(&mut tx, id, expected_version, actor).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn write_batch(&self, writes: Vec<BookWrite>, atomic: bool) -> Result<BatchResult, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(writes.len());
+
+        for write in writes {
+            // En modo best-effort cada escritura va en su propio savepoint
+            let result = if atomic {
+                apply_write(&mut tx, write).await
+            } else {
+                let mut savepoint = Connection::begin(&mut *tx).await?;
+                let result = apply_write(&mut savepoint, write).await;
+                match result {
+                    Ok(_) => savepoint.commit().await?,
+                    Err(_) => savepoint.rollback().await?,
+                }
+                result
+            };
+            let failed = result.is_err();
+            results.push(result);
+            if atomic && failed {
+                tx.rollback().await?;
+                return Ok(BatchResult { results, committed: false });
+            }
+        }
+
+        tx.commit().await?;
+        Ok(BatchResult { results, committed: true })
+    }
+
+    async fn merge(&self, source_id: &str, target: Book) -> Result<Book, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let target_id = target.id.clone();
+        let actor = target.updated_by.clone().unwrap_or_default();
+        let merged = update_book(&mut tx, target, RevisionAction::Merge).await?;
+        if !delete_book(&mut tx, source_id, None, &actor).await? {
+            return Err(RepoError::NotFound(source_id.to_string()));
+        }
+        // Evita cadenas: lo que apuntaba al origen pasa a apuntar al destino
+        sqlx::query("UPDATE book_redirects SET new_id = ?1 WHERE new_id = ?2")
+            .bind(&target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .traced(DB, "UPDATE book_redirects")
+            .await?;
+        sqlx::query("INSERT INTO book_redirects (old_id, new_id, created_at) VALUES (?1, ?2, ?3)")
+            .bind(source_id)
+            .bind(&target_id)
+            .bind(to_db(book::now()))
+            .execute(&mut *tx)
+            .traced(DB, "INSERT book_redirects")
+            .await?;
+        tx.commit().await?;
+        Ok(merged)
+    }
+
+    async fn list_trash(&self, deleted_since: Option<DateTime<Utc>>) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
+            "SELECT * FROM books WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at >= ?1) ORDER BY deleted_at DESC",
+        )
+            .bind(deleted_since.map(to_db))
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
+            .await?;
+        Ok(books)
+    }
+
+    async fn restore(&self, id: &str, actor: &str) -> Result<Option<Book>, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = select_trashed(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        let result = sqlx::query(
+            "UPDATE books SET deleted_at = NULL, updated_at = ?2, updated_by = ?3 WHERE id = ?1 AND deleted_at IS NOT NULL",
+        )
             .bind(id)
-            .fetch_optional(&self.pool)
+            .bind(to_db(book::now()))
+            .bind(actor)
+            .execute(&mut *tx)
+            .traced(DB, "UPDATE books")
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        // Un libro fusionado que se recupera deja de redirigir al destino
+        sqlx::query("DELETE FROM book_redirects WHERE old_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .traced(DB, "DELETE book_redirects")
             .await?;
+        let book = select_book(&mut tx, id).await?;
+        insert_revision(&mut tx, BookRevision::new(RevisionAction::Restore, actor, Some(before), book.clone())).await?;
+        tx.commit().await?;
         Ok(book)
     }
 
-    async fn create(&self, book: Book) -> Result<Book, Error> {
-        sqlx::query(
+    async fn purge(&self, id: &str, actor: &str) -> Result<bool, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = select_trashed(&mut tx, id).await? else {
+            return Ok(false);
+        };
+        sqlx::query("DELETE FROM books WHERE id = ? AND deleted_at IS NOT NULL")
+            .bind(id)
+            .execute(&mut *tx)
+            .traced(DB, "DELETE books")
+            .await?;
+        insert_revision(&mut tx, BookRevision::new(RevisionAction::Purge, actor, Some(before), None)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn resolve_redirect(&self, id: &str) -> Result<Option<String>, RepoError> {
+        let new_id = sqlx::query_scalar::<_, String>("SELECT new_id FROM book_redirects WHERE old_id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .traced(DB, "SELECT book_redirects")
+            .await?;
+        Ok(new_id)
+    }
+
+    async fn search(&self, filter: &BookFilter) -> Result<Vec<Book>, RepoError> {
+        let mut sql = String::from("SELECT * FROM books WHERE deleted_at IS NULL");
+        let mut binds = Vec::new();
+
+        if let Some(t) = &filter.title {
+            sql.push_str(" AND title LIKE '%' || ? || '%'");
+            binds.push(t.clone());
+        }
+        if let Some(a) = &filter.author {
+            sql.push_str(" AND author LIKE '%' || ? || '%'");
+            binds.push(a.clone());
+        }
+        if let Some(since) = filter.updated_since {
+            sql.push_str(" AND updated_at >= ?");
+            binds.push(to_db(since));
+        }
+        if let Some(from) = filter.created_from {
+            sql.push_str(" AND created_at >= ?");
+            binds.push(to_db(from));
+        }
+        if let Some(to) = filter.created_to {
+            sql.push_str(" AND created_at < ?");
+            binds.push(to_db(to));
+        }
+        sql.push_str(" ORDER BY created_at, id");
+
+        let mut query = sqlx::query_as::<_, Book>(&sql);
+        for b in binds {
+            query = query.bind(b);
+        }
+
+        let books = query.fetch_all(&self.pool).traced(DB, "SELECT books").await?;
+        Ok(books)
+    }
+
+    async fn duplicate_candidates(&self, book: &Book) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
             r#"
-            INSERT INTO books (id, title, author, published_year, created_at)
-            VALUES (?1, ?2, ?3, ?4, ?5)
+            SELECT * FROM books
+             WHERE deleted_at IS NULL
+               AND id <> ?1
+               AND (author_key = ?2 OR title_key = ?3 OR isbn_key = ?4)
+             ORDER BY created_at, id
             "#,
         )
             .bind(&book.id)
-            .bind(&book.title)
-            .bind(&book.author)
-            .bind(book.published_year)
-            .bind(&book.created_at)
-            .execute(&self.pool)
+            .bind(author_key(&book.author))
+            .bind(title_key(&book.title))
+            .bind(book.isbn.as_deref().map(normalize_isbn))
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
             .await?;
-        Ok(book)
+        Ok(books)
     }
 
-    async fn update(&self, book: Book) -> Result<Book, Error> {
-        sqlx::query(
+    async fn all_duplicate_candidates(&self) -> Result<Vec<Book>, RepoError> {
+        let books = sqlx::query_as::<_, Book>(
             r#"
-            UPDATE books
-               SET title = ?1,
-                   author = ?2,
-                   published_year = ?3
-             WHERE id = ?4
+            SELECT * FROM books b
+             WHERE deleted_at IS NULL
+               AND (EXISTS (SELECT 1 FROM books o
+                             WHERE o.author_key = b.author_key AND o.id <> b.id AND o.deleted_at IS NULL)
+                    OR EXISTS (SELECT 1 FROM books o
+                                WHERE o.title_key = b.title_key AND o.id <> b.id AND o.deleted_at IS NULL)
+                    OR EXISTS (SELECT 1 FROM books o
+                                WHERE o.isbn_key = b.isbn_key AND o.id <> b.id AND o.deleted_at IS NULL))
+             ORDER BY created_at, id
             "#,
         )
-            .bind(&book.title)
-            .bind(&book.author)
-            .bind(book.published_year)
-            .bind(&book.id)
-            .execute(&self.pool)
+            .fetch_all(&self.pool)
+            .traced(DB, "SELECT books")
             .await?;
-        Ok(book)
+        Ok(books)
     }
 
-    async fn delete(&self, id: &str) -> Result<(), Error> {
-        sqlx::query("DELETE FROM books WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
+    async fn count(&self) -> Result<BookCounts, RepoError> {
+        let (active, trashed): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*) - COUNT(deleted_at), COUNT(deleted_at) FROM books",
+        )
+            .fetch_one(&self.pool)
+            .traced(DB, "SELECT books")
             .await?;
-        Ok(())
+        Ok(BookCounts { active, trashed })
     }
+}
 
-    async fn search(
-        &self,
-        title: Option<&str>,
-        author: Option<&str>,
-    ) -> Result<Vec<Book>, Error> {
-        let mut sql = String::from("SELECT * FROM books");
-        let mut binds = Vec::new();
+async fn select_book(conn: &mut SqliteConnection, id: &str) -> Result<Option<Book>, RepoError> {
+    let book = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .traced(DB, "SELECT books")
+        .await?;
+    Ok(book)
+}
 
-        if title.is_some() || author.is_some() {
-            sql.push_str(" WHERE ");
-            let mut clauses = Vec::new();
+async fn select_trashed(conn: &mut SqliteConnection, id: &str) -> Result<Option<Book>, RepoError> {
+    let book = sqlx::query_as::<_, Book>("SELECT * FROM books WHERE id = ? AND deleted_at IS NOT NULL")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .traced(DB, "SELECT books")
+        .await?;
+    Ok(book)
+}
 
-            if let Some(t) = title {
-                clauses.push("title LIKE '%' || ? || '%'");
-                binds.push(t);
-            }
-            if let Some(a) = author {
-                clauses.push("author LIKE '%' || ? || '%'");
-                binds.push(a);
+async fn insert_book(conn: &mut SqliteConnection, mut book: Book) -> Result<Book, RepoError> {
+    book.updated_at = book.created_at;
+    sqlx::query(
+        r#"
+        INSERT INTO books (id, title, author, published_year, isbn, created_at, version, updated_at, updated_by,
+                           isbn_key, author_key, title_key)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#,
+    )
+        .bind(&book.id)
+        .bind(&book.title)
+        .bind(&book.author)
+        .bind(book.published_year)
+        .bind(&book.isbn)
+        .bind(to_db(book.created_at))
+        .bind(book.version)
+        .bind(to_db(book.updated_at))
+        .bind(&book.updated_by)
+        .bind(book.isbn.as_deref().map(normalize_isbn))
+        .bind(author_key(&book.author))
+        .bind(title_key(&book.title))
+        .execute(&mut *conn)
+        .traced(DB, "INSERT books")
+        .await?;
+    let actor = book.updated_by.clone().unwrap_or_default();
+    insert_revision(conn, BookRevision::new(RevisionAction::Create, &actor, None, Some(book.clone()))).await?;
+    Ok(book)
+}
+
+async fn update_book(conn: &mut SqliteConnection, mut book: Book, action: RevisionAction) -> Result<Book, RepoError> {
+    let before = select_book(conn, &book.id).await?;
+    book.updated_at = book::now();
+    let result = sqlx::query(
+        r#"
+        UPDATE books
+           SET title = ?1,
+               author = ?2,
+               published_year = ?3,
+               isbn = ?4,
+               updated_at = ?7,
+               updated_by = ?8,
+               isbn_key = ?9,
+               author_key = ?10,
+               title_key = ?11,
+               version = version + 1
+         WHERE id = ?5
+           AND version = ?6
+           AND deleted_at IS NULL
+        "#,
+    )
+        .bind(&book.title)
+        .bind(&book.author)
+        .bind(book.published_year)
+        .bind(&book.isbn)
+        .bind(&book.id)
+        .bind(book.version)
+        .bind(to_db(book.updated_at))
+        .bind(&book.updated_by)
+        .bind(book.isbn.as_deref().map(normalize_isbn))
+        .bind(author_key(&book.author))
+        .bind(title_key(&book.title))
+        .execute(&mut *conn)
+        .traced(DB, "UPDATE books")
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(missing_or_stale(conn, book.id).await);
+    }
+    book.version += 1;
+    let actor = book.updated_by.clone().unwrap_or_default();
+    insert_revision(conn, BookRevision::new(action, &actor, before, Some(book.clone()))).await?;
+    Ok(book)
+}
+
+async fn delete_book(
+    conn: &mut SqliteConnection,
+    id: &str,
+    expected_version: Option<i64>,
+    actor: &str,
+) -> Result<bool, RepoError> {
+    let before = select_book(conn, id).await?;
+    let result = sqlx::query(
+        r#"
+        UPDATE books
+           SET deleted_at = ?3, updated_at = ?3, updated_by = ?4
+         WHERE id = ?1
+           AND deleted_at IS NULL
+           AND (?2 IS NULL OR version = ?2)
+        "#,
+    )
+        .bind(id)
+        .bind(expected_version)
+        .bind(to_db(book::now()))
+        .bind(actor)
+        .execute(&mut *conn)
+        .traced(DB, "UPDATE books")
+        .await?;
+    if let (true, Some(before)) = (result.rows_affected() > 0, before) {
+        insert_revision(conn, BookRevision::new(RevisionAction::Delete, actor, Some(before), None)).await?;
+        return Ok(true);
+    }
+    match expected_version {
+        Some(_) => match missing_or_stale(conn, id.to_string()).await {
+            RepoError::NotFound(_) => Ok(false),
+            e => Err(e),
+        },
+        None => Ok(false),
+    }
+}
+
+async fn apply_write(conn: &mut SqliteConnection, write: BookWrite) -> Result<Option<Book>, RepoError> {
+    match write {
+        BookWrite::Create(book) => insert_book(conn, book).await.map(Some),
+        BookWrite::Update(book) => update_book(conn, book, RevisionAction::Update).await.map(Some),
+        BookWrite::Delete { id, expected_version, actor } => {
+            match delete_book(conn, &id, expected_version, &actor).await? {
+                true => Ok(None),
+                false => Err(RepoError::NotFound(id)),
             }
+        }
+    }
+}
+
+/// Formato de las columnas de fecha: RFC 3339 en UTC con milisegundos. Al
+/// tener siempre el mismo ancho, comparar como texto es comparar fechas.
+pub(crate) fn to_db(ts: DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Tras un UPDATE/DELETE condicional sin filas afectadas, distingue
+/// entre libro inexistente y versión desactualizada
+async fn missing_or_stale(conn: &mut SqliteConnection, id: String) -> RepoError {
+    match select_book(conn, &id).await {
+        Ok(Some(_)) => RepoError::VersionMismatch(id),
+        Ok(None) => RepoError::NotFound(id),
+        Err(e) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteBookRepository;
+    use crate::{
+        app::book_repository::BookRepository,
+        domain::book::Book,
+        infra::conformance::conformance_suite,
+        telemetry::Recorder,
+    };
+    use opentelemetry::{
+        trace::{SpanKind, Status, TraceContextExt},
+        Value,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tracing::Instrument;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    async fn repo() -> SqliteBookRepository {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+        SqliteBookRepository { pool }
+    }
+
+    conformance_suite!(Some(repo().await));
+
+    #[tokio::test]
+    async fn stored_timestamps_round_trip_and_sort_as_text() {
+        let repo = repo().await;
+        let book = repo
+            .create(Book::new("Dune".into(), "Frank Herbert".into(), None))
+            .await
+            .unwrap();
+        assert_eq!(repo.get_by_id(&book.id).await.unwrap().unwrap().created_at, book.created_at);
 
-            sql.push_str(&clauses.join(" AND "));
+        let raw: String = sqlx::query_scalar("SELECT created_at FROM books WHERE id = ?")
+            .bind(&book.id)
+            .fetch_one(&repo.pool)
+            .await
+            .unwrap();
+        assert_eq!(raw.len(), "2025-01-31T09:30:00.000Z".len());
+        assert!(raw.ends_with('Z'));
+    }
+
+    // `current_thread`: el subscriber por defecto es del hilo
+    #[tokio::test(flavor = "current_thread")]
+    async fn statements_are_traced_as_children_of_the_current_span() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.layer()));
+        let repo = repo().await;
+
+        let call = tracing::info_span!("call");
+        let parent = call.context().span().span_context().span_id();
+        async {
+            repo.create(Book::new("Dune".into(), "Frank Herbert".into(), None)).await.unwrap();
+            sqlx::query("DROP TABLE book_redirects").execute(&repo.pool).await.unwrap();
+            assert!(repo.resolve_redirect("missing").await.is_err());
         }
+        .instrument(call)
+        .await;
+        // El hilo de cada conexión guarda el span de su último comando hasta recibir otro
+        repo.pool.close().await;
 
-        let mut query = sqlx::query_as::<_, Book>(&sql);
-        for &b in &binds {
-            query = query.bind(b);
+        let spans = recorder.spans();
+        let named = |name: &str| spans.iter().find(|s| s.name == name).unwrap_or_else(|| panic!("{}", name));
+        for statement in ["INSERT books", "INSERT book_revisions"].map(named) {
+            assert_eq!(statement.parent_span_id, parent);
+            assert_eq!(statement.span_kind, SpanKind::Client);
+            let attribute = |key: &str| statement.attributes.iter().find(|kv| kv.key.as_str() == key).unwrap().value.clone();
+            assert_eq!(attribute("db.system"), Value::from("sqlite"));
+            assert_eq!(attribute("db.operation.name"), Value::from("INSERT"));
+            assert_eq!(attribute("db.query.summary"), Value::from(statement.name.to_string()));
         }
 
-        let books = query.fetch_all(&self.pool).await?;
-        Ok(books)
+        let lookup = named("SELECT book_redirects");
+        assert_eq!(lookup.parent_span_id, parent);
+        assert!(matches!(lookup.status, Status::Error { .. }));
     }
 }
@@ -0,0 +1,200 @@
+use crate::{
+    app::{
+        book_repository::RepoError,
+        idempotency_store::{decode_headers, encode_headers, IdempotencyStore, Reservation, StoredResponse},
+    },
+    infra::sqlite_book_repository::to_db,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+pub struct SqliteIdempotencyStore {
+    pub pool: SqlitePool,
+}
+
+#[async_trait]
+impl IdempotencyStore for SqliteIdempotencyStore {
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        not_before: DateTime<Utc>,
+        lease_before: DateTime<Utc>,
+    ) -> Result<Reservation, RepoError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE created_at < ?")
+            .bind(to_db(not_before))
+            .execute(&self.pool)
+            .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO idempotency_keys (scope, key, request_hash, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+        )
+            .bind(scope)
+            .bind(key)
+            .bind(request_hash)
+            .bind(to_db(Utc::now()))
+            .execute(&self.pool)
+            .await?;
+        if inserted.rows_affected() == 1 {
+            return Ok(Reservation::Reserved);
+        }
+
+        // Reserva abandonada: se reclama como si fuera nueva
+        let reclaimed = sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+               SET request_hash = ?1,
+                   created_at = ?2
+             WHERE scope = ?3
+               AND key = ?4
+               AND status IS NULL
+               AND created_at < ?5
+            "#,
+        )
+            .bind(request_hash)
+            .bind(to_db(Utc::now()))
+            .bind(scope)
+            .bind(key)
+            .bind(to_db(lease_before))
+            .execute(&self.pool)
+            .await?;
+        if reclaimed.rows_affected() == 1 {
+            return Ok(Reservation::Reserved);
+        }
+
+        let row = sqlx::query(
+            "SELECT request_hash, status, headers, body FROM idempotency_keys WHERE scope = ? AND key = ?",
+        )
+            .bind(scope)
+            .bind(key)
+            .fetch_one(&self.pool)
+            .await?;
+        let status: Option<i64> = row.try_get("status")?;
+        let response = match status {
+            Some(status) => Some(StoredResponse {
+                status: status as u16,
+                headers: decode_headers(row.try_get("headers")?)?,
+                body: row.try_get::<Option<Vec<u8>>, _>("body")?.unwrap_or_default(),
+            }),
+            None => None,
+        };
+        Ok(Reservation::Existing {
+            request_hash: row.try_get("request_hash")?,
+            response,
+        })
+    }
+
+    async fn complete(&self, scope: &str, key: &str, response: &StoredResponse) -> Result<(), RepoError> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+               SET status = ?1,
+                   headers = ?2,
+                   body = ?3
+             WHERE scope = ?4
+               AND key = ?5
+            "#,
+        )
+            .bind(response.status as i64)
+            .bind(encode_headers(&response.headers)?)
+            .bind(&response.body)
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), RepoError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE scope = ? AND key = ?")
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteIdempotencyStore;
+    use crate::{
+        app::idempotency_store::{IdempotencyStore, Reservation, StoredResponse},
+        infra::sqlite_book_repository::to_db,
+    };
+    use chrono::{Duration, Utc};
+    use sqlx::{sqlite::SqlitePoolOptions, Executor};
+
+    #[tokio::test]
+    async fn abandoned_reservations_are_reclaimed_and_headers_round_trip() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+        let store = SqliteIdempotencyStore { pool };
+        let at = |secs: i64| Utc::now() + Duration::seconds(secs);
+        let long_ago = at(-3600);
+
+        // Dentro del plazo la reserva sigue siendo de la petición original
+        assert!(matches!(store.reserve("s", "k", "a", long_ago, at(-60)).await.unwrap(), Reservation::Reserved));
+        assert!(matches!(
+            store.reserve("s", "k", "a", long_ago, at(-60)).await.unwrap(),
+            Reservation::Existing { response: None, .. }
+        ));
+
+        // Pasado el plazo la reclama otra, aunque el cuerpo sea distinto
+        assert!(matches!(store.reserve("s", "k", "b", long_ago, at(1)).await.unwrap(), Reservation::Reserved));
+
+        let response = StoredResponse {
+            status: 201,
+            headers: vec![("etag".into(), "\"1\"".into()), ("location".into(), "/books/1".into())],
+            body: b"{}".to_vec(),
+        };
+        store.complete("s", "k", &response).await.unwrap();
+
+        // Con respuesta ya no caduca la reserva, solo la retención
+        let Reservation::Existing { request_hash, response: Some(stored) } =
+            store.reserve("s", "k", "b", long_ago, at(1)).await.unwrap()
+        else {
+            panic!("expected a stored response");
+        };
+        assert_eq!(request_hash, "b");
+        assert_eq!(stored.headers, response.headers);
+    }
+
+    #[tokio::test]
+    async fn keys_stored_before_normalizing_timestamps_still_expire() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+        // Como las guardaba `to_rfc3339`: nanosegundos y `+00:00`
+        let created_at = Utc::now() - Duration::minutes(5);
+        sqlx::query("INSERT INTO idempotency_keys (scope, key, request_hash, created_at) VALUES ('s', 'k', 'a', ?)")
+            .bind(created_at.to_rfc3339())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        pool.execute(include_str!("../../migrations/sqlite/20251029100000_normalize_idempotency_timestamps.up.sql"))
+            .await
+            .unwrap();
+        let stored: String = sqlx::query_scalar("SELECT created_at FROM idempotency_keys").fetch_one(&pool).await.unwrap();
+        assert_eq!(stored, to_db(created_at));
+
+        // Reserva abandonada hace más de un minuto: se reclama
+        let store = SqliteIdempotencyStore { pool };
+        let now = Utc::now();
+        let reservation = store.reserve("s", "k", "b", now - Duration::hours(1), now - Duration::minutes(1)).await.unwrap();
+        assert!(matches!(reservation, Reservation::Reserved));
+    }
+}
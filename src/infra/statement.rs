@@ -0,0 +1,53 @@
+use pin_project_lite::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tracing::{field::Empty, instrument::Instrumented, Instrument};
+
+pin_project! {
+    /// Sentencia SQL en curso dentro de su span
+    pub struct Traced<F> {
+        #[pin]
+        inner: Instrumented<F>,
+    }
+}
+
+/// Span de cliente por sentencia SQL, hijo del de la llamada al repositorio.
+/// `summary` es la operación y la tabla (`SELECT books`), como
+/// `db.query.summary` en las convenciones de OpenTelemetry; no lleva la
+/// sentencia entera para no exponer valores ni abrir una serie por consulta.
+pub(crate) trait TraceStatement: Future + Sized {
+    fn traced(self, system: &'static str, summary: &'static str) -> Traced<Self> {
+        let operation = summary.split(' ').next().unwrap_or(summary);
+        let span = tracing::info_span!(
+            "statement",
+            otel.name = summary,
+            otel.kind = "client",
+            otel.status_code = Empty,
+            db.system = system,
+            db.operation.name = operation,
+            db.query.summary = summary,
+        );
+        Traced { inner: self.instrument(span) }
+    }
+}
+
+impl<F: Future> TraceStatement for F {}
+
+impl<F, T> Future for Traced<F>
+where
+    F: Future<Output = Result<T, sqlx::Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let result = ready!(this.inner.as_mut().poll(cx));
+        if result.is_err() {
+            this.inner.span().record("otel.status_code", "ERROR");
+        }
+        Poll::Ready(result)
+    }
+}
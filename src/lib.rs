@@ -5,3 +5,8 @@ pub mod infra;
 pub mod handlers;
 pub mod middleware;
 pub mod error;
+pub mod extract;
+pub mod grpc;
+pub mod shutdown;
+pub mod metrics;
+pub mod telemetry;
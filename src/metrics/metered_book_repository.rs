@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{future::Future, sync::Arc, time::Instant};
+use tracing::{field::Empty, Instrument};
+
+use crate::{
+    app::book_repository::{BatchResult, BookCounts, BookFilter, BookRepository, BookWrite, RepoError},
+    domain::book::Book,
+    metrics::Metrics,
+};
+
+/// Envoltorio de cualquier `BookRepository` que mide cada llamada y la traza
+/// como span hijo de la petición
+pub struct MeteredBookRepository<R> {
+    inner: Arc<R>,
+    metrics: Arc<Metrics>,
+}
+
+impl<R: BookRepository> MeteredBookRepository<R> {
+    pub fn new(inner: Arc<R>, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// El repositorio envuelto, para lo que no debe contar como llamada
+    pub fn inner(&self) -> &Arc<R> {
+        &self.inner
+    }
+
+    async fn timed<T>(
+        &self,
+        method: &'static str,
+        call: impl Future<Output = Result<T, RepoError>>,
+    ) -> Result<T, RepoError> {
+        // El nombre de la operación es el del método; cada sentencia abre su span hijo (ver `infra::statement`)
+        let span = tracing::info_span!(
+            "repository",
+            otel.name = %format!("BookRepository.{}", method),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            db.operation.name = method,
+        );
+        let start = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        self.metrics.observe_repository(method, result.is_ok(), start.elapsed());
+        if result.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<R: BookRepository> BookRepository for MeteredBookRepository<R> {
+    async fn get_all(&self) -> Result<Vec<Book>, RepoError> {
+        self.timed("get_all", self.inner.get_all()).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Option<Book>, RepoError> {
+        self.timed("get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Book>, RepoError> {
+        self.timed("get_by_ids", self.inner.get_by_ids(ids)).await
+    }
+
+    async fn list_page(&self, after: Option<&str>, limit: i64) -> Result<Vec<Book>, RepoError> {
+        self.timed("list_page", self.inner.list_page(after, limit)).await
+    }
+
+    async fn create(&self, book: Book) -> Result<Book, RepoError> {
+        self.timed("create", self.inner.create(book)).await
+    }
+
+    async fn update(&self, book: Book) -> Result<Book, RepoError> {
+        self.timed("update", self.inner.update(book)).await
+    }
+
+    async fn revert(&self, book: Book) -> Result<Book, RepoError> {
+        self.timed("revert", self.inner.revert(book)).await
+    }
+
+    async fn delete(&self, id: &str, expected_version: Option<i64>, actor: &str) -> Result<bool, RepoError> {
+        self.timed("delete", self.inner.delete(id, expected_version, actor)).await
+    }
+
+    async fn write_batch(&self, writes: Vec<BookWrite>, atomic: bool) -> Result<BatchResult, RepoError> {
+        self.timed("write_batch", self.inner.write_batch(writes, atomic)).await
+    }
+
+    async fn merge(&self, source_id: &str, target: Book) -> Result<Book, RepoError> {
+        self.timed("merge", self.inner.merge(source_id, target)).await
+    }
+
+    async fn resolve_redirect(&self, id: &str) -> Result<Option<String>, RepoError> {
+        self.timed("resolve_redirect", self.inner.resolve_redirect(id)).await
+    }
+
+    async fn list_trash(&self, deleted_since: Option<DateTime<Utc>>) -> Result<Vec<Book>, RepoError> {
+        self.timed("list_trash", self.inner.list_trash(deleted_since)).await
+    }
+
+    async fn restore(&self, id: &str, actor: &str) -> Result<Option<Book>, RepoError> {
+        self.timed("restore", self.inner.restore(id, actor)).await
+    }
+
+    async fn purge(&self, id: &str, actor: &str) -> Result<bool, RepoError> {
+        self.timed("purge", self.inner.purge(id, actor)).await
+    }
+
+    async fn search(&self, filter: &BookFilter) -> Result<Vec<Book>, RepoError> {
+        self.timed("search", self.inner.search(filter)).await
+    }
+
+    async fn duplicate_candidates(&self, book: &Book) -> Result<Vec<Book>, RepoError> {
+        self.timed("duplicate_candidates", self.inner.duplicate_candidates(book)).await
+    }
+
+    async fn all_duplicate_candidates(&self) -> Result<Vec<Book>, RepoError> {
+        self.timed("all_duplicate_candidates", self.inner.all_duplicate_candidates()).await
+    }
+
+    async fn count(&self) -> Result<BookCounts, RepoError> {
+        self.timed("count", self.inner.count()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MeteredBookRepository;
+    use crate::{
+        infra::{conformance::conformance_suite, memory_book_repository::InMemoryBookRepository},
+        metrics::Metrics,
+    };
+    use std::sync::Arc;
+
+    fn repo() -> MeteredBookRepository<InMemoryBookRepository> {
+        MeteredBookRepository::new(Arc::new(InMemoryBookRepository::new()), Arc::new(Metrics::new()))
+    }
+
+    // El envoltorio no debe cambiar el comportamiento del repositorio
+    conformance_suite!(Some(repo()));
+}
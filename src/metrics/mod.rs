@@ -0,0 +1,236 @@
+//! Métricas en el formato de texto de Prometheus, sin dependencias: la app
+//! acumula contadores e histogramas y `/metrics` los vuelca junto con los
+//! gauges que se calculan en el momento.
+
+pub mod metered_book_repository;
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// Límites superiores de los buckets de latencia, en segundos
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// No acumulados: `buckets[i]` cuenta las muestras entre `BUCKETS[i-1]` y `BUCKETS[i]`
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|le| secs <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+/// Petición HTTP ya atendida
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey {
+    method: String,
+    /// Plantilla de la ruta (`/books/:id`), no la URL: acota las series
+    route: String,
+    status: u16,
+}
+
+#[derive(Default)]
+struct Registry {
+    requests: BTreeMap<RequestKey, Histogram>,
+    /// Por `AppError::code`
+    errors: BTreeMap<&'static str, u64>,
+    /// Por método de `BookRepository` y resultado (`ok` o `error`)
+    repository: BTreeMap<(&'static str, &'static str), Histogram>,
+    /// Llamadas gRPC por método (`/paquete.Servicio/Método`) y código de estado
+    grpc: BTreeMap<(String, String), Histogram>,
+}
+
+/// Valor calculado al servir `/metrics` (conexiones del pool, libros...)
+pub struct Gauge {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Un panic con el lock tomado no invalida contadores ya sumados
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let key = RequestKey { method: method.into(), route: route.into(), status };
+        self.registry().requests.entry(key).or_default().observe(elapsed);
+    }
+
+    pub fn count_error(&self, code: &'static str) {
+        *self.registry().errors.entry(code).or_default() += 1;
+    }
+
+    pub fn observe_grpc(&self, method: &str, code: &str, elapsed: Duration) {
+        self.registry().grpc.entry((method.into(), code.into())).or_default().observe(elapsed);
+    }
+
+    pub fn observe_repository(&self, method: &'static str, ok: bool, elapsed: Duration) {
+        let outcome = if ok { "ok" } else { "error" };
+        self.registry().repository.entry((method, outcome)).or_default().observe(elapsed);
+    }
+
+    /// Exposición completa: lo acumulado más `gauges`
+    pub fn render(&self, gauges: &[Gauge]) -> String {
+        let registry = self.registry();
+        let mut out = String::new();
+
+        header(&mut out, "http_requests_total", "counter", "Peticiones HTTP atendidas");
+        for (key, histogram) in &registry.requests {
+            let labels = request_labels(key);
+            sample(&mut out, "http_requests_total", &labels, histogram.count as f64);
+        }
+        header(&mut out, "http_request_duration_seconds", "histogram", "Latencia de las peticiones HTTP");
+        for (key, histogram) in &registry.requests {
+            write_histogram(&mut out, "http_request_duration_seconds", &request_labels(key), histogram);
+        }
+
+        header(&mut out, "grpc_requests_total", "counter", "Llamadas gRPC atendidas");
+        for ((method, code), histogram) in &registry.grpc {
+            let labels = [("method", method.clone()), ("code", code.clone())];
+            sample(&mut out, "grpc_requests_total", &labels, histogram.count as f64);
+        }
+        header(&mut out, "grpc_request_duration_seconds", "histogram", "Latencia de las llamadas gRPC, stream incluido");
+        for ((method, code), histogram) in &registry.grpc {
+            let labels = [("method", method.clone()), ("code", code.clone())];
+            write_histogram(&mut out, "grpc_request_duration_seconds", &labels, histogram);
+        }
+
+        header(&mut out, "app_errors_total", "counter", "Respuestas de error por código de AppError");
+        for (code, count) in &registry.errors {
+            sample(&mut out, "app_errors_total", &[("code", code.to_string())], *count as f64);
+        }
+
+        header(
+            &mut out,
+            "repository_call_duration_seconds",
+            "histogram",
+            "Latencia de las llamadas a BookRepository",
+        );
+        for ((method, outcome), histogram) in &registry.repository {
+            let labels = [("method", method.to_string()), ("outcome", outcome.to_string())];
+            write_histogram(&mut out, "repository_call_duration_seconds", &labels, histogram);
+        }
+
+        let mut last = "";
+        for gauge in gauges {
+            if gauge.name != last {
+                header(&mut out, gauge.name, "gauge", gauge.help);
+                last = gauge.name;
+            }
+            sample(&mut out, gauge.name, &gauge.labels, gauge.value);
+        }
+        out
+    }
+}
+
+fn request_labels(key: &RequestKey) -> [(&'static str, String); 3] {
+    [("method", key.method.clone()), ("route", key.route.clone()), ("status", key.status.to_string())]
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &[(&'static str, String)], histogram: &Histogram) {
+    let bucket = format!("{}_bucket", name);
+    let mut cumulative = 0;
+    for (le, count) in BUCKETS.iter().zip(histogram.buckets) {
+        cumulative += count;
+        let mut labels = labels.to_vec();
+        labels.push(("le", le.to_string()));
+        sample(out, &bucket, &labels, cumulative as f64);
+    }
+    let mut labels_inf = labels.to_vec();
+    labels_inf.push(("le", "+Inf".into()));
+    sample(out, &bucket, &labels_inf, histogram.count as f64);
+    sample(out, &format!("{}_sum", name), labels, histogram.sum);
+    sample(out, &format!("{}_count", name), labels, histogram.count as f64);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&'static str, String)], value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> =
+            labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, escape(value))).collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// Escapado de valores de etiqueta según el formato de texto
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gauge, Metrics};
+    use std::time::Duration;
+
+    #[test]
+    fn histograms_are_cumulative_and_counted() {
+        let metrics = Metrics::new();
+        metrics.observe_request("GET", "/books/:id", 200, Duration::from_millis(3));
+        metrics.observe_request("GET", "/books/:id", 200, Duration::from_millis(30));
+        metrics.observe_request("GET", "/books/:id", 200, Duration::from_secs(20));
+        metrics.count_error("not_found");
+        metrics.observe_repository("get_by_id", false, Duration::from_millis(1));
+        metrics.observe_grpc("/library.catalog.v1.CatalogService/Get", "NotFound", Duration::from_millis(2));
+
+        let out = metrics.render(&[]);
+        let labels = r#"method="GET",route="/books/:id",status="200""#;
+        assert!(out.contains(&format!("http_requests_total{{{}}} 3\n", labels)), "{}", out);
+        assert!(out.contains(&format!("http_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1\n", labels)));
+        assert!(out.contains(&format!("http_request_duration_seconds_bucket{{{},le=\"0.05\"}} 2\n", labels)));
+        assert!(out.contains(&format!("http_request_duration_seconds_bucket{{{},le=\"10\"}} 2\n", labels)));
+        assert!(out.contains(&format!("http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 3\n", labels)));
+        assert!(out.contains(&format!("http_request_duration_seconds_count{{{}}} 3\n", labels)));
+        assert!(out.contains("app_errors_total{code=\"not_found\"} 1\n"));
+        assert!(out.contains("repository_call_duration_seconds_count{method=\"get_by_id\",outcome=\"error\"} 1\n"));
+        assert!(out.contains("# TYPE repository_call_duration_seconds histogram\n"));
+        let grpc = r#"method="/library.catalog.v1.CatalogService/Get",code="NotFound""#;
+        assert!(out.contains(&format!("grpc_requests_total{{{}}} 1\n", grpc)));
+        assert!(out.contains(&format!("grpc_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1\n", grpc)));
+    }
+
+    #[test]
+    fn gauges_share_one_header_and_labels_are_escaped() {
+        let gauge = |state: &str, value| Gauge {
+            name: "library_books",
+            help: "Libros",
+            labels: vec![("state", state.into())],
+            value,
+        };
+        let out = Metrics::new().render(&[gauge("active", 2.0), gauge("tra\"sh\\ed", 1.0)]);
+
+        assert_eq!(out.matches("# TYPE library_books gauge").count(), 1);
+        assert!(out.contains("library_books{state=\"active\"} 2\n"));
+        assert!(out.contains("library_books{state=\"tra\\\"sh\\\\ed\"} 1\n"));
+    }
+}
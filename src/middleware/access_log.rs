@@ -0,0 +1,99 @@
+use axum::{body::Body, http::Request, middleware::Next, response::Response};
+use std::time::Instant;
+
+use crate::middleware::auth::Subject;
+
+/// Una línea por petición con método, ruta, estado, latencia y el `sub` del
+/// token si la ruta está protegida (`-` si no). El `request_id` y el
+/// `trace_id` los añade la capa de logs (ver `telemetry::logs`).
+pub async fn access_log(req: Request<Body>, next: Next) -> Response {
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let subject = res.extensions().get::<Subject>().map_or("-", |Subject(sub)| sub.as_str());
+
+    tracing::info!(
+        target: "access_log",
+        method,
+        path,
+        status = res.status().as_u16(),
+        latency_ms,
+        subject,
+        "request completed",
+    );
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::build_app,
+        config::{Config, LogFormat},
+        infra::memory_backend,
+        telemetry::{
+            logs::{Buffer, LogLayer},
+            Recorder,
+        },
+    };
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // `current_thread`: el subscriber por defecto es del hilo y el servidor corre en él
+    #[tokio::test(flavor = "current_thread")]
+    async fn access_log_lines_carry_subject_and_request_id() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry()
+                .with(Recorder::default().layer())
+                .with(LogLayer::new(LogFormat::Json, move || writer.clone())),
+        );
+
+        let app = build_app(memory_backend(), Config::new("sqlite::memory:".into(), "secret".into()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = reqwest::Client::new();
+        let token: String = client
+            .post(format!("http://{}/login", addr))
+            .json(&json!({"username": "admin", "password": "password"}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let res = client
+            .delete(format!("http://{}/books/missing", addr))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+        let request_id = res.headers()["x-request-id"].to_str().unwrap().to_string();
+
+        let access: Vec<Value> = buffer
+            .lines()
+            .iter()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter(|line| line["target"] == "access_log")
+            .collect();
+        assert_eq!(access.len(), 2);
+        assert_eq!(access[0]["path"], "/login");
+        assert_eq!(access[0]["subject"], "-");
+
+        let delete = &access[1];
+        assert_eq!(delete["method"], "DELETE");
+        assert_eq!(delete["path"], "/books/missing");
+        assert_eq!(delete["status"], 404);
+        assert_eq!(delete["subject"], "admin");
+        assert_eq!(delete["request_id"], request_id.as_str());
+        assert!(delete["latency_ms"].is_f64());
+        assert_eq!(delete["trace_id"].as_str().unwrap().len(), 32);
+    }
+}
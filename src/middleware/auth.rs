@@ -1,32 +1,77 @@
 use axum::{
     body::Body,
-    http::{Request, header::AUTHORIZATION},
+    extract::State,
+    http::{HeaderMap, Request, header::AUTHORIZATION},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use jsonwebtoken::{decode, DecodingKey, Validation};
-use serde_json::Value;
-use crate::{config::jwt_secret, error::AppError};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::{config::Config, error::AppError};
 
-pub async fn auth(req: Request<Body>, next: Next) -> Response {
-    let auth_header = req
-        .headers()
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    /// Rol de los tokens emitidos antes de que existieran roles
+    #[default]
+    Cataloguer,
+}
+
+/// Contenido del JWT; el middleware `auth` lo deja en las extensiones de la petición
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    #[serde(default)]
+    pub role: Role,
+}
+
+/// `sub` del token que autorizó la petición; `auth` lo deja en las
+/// extensiones de la respuesta para el log de acceso
+#[derive(Clone, Debug)]
+pub struct Subject(pub String);
+
+pub async fn auth(State(config): State<Arc<Config>>, mut req: Request<Body>, next: Next) -> Response {
+    match authorize(req.headers(), &config.jwt_secret) {
+        Ok(claims) => {
+            let subject = Subject(claims.sub.clone());
+            req.extensions_mut().insert(claims);
+            let mut res = next.run(req).await;
+            res.extensions_mut().insert(subject);
+            res
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Solo para rutas ya protegidas por `auth`: exige el rol `admin`
+pub async fn require_admin(req: Request<Body>, next: Next) -> Response {
+    match req.extensions().get::<Claims>() {
+        Some(claims) if claims.role == Role::Admin => next.run(req).await,
+        Some(_) => AppError::Forbidden("Admin role required".into()).into_response(),
+        None => AppError::Auth.into_response(),
+    }
+}
+
+/// Valida el `Authorization: Bearer <jwt>` de una petición
+pub fn authorize(headers: &HeaderMap, jwt_secret: &str) -> Result<Claims, AppError> {
+    let auth_header = headers
         .get(AUTHORIZATION)
         .and_then(|h| h.to_str().ok());
 
     if let Some(header) = auth_header {
         if let Some(token) = header.strip_prefix("Bearer ") {
-            if decode::<Value>(
+            if let Ok(data) = decode::<Claims>(
                 token,
-                &DecodingKey::from_secret(jwt_secret().as_ref()),
+                &DecodingKey::from_secret(jwt_secret.as_ref()),
                 &Validation::default(),
-            )
-                .is_ok()
-            {
-                return next.run(req).await;
+            ) {
+                return Ok(data.claims);
             }
         }
     }
 
-    AppError::Auth.into_response()
+    Err(AppError::Auth)
 }
@@ -0,0 +1,66 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        },
+        HeaderMap, HeaderValue, Method, Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+use crate::config::Config;
+
+const ALLOW_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+const ALLOW_HEADERS: &str = "authorization, content-type, if-match, if-none-match, idempotency-key, x-request-id";
+/// Cabeceras de respuesta que el navegador deja leer al cliente
+const EXPOSE_HEADERS: &str = "etag, location, retry-after, x-request-id, x-possible-duplicates, idempotent-replayed, \
+ratelimit-limit, ratelimit-remaining, ratelimit-reset";
+
+/// CORS para los orígenes de `Config::cors_allowed_origins`. Responde a los
+/// preflight antes de llegar a `auth`; a un origen no admitido no le añade
+/// cabeceras y es el navegador quien bloquea la respuesta.
+pub async fn cors(State(config): State<Arc<Config>>, req: Request<Body>, next: Next) -> Response {
+    let origin = req
+        .headers()
+        .get(ORIGIN)
+        .filter(|origin| is_allowed(&config, origin))
+        .cloned();
+    let Some(origin) = origin else {
+        return next.run(req).await;
+    };
+
+    let preflight = req.method() == Method::OPTIONS && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+    let mut res = if preflight {
+        let mut res = StatusCode::NO_CONTENT.into_response();
+        let headers = res.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static(ALLOW_METHODS));
+        headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static(ALLOW_HEADERS));
+        headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static("600"));
+        res
+    } else {
+        let mut res = next.run(req).await;
+        res.headers_mut()
+            .insert(ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(EXPOSE_HEADERS));
+        res
+    };
+    allow_origin(res.headers_mut(), origin);
+    res
+}
+
+fn is_allowed(config: &Config, origin: &HeaderValue) -> bool {
+    config
+        .cors_allowed_origins
+        .iter()
+        .any(|allowed| allowed == "*" || origin.as_bytes() == allowed.as_bytes())
+}
+
+fn allow_origin(headers: &mut HeaderMap, origin: HeaderValue) {
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    // La respuesta depende del `Origin`: las cachés no deben mezclarlas
+    headers.append(VARY, HeaderValue::from_static("origin"));
+}
@@ -0,0 +1,140 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::State,
+    http::{
+        header::{CONTENT_TYPE, ETAG, LOCATION},
+        HeaderName, HeaderValue, Method, Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::{
+    app::idempotency_store::{IdempotencyStore, Reservation, StoredResponse},
+    error::AppError,
+    handlers::duplicates_handler::POSSIBLE_DUPLICATES,
+    middleware::auth::Claims,
+};
+
+pub static IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub static IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// Tiempo que se conserva una clave antes de poder reutilizarla
+pub const RETENTION_HOURS: i64 = 24;
+/// Una reserva sin respuesta más antigua se da por abandonada y otra petición
+/// puede reclamarla; debe superar lo que tarde la petición más lenta
+pub const LEASE_SECS: i64 = 60;
+
+/// Cabeceras de la respuesta original que se guardan y se repiten; el resto
+/// (`X-Request-Id`, `RateLimit-*`...) son propias de cada petición
+pub static REPLAYED_HEADERS: [&HeaderName; 4] = [&CONTENT_TYPE, &ETAG, &LOCATION, &POSSIBLE_DUPLICATES];
+
+const MAX_KEY_LEN: usize = 255;
+/// Igual que el límite por defecto del extractor `Json`
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Para `POST` con `Idempotency-Key`: la primera petición se procesa y su
+/// respuesta se guarda; las repeticiones con el mismo cuerpo reciben la
+/// respuesta guardada y con otro cuerpo, 422.
+pub async fn idempotency(
+    State(store): State<Arc<dyn IdempotencyStore>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if req.method() != Method::POST || !req.headers().contains_key(&IDEMPOTENCY_KEY) {
+        return next.run(req).await;
+    }
+    match handle(store, req, next).await {
+        Ok(res) => res,
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn handle(
+    store: Arc<dyn IdempotencyStore>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let key = req
+        .headers()
+        .get(&IDEMPOTENCY_KEY)
+        .and_then(|h| h.to_str().ok())
+        .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LEN)
+        .map(str::to_owned)
+        .ok_or_else(|| AppError::BadRequest(format!(
+            "Idempotency-Key must be 1 to {} visible ASCII characters",
+            MAX_KEY_LEN
+        )))?;
+    // La clave la elige el cliente: dos usuarios pueden repetir la misma
+    let sub = req.extensions().get::<Claims>().map_or("", |claims| claims.sub.as_str());
+    let scope = format!("{} {} {}", sub, req.method(), req.uri().path());
+
+    let (parts, body) = req.into_parts();
+    let bytes = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Cannot read request body: {}", e)))?;
+    let request_hash = hex::encode(Sha256::digest(&bytes));
+
+    let now = chrono::Utc::now();
+    let not_before = now - chrono::Duration::hours(RETENTION_HOURS);
+    let lease_before = now - chrono::Duration::seconds(LEASE_SECS);
+    match store.reserve(&scope, &key, &request_hash, not_before, lease_before).await? {
+        Reservation::Existing { request_hash: stored, .. } if stored != request_hash => {
+            return Err(AppError::Unprocessable(
+                "Idempotency-Key was already used with a different request".into(),
+            ));
+        }
+        Reservation::Existing { response: None, .. } => {
+            return Err(AppError::Conflict(
+                "A request with this Idempotency-Key is still being processed".into(),
+            ));
+        }
+        Reservation::Existing { response: Some(stored), .. } => return Ok(replay(stored)),
+        Reservation::Reserved => {}
+    }
+
+    let res = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+
+    // Los 5xx no se guardan: el cliente debe poder reintentar
+    if res.status().is_server_error() {
+        store.release(&scope, &key).await?;
+        return Ok(res);
+    }
+    let (parts, body) = res.into_parts();
+    let bytes = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::Db(anyhow::anyhow!("Cannot buffer response: {}", e)))?;
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: REPLAYED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = parts.headers.get(*name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect(),
+        body: bytes.to_vec(),
+    };
+    // El handler ya ha escrito: un error aquí haría reintentar al cliente y
+    // duplicar lo que la clave debía proteger. La reserva se queda sin
+    // respuesta y los reintentos reciben 409 hasta que caduque.
+    if let Err(e) = store.complete(&scope, &key, &stored).await {
+        tracing::error!(error = %e, "could not store the response for an Idempotency-Key");
+    }
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut res = Response::new(Body::from(stored.body));
+    *res.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            res.headers_mut().insert(name, value);
+        }
+    }
+    res.headers_mut()
+        .insert(IDEMPOTENT_REPLAYED.clone(), HeaderValue::from_static("true"));
+    res
+}
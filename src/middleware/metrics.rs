@@ -0,0 +1,47 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::{sync::Arc, time::Instant};
+
+use crate::{error::ErrorCode, metrics::Metrics};
+
+/// Registra método, plantilla de ruta, estado y latencia de cada petición, y
+/// el código de los `AppError` que acaban en respuesta
+pub async fn track_metrics(State(metrics): State<Arc<Metrics>>, req: Request<Body>, next: Next) -> Response {
+    let method = method_label(req.method());
+    // Sin ruta (404 del router) se agrupa todo: la URL dispararía las series
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+    metrics.observe_request(method, &route, res.status().as_u16(), start.elapsed());
+    if let Some(ErrorCode(code)) = res.extensions().get::<ErrorCode>() {
+        metrics.count_error(code);
+    }
+    res
+}
+
+/// Los métodos de extensión se agrupan: cualquiera puede inventarse uno por
+/// petición y cada uno abriría series nuevas
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::PATCH => "PATCH",
+        Method::DELETE => "DELETE",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
@@ -1,2 +1,9 @@
 // src/middleware/mod.rs
 pub mod auth;
+pub mod request_id;
+pub mod idempotency;
+pub mod cors;
+pub mod metrics;
+pub mod trace;
+pub mod access_log;
+pub mod rate_limit;
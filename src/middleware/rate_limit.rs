@@ -0,0 +1,398 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, HeaderName, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    error::AppError,
+    middleware::auth::{authorize, Claims},
+};
+
+pub static RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub static RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub static RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Tope de cubos en memoria: al llegar a él, cada cliente nuevo desplaza al
+/// que lleva más tiempo sin pedir nada
+const MAX_BUCKETS: usize = 10_000;
+/// Cada cuánto se descartan en segundo plano los cubos ya llenos
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Cubo de fichas por cliente: admite ráfagas de `burst` peticiones y repone
+/// `per_minute` fichas por minuto. Con `per_minute = 0` no limita nada.
+pub struct RateLimiter {
+    burst: u32,
+    per_sec: f64,
+    max_buckets: usize,
+    buckets: Mutex<Buckets>,
+    /// Conexiones cuyo `X-Forwarded-For` se cree; ver `client_ip`
+    trusted_proxies: Vec<IpAddr>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Posición en `Buckets::by_use`
+    last_use: u64,
+}
+
+/// Cubos por clave y, aparte, las claves por orden de último uso para
+/// desalojar la más antigua sin recorrer el mapa
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    by_use: BTreeMap<u64, String>,
+    uses: u64,
+}
+
+/// Estado del cubo tras una petición, para las cabeceras `RateLimit-*`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Hasta que el cubo vuelva a estar lleno
+    pub reset: Duration,
+    /// Hasta la siguiente ficha; cero si la petición pasa
+    pub retry_after: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            burst,
+            per_sec: per_minute as f64 / 60.0,
+            max_buckets: MAX_BUCKETS,
+            buckets: Mutex::new(Buckets::default()),
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.rate_limit_per_minute, config.rate_limit_burst).trusting(config)
+    }
+
+    /// Presupuesto propio de `/login`, por IP, para frenar a quien prueba
+    /// contraseñas sin gastar el de las lecturas del catálogo
+    pub fn for_login(config: &Config) -> Self {
+        Self::new(config.rate_limit_login_per_minute, config.rate_limit_login_burst).trusting(config)
+    }
+
+    fn trusting(self, config: &Config) -> Self {
+        let trusted_proxies = config.trusted_proxies.iter().map(IpAddr::to_canonical).collect();
+        Self { trusted_proxies, ..self }
+    }
+
+    /// IP del cliente detrás de `peer`, la conexión. Si `peer` es un proxy de
+    /// confianza se recorre `X-Forwarded-For` de derecha a izquierda hasta el
+    /// primer salto que no lo es: lo que haya más a la izquierda lo escribe
+    /// el cliente y no sirve. Sin proxies configurados la cabecera se ignora.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+        let hops = headers.get_all(&X_FORWARDED_FOR).iter().flat_map(|value| value.to_str().unwrap_or("").split(','));
+        for hop in hops.collect::<Vec<_>>().into_iter().rev() {
+            if !self.trusted_proxies.contains(&client) {
+                break;
+            }
+            match hop.trim().parse::<IpAddr>() {
+                Ok(ip) => client = ip.to_canonical(),
+                Err(_) => break,
+            }
+        }
+        client
+    }
+
+    /// Clave del cubo de un cliente que puede venir con token o sin él: el
+    /// `sub` si el token es válido, si no la IP. `peer` es `None` si el
+    /// servidor no da la dirección de la conexión; esos clientes comparten cubo.
+    pub fn client_key(&self, peer: Option<IpAddr>, headers: &HeaderMap, jwt_secret: &str) -> String {
+        match authorize(headers, jwt_secret) {
+            Ok(claims) => format!("sub:{}", claims.sub),
+            Err(_) => self.ip_key(peer, headers),
+        }
+    }
+
+    fn ip_key(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> String {
+        peer.map_or("ip:unknown".to_string(), |peer| ip_key(self.client_ip(peer, headers)))
+    }
+
+    /// Descarta cada `SWEEP_INTERVAL` los cubos llenos, que equivalen a un
+    /// cliente que no ha llegado nunca. Fuera de un runtime de tokio no hace
+    /// nada: el tope de `MAX_BUCKETS` sigue acotando la memoria.
+    pub fn spawn_sweeper(self: &Arc<Self>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        // Con una referencia débil la tarea acaba cuando se suelta el limitador
+        let limiter: Weak<Self> = Arc::downgrade(self);
+        runtime.spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match limiter.upgrade() {
+                    Some(limiter) => limiter.sweep(Instant::now()),
+                    None => break,
+                }
+            }
+        });
+    }
+
+    fn sweep(&self, now: Instant) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets { by_key, by_use, .. } = &mut *buckets;
+        by_key.retain(|_, bucket| {
+            let full = self.refilled(bucket, now) >= self.burst as f64;
+            if full {
+                by_use.remove(&bucket.last_use);
+            }
+            !full
+        });
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_sec).min(self.burst as f64)
+    }
+
+    /// Consume una ficha del cubo de `key`; `None` si el límite está desactivado
+    pub fn check(&self, key: &str) -> Option<Quota> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Option<Quota> {
+        if self.per_sec == 0.0 {
+            return None;
+        }
+        let burst = self.burst as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets { by_key, by_use, uses } = &mut *buckets;
+
+        if by_key.len() >= self.max_buckets && !by_key.contains_key(key) {
+            if let Some((_, oldest)) = by_use.pop_first() {
+                by_key.remove(&oldest);
+            }
+        }
+        *uses += 1;
+        let bucket = by_key.entry(key.to_string()).or_insert(Bucket { tokens: burst, updated: now, last_use: *uses });
+        by_use.remove(&bucket.last_use);
+        by_use.insert(*uses, key.to_string());
+        bucket.last_use = *uses;
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Some(Quota {
+            allowed,
+            limit: self.burst,
+            remaining: bucket.tokens as u32,
+            reset: Duration::from_secs_f64((burst - bucket.tokens) / self.per_sec),
+            retry_after: if allowed {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_sec)
+            },
+        })
+    }
+}
+
+impl Quota {
+    fn insert_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT.clone(), self.limit.into());
+        headers.insert(RATELIMIT_REMAINING.clone(), self.remaining.into());
+        headers.insert(RATELIMIT_RESET.clone(), ceil_secs(self.reset).into());
+    }
+}
+
+/// Rutas anónimas: un cubo por IP de origen
+pub async fn limit_by_ip(State(limiter): State<Arc<RateLimiter>>, req: Request<Body>, next: Next) -> Response {
+    let key = limiter.ip_key(peer(&req), req.headers());
+    limited(&limiter, &key, req, next).await
+}
+
+/// Rutas con token opcional, como `/graphql`: por `sub` si lo hay, si no por IP
+pub async fn limit_by_subject_or_ip(
+    State((limiter, config)): State<(Arc<RateLimiter>, Arc<Config>)>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let key = limiter.client_key(peer(&req), req.headers(), &config.jwt_secret);
+    limited(&limiter, &key, req, next).await
+}
+
+/// Sin `ConnectInfo` (un `serve` sin `into_make_service_with_connect_info`)
+/// no se sabe de dónde viene la petición
+fn peer(req: &Request<Body>) -> Option<IpAddr> {
+    req.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Clave del cubo de una IP. Cada cliente IPv6 suele tener un /64 entero,
+/// así que se cuenta por prefijo: rotar de dirección no da cubos nuevos.
+fn ip_key(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => format!("ip:{}", ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => format!("ip:{}", ip),
+            None => {
+                let [a, b, c, d, ..] = ip.segments();
+                format!("ip:{}/64", Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0))
+            }
+        },
+    }
+}
+
+/// Solo para rutas ya protegidas por `auth`: un cubo por `sub` del token,
+/// esté donde esté el cliente
+pub async fn limit_by_subject(State(limiter): State<Arc<RateLimiter>>, req: Request<Body>, next: Next) -> Response {
+    let Some(claims) = req.extensions().get::<Claims>() else {
+        return AppError::Auth.into_response();
+    };
+    let key = format!("sub:{}", claims.sub);
+    limited(&limiter, &key, req, next).await
+}
+
+async fn limited(limiter: &RateLimiter, key: &str, req: Request<Body>, next: Next) -> Response {
+    let Some(quota) = limiter.check(key) else {
+        return next.run(req).await;
+    };
+    let mut res = if quota.allowed {
+        next.run(req).await
+    } else {
+        AppError::RateLimited(ceil_secs(quota.retry_after)).into_response()
+    };
+    quota.insert_headers(res.headers_mut());
+    res
+}
+
+/// Segundos enteros hacia arriba: `Retry-After: 0` invitaría a reintentar ya
+pub fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ip_key, RateLimiter};
+    use crate::config::Config;
+    use axum::http::HeaderMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn bursts_are_allowed_and_tokens_refill_over_time() {
+        // Una ficha cada 2 s
+        let limiter = RateLimiter::new(30, 3);
+        let start = Instant::now();
+
+        for remaining in [2, 1, 0] {
+            let quota = limiter.check_at("ip:1", start).unwrap();
+            assert!(quota.allowed);
+            assert_eq!(quota.remaining, remaining);
+        }
+        let denied = limiter.check_at("ip:1", start).unwrap();
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Duration::from_secs(2));
+        assert_eq!(denied.reset, Duration::from_secs(6));
+
+        // Cada clave tiene su cubo
+        assert!(limiter.check_at("ip:2", start).unwrap().allowed);
+
+        let later = limiter.check_at("ip:1", start + Duration::from_secs(2)).unwrap();
+        assert!(later.allowed);
+        assert_eq!(later.remaining, 0);
+        // Nunca se acumulan más fichas que la ráfaga
+        let idle = limiter.check_at("ip:1", start + Duration::from_secs(3600)).unwrap();
+        assert_eq!(idle.remaining, 2);
+    }
+
+    #[test]
+    fn zero_rate_disables_the_limit() {
+        let limiter = RateLimiter::new(0, 1);
+        assert_eq!(limiter.check("ip:1"), None);
+    }
+
+    #[test]
+    fn the_least_recently_used_bucket_makes_room_for_new_clients() {
+        let limiter = RateLimiter { max_buckets: 2, ..RateLimiter::new(60, 2) };
+        let start = Instant::now();
+
+        // Dos clientes a medias: llenos no estarían, así que solo cuenta el uso
+        limiter.check_at("ip:1", start);
+        limiter.check_at("ip:2", start);
+        limiter.check_at("ip:1", start);
+        limiter.check_at("ip:3", start);
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_key.len(), 2);
+        assert!(!buckets.by_key.contains_key("ip:2"));
+        assert_eq!(buckets.by_use.values().collect::<Vec<_>>(), ["ip:1", "ip:3"]);
+    }
+
+    #[test]
+    fn sweeping_drops_only_full_buckets() {
+        // Una ficha por segundo
+        let limiter = RateLimiter::new(60, 2);
+        let start = Instant::now();
+        limiter.check_at("ip:1", start);
+        limiter.check_at("ip:2", start + Duration::from_secs(5));
+
+        limiter.sweep(start + Duration::from_secs(5));
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_key.keys().collect::<Vec<_>>(), ["ip:2"]);
+        assert_eq!(buckets.by_use.len(), 1);
+    }
+
+    #[test]
+    fn ipv6_clients_are_keyed_by_their_64_prefix() {
+        let key = |ip: &str| ip_key(ip.parse().unwrap());
+        assert_eq!(key("2001:db8:1:2:aaaa::1"), "ip:2001:db8:1:2::/64");
+        assert_eq!(key("2001:db8:1:2:bbbb::2"), key("2001:db8:1:2:aaaa::1"));
+        assert_ne!(key("2001:db8:1:3::1"), key("2001:db8:1:2::1"));
+        assert_eq!(key("::ffff:192.0.2.7"), "ip:192.0.2.7");
+        assert_eq!(key("192.0.2.7"), "ip:192.0.2.7");
+    }
+
+    #[test]
+    fn forwarded_for_is_only_trusted_from_configured_proxies() {
+        let config = Config {
+            trusted_proxies: vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()],
+            ..Config::new("sqlite::memory:".into(), "s3cret".into())
+        };
+        let limiter = RateLimiter::from_config(&config);
+        let client_ip = |peer: &str, forwarded: &[&str]| {
+            let mut headers = HeaderMap::new();
+            for value in forwarded {
+                headers.append("x-forwarded-for", value.parse().unwrap());
+            }
+            limiter.client_ip(peer.parse().unwrap(), &headers).to_string()
+        };
+
+        // Conexión directa: la cabecera la pone el cliente
+        assert_eq!(client_ip("203.0.113.9", &["198.51.100.1"]), "203.0.113.9");
+        // Se salta los proxies de confianza y no pasa del primer salto ajeno
+        assert_eq!(client_ip("10.0.0.1", &["192.0.2.66, 198.51.100.1, 10.0.0.2"]), "198.51.100.1");
+        assert_eq!(client_ip("::ffff:10.0.0.1", &["192.0.2.66", "198.51.100.1"]), "198.51.100.1");
+        // Un salto ilegible deja el último conocido
+        assert_eq!(client_ip("10.0.0.1", &["198.51.100.1, unknown"]), "10.0.0.1");
+        assert_eq!(client_ip("10.0.0.1", &[]), "10.0.0.1");
+
+        // Sin proxies configurados la cabecera no cuenta
+        let limiter = RateLimiter::new(60, 1);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "198.51.100.1".parse().unwrap());
+        assert_eq!(limiter.client_key(Some("10.0.0.1".parse().unwrap()), &headers, "s3cret"), "ip:10.0.0.1");
+        assert_eq!(limiter.client_key(None, &headers, "s3cret"), "ip:unknown");
+    }
+}
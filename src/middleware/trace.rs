@@ -0,0 +1,85 @@
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field::Empty, Instrument};
+
+use crate::{middleware::request_id, telemetry};
+
+/// Abre el span de servidor de cada petición. Con un `traceparent` válido se
+/// une a la traza del cliente; si no, empieza una nueva (ver `telemetry`).
+pub async fn trace_request(req: Request<Body>, next: Next) -> Response {
+    let method = req.method().clone();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    let span = tracing::info_span!(
+        "request",
+        otel.name = %format!("{} {}", method, route),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %method,
+        http.route = %route,
+        url.path = %req.uri().path(),
+        http.response.status_code = Empty,
+        request_id = request_id::current().unwrap_or_default(),
+    );
+    telemetry::set_remote_parent(&span, req.headers());
+    let res = next.run(req).instrument(span.clone()).await;
+
+    // Entero con signo: `tracing-opentelemetry` pasa los `u64` a texto
+    span.record("http.response.status_code", i64::from(res.status().as_u16()));
+    if res.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{app::build_app, config::Config, infra::memory_backend, telemetry::Recorder};
+    use opentelemetry::{trace::SpanId, Value};
+    use tokio::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    // `current_thread`: el subscriber por defecto es del hilo y el servidor corre en él
+    #[tokio::test(flavor = "current_thread")]
+    async fn repository_spans_are_children_of_the_remote_trace() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.layer()));
+
+        let app = build_app(memory_backend(), Config::new("sqlite::memory:".into(), "secret".into()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let res = reqwest::Client::new()
+            .get(format!("http://{}/books/missing", addr))
+            .header("traceparent", REMOTE)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+
+        // Los spans de hyper y reqwest también pasan por la capa; solo interesan los de la app
+        let spans = recorder.spans();
+        let named = |name: &str| spans.iter().find(|s| s.name == name).unwrap_or_else(|| panic!("{}", name));
+        let request = named("GET /books/:id");
+        let lookup = named("BookRepository.get_by_id");
+
+        assert_eq!(request.span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(request.parent_span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+        assert_eq!(lookup.span_context.trace_id(), request.span_context.trace_id());
+        assert_eq!(lookup.parent_span_id, request.span_context.span_id());
+        let status = request.attributes.iter().find(|kv| kv.key.as_str() == "http.response.status_code").unwrap();
+        assert_eq!(status.value, Value::I64(404));
+    }
+}
@@ -0,0 +1,116 @@
+use std::{future::Future, time::Duration};
+use tokio::sync::watch;
+
+/// Aviso de apagado compartido por los servidores HTTP y gRPC: al dispararlo
+/// dejan de aceptar conexiones y terminan las peticiones en curso.
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: watch::Sender<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self { tx: watch::channel(false).0 }
+    }
+
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Se completa al disparar el apagado, aunque ya se hubiera disparado antes.
+    /// Es lo que reciben `with_graceful_shutdown` y `serve_with_shutdown`.
+    pub fn signalled(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut rx = self.tx.subscribe();
+        async move {
+            // Sin emisores no llegará nunca: se trata como apagado
+            let _ = rx.wait_for(|triggered| *triggered).await;
+        }
+    }
+
+    /// Dispara el apagado y espera a que `servers` termine, como mucho `timeout`.
+    /// `None` si se agotó el plazo con peticiones aún en curso.
+    pub async fn drain<F: Future>(&self, servers: F, timeout: Duration) -> Option<F::Output> {
+        self.trigger();
+        tokio::time::timeout(timeout, servers).await.ok()
+    }
+}
+
+/// Espera a SIGTERM (lo que envía el orquestador) o Ctrl+C
+pub async fn terminate_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("failed to install Ctrl+C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shutdown;
+    use axum::{routing::get, Router};
+    use std::{future::IntoFuture, time::Duration};
+    use tokio::net::TcpListener;
+
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        "done"
+    }
+
+    async fn spawn(shutdown: &Shutdown) -> (String, tokio::task::JoinHandle<std::io::Result<()>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/slow", get(slow));
+        let server = tokio::spawn(
+            axum::serve(listener, app).with_graceful_shutdown(shutdown.signalled()).into_future(),
+        );
+        (base, server)
+    }
+
+    #[tokio::test]
+    async fn in_flight_requests_finish_and_new_connections_are_refused() {
+        let shutdown = Shutdown::new();
+        let (base, server) = spawn(&shutdown).await;
+
+        // El cliente se crea antes: montarlo tarda y la petición llegaría tarde
+        let client = reqwest::Client::new();
+        let in_flight = tokio::spawn(client.get(format!("{}/slow", base)).send());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let drained = shutdown.drain(server, Duration::from_secs(5)).await;
+        assert!(drained.is_some(), "el servidor no terminó dentro del plazo");
+
+        let res = in_flight.await.unwrap().unwrap();
+        assert_eq!(res.text().await.unwrap(), "done");
+        assert!(client.get(format!("{}/slow", base)).send().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn drain_gives_up_after_the_timeout() {
+        let shutdown = Shutdown::new();
+        let (base, server) = spawn(&shutdown).await;
+
+        let client = reqwest::Client::new();
+        let _in_flight = tokio::spawn(client.get(format!("{}/slow", base)).send());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(shutdown.drain(server, Duration::from_millis(10)).await.is_none());
+    }
+}
@@ -0,0 +1,210 @@
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::{fmt, io::Write};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_log::NormalizeEvent;
+use opentelemetry::trace::{TraceContextExt, TraceId};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    fmt::MakeWriter,
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+    Layer,
+};
+
+use crate::{config::LogFormat, middleware::request_id};
+
+/// Escribe cada evento en una línea, texto o JSON, con el id de la petición
+/// en curso y el de su traza
+pub struct LogLayer<W> {
+    format: LogFormat,
+    writer: W,
+}
+
+impl<W> LogLayer<W> {
+    pub fn new(format: LogFormat, writer: W) -> Self {
+        Self { format, writer }
+    }
+}
+
+/// Campos del evento en orden, con `message` aparte
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl EventFields {
+    fn push(&mut self, field: &Field, value: Value) {
+        match field.name() {
+            "message" => {
+                self.message = match value {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                }
+            }
+            // Metadatos que añade tracing-log a los eventos del crate `log`
+            name if name.starts_with("log.") => {}
+            name => self.fields.push((name, value)),
+        }
+    }
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, Value::from(format!("{:?}", value)));
+    }
+}
+
+impl<S, W> Layer<S> for LogLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let normalized = event.normalized_metadata();
+        let meta = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+
+        // Fuera de una petición (arranque, tareas de fondo) no hay ninguno de los dos
+        if let Some(id) = request_id::current() {
+            fields.fields.push(("request_id", Value::from(id)));
+        }
+        if let Some(trace_id) = ctx.event_span(event).and_then(|span| trace_id(&span)) {
+            fields.fields.push(("trace_id", Value::from(trace_id.to_string())));
+        }
+
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+        let line = match self.format {
+            LogFormat::Text => text_line(&timestamp, meta.level(), meta.target(), fields),
+            LogFormat::Json => json_line(&timestamp, meta.level(), meta.target(), fields),
+        };
+        let _ = writeln!(self.writer.make_writer(), "{}", line);
+    }
+}
+
+/// La traza del padre remoto o del span raíz; el id del raíz se genera al
+/// abrirlo, antes de que `tracing-opentelemetry` construya el span
+fn trace_id<'a, R: LookupSpan<'a>>(span: &SpanRef<'a, R>) -> Option<TraceId> {
+    let extensions = span.extensions();
+    let data = extensions.get::<OtelData>()?;
+    let parent = data.parent_cx.span().span_context().trace_id();
+    if parent != TraceId::INVALID { Some(parent) } else { data.builder.trace_id }
+}
+
+/// `<ts> <NIVEL> <target>: <mensaje> clave=valor ...`; las cadenas van entre comillas
+fn text_line(timestamp: &str, level: &tracing::Level, target: &str, fields: EventFields) -> String {
+    let mut line = format!("{} {:>5} {}: {}", timestamp, level, target, fields.message);
+    for (name, value) in fields.fields {
+        line.push_str(&format!(" {}={}", name, value));
+    }
+    line
+}
+
+fn json_line(timestamp: &str, level: &tracing::Level, target: &str, fields: EventFields) -> String {
+    let mut object = Map::new();
+    object.insert("timestamp".into(), timestamp.into());
+    object.insert("level".into(), level.as_str().into());
+    object.insert("target".into(), target.into());
+    object.insert("message".into(), fields.message.into());
+    for (name, value) in fields.fields {
+        object.insert(name.into(), value);
+    }
+    Value::Object(object).to_string()
+}
+
+/// Salida en memoria para leer lo escrito en los tests
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct Buffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Buffer {
+    pub(crate) fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(String::from).collect()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Buffer, LogLayer};
+    use crate::{config::LogFormat, telemetry::Recorder};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn capture(format: LogFormat, emit: impl FnOnce()) -> Vec<String> {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(Recorder::default().layer())
+            .with(LogLayer::new(format, move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, emit);
+        buffer.lines()
+    }
+
+    #[test]
+    fn json_lines_carry_fields_and_the_trace() {
+        let lines = capture(LogFormat::Json, || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info!(method = "GET", status = 404u16, latency_ms = 1.5, "request completed");
+            });
+            tracing::warn!("outside");
+        });
+
+        let inside: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(inside["level"], "INFO");
+        assert_eq!(inside["message"], "request completed");
+        assert_eq!(inside["method"], "GET");
+        assert_eq!(inside["status"], 404);
+        assert_eq!(inside["latency_ms"], 1.5);
+        assert_eq!(inside["trace_id"].as_str().unwrap().len(), 32);
+
+        let outside: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(outside["level"], "WARN");
+        assert!(outside.get("trace_id").is_none());
+        assert!(outside.get("request_id").is_none());
+    }
+
+    #[test]
+    fn text_lines_quote_strings() {
+        let lines = capture(LogFormat::Text, || {
+            tracing::info!(target: "access_log", path = "/books", status = 200, "request completed");
+        });
+        assert!(lines[0].ends_with(r#"  INFO access_log: request completed path="/books" status=200"#), "{}", lines[0]);
+    }
+}
@@ -0,0 +1,287 @@
+//! Trazas: contexto W3C en cada span de la app y exportación OTLP opcional.
+//! `tracing-opentelemetry` convierte los spans de `tracing` y
+//! `opentelemetry-otlp` los envía al colector por OTLP/gRPC.
+
+pub mod logs;
+
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TracerProvider as _,
+    InstrumentationScope, KeyValue,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{self, BatchConfigBuilder, BatchSpanProcessor, Sampler, Tracer, TracerProvider},
+    Resource,
+};
+use std::time::Duration;
+use tonic::codegen::http::HeaderMap;
+use tracing::{Level, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt, Layer};
+
+use crate::config::Config;
+
+use self::logs::LogLayer;
+
+/// Spans por envío; si se llena antes del siguiente tick se envía ya
+const BATCH_SIZE: usize = 512;
+const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// Spans en espera de exportar; con el colector caído se descartan los nuevos
+const QUEUE_SIZE: usize = 4096;
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lo que hay que cerrar al apagar: el proveedor y su exportador, si lo hay
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    /// Instala el subscriber global: logs por stdout en `log_format` y, si
+    /// `otlp_endpoint` está configurado, spans exportados al colector
+    pub fn init(config: &Config) -> Self {
+        let provider = tracer_provider(config.otlp_endpoint.as_deref());
+        tracing_subscriber::registry()
+            // Solo spans propios: los de tonic o hyper incluirían los del exportador
+            .with(layer(&provider).with_filter(filter_fn(|meta| meta.is_span() && is_own(meta))))
+            // Los spans propios hacen falta para sacar el `trace_id` de cada línea
+            .with(LogLayer::new(config.log_format, std::io::stdout).with_filter(filter_fn(|meta| {
+                if meta.is_span() { is_own(meta) } else { *meta.level() <= Level::INFO }
+            })))
+            .init();
+        Self { provider }
+    }
+
+    /// Envía los spans pendientes antes de salir
+    pub async fn shutdown(self) {
+        // `shutdown` bloquea el hilo hasta que el procesador vacía la cola
+        let provider = self.provider;
+        let flush = tokio::task::spawn_blocking(move || provider.shutdown());
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, flush).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => tracing::warn!(error = %e, "could not flush pending spans"),
+            Ok(Err(_)) | Err(_) => tracing::warn!("timed out flushing pending spans"),
+        }
+    }
+}
+
+/// Con `endpoint`, exporta en lotes; sin él, los spans siguen teniendo ids
+/// y los logs llevan el `trace_id`
+fn tracer_provider(endpoint: Option<&str>) -> TracerProvider {
+    let mut provider = builder();
+    if let Some(endpoint) = endpoint {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .expect("otlp_endpoint validado al cargar la configuración");
+        let batch = BatchConfigBuilder::default()
+            .with_scheduled_delay(EXPORT_INTERVAL)
+            .with_max_queue_size(QUEUE_SIZE)
+            .with_max_export_batch_size(BATCH_SIZE)
+            .build();
+        provider = provider
+            .with_span_processor(BatchSpanProcessor::builder(exporter, runtime::Tokio).with_batch_config(batch).build());
+    }
+    provider.build()
+}
+
+/// Muestrea todo salvo lo que el cliente ya decidió no muestrear (`-00`)
+fn builder() -> trace::Builder {
+    TracerProvider::builder()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
+        .with_resource(Resource::new([
+            KeyValue::new("service.name", env!("CARGO_PKG_NAME")),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]))
+}
+
+/// Los campos `otel.*` fijan nombre, tipo y estado; el resto son atributos
+fn layer<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let scope = InstrumentationScope::builder(env!("CARGO_CRATE_NAME")).with_version(env!("CARGO_PKG_VERSION")).build();
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer_with_scope(scope))
+        .with_location(false)
+        .with_threads(false)
+        .with_tracked_inactivity(false)
+}
+
+fn is_own(meta: &tracing::Metadata<'_>) -> bool {
+    meta.target().starts_with(env!("CARGO_CRATE_NAME")) && *meta.level() <= Level::INFO
+}
+
+/// Une `span` a la traza del cliente si `headers` traen un `traceparent`
+/// válido; si no, el span empieza una traza nueva
+pub fn set_remote_parent(span: &Span, headers: &HeaderMap) {
+    let context = TraceContextPropagator::new().extract(&Headers(headers));
+    span.set_parent(context);
+}
+
+struct Headers<'a>(&'a HeaderMap);
+
+impl Extractor for Headers<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Exportador en memoria para leer los spans terminados en los tests
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Recorder(std::sync::Arc<std::sync::Mutex<Vec<opentelemetry_sdk::export::trace::SpanData>>>);
+
+#[cfg(test)]
+impl Recorder {
+    /// Capa como la de `Telemetry::init` que exporta a este recorder al
+    /// cerrar cada span
+    pub(crate) fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        layer(&builder().with_simple_exporter(self.clone()).build())
+    }
+
+    pub(crate) fn spans(&self) -> Vec<opentelemetry_sdk::export::trace::SpanData> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl opentelemetry_sdk::export::trace::SpanExporter for Recorder {
+    fn export(
+        &mut self,
+        batch: Vec<opentelemetry_sdk::export::trace::SpanData>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = opentelemetry_sdk::export::trace::ExportResult> + Send>> {
+        self.0.lock().unwrap().extend(batch);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{layer, set_remote_parent, tracer_provider, Recorder, Telemetry};
+    use opentelemetry::trace::{SpanKind, Status};
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tonic::codegen::http::HeaderMap;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn headers(traceparent: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", traceparent.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn children_join_the_remote_trace() {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!(
+                "request",
+                otel.name = "GET /books/:id",
+                otel.kind = "server",
+                http.response.status_code = tracing::field::Empty,
+            );
+            set_remote_parent(&request, &headers(REMOTE));
+            let _entered = request.enter();
+
+            tracing::info_span!("repository", otel.kind = "client", otel.status_code = "ERROR").in_scope(|| {});
+            request.record("http.response.status_code", 404);
+        });
+
+        let spans = recorder.spans();
+        let [repository, request] = spans.as_slice() else { panic!("{:?}", spans) };
+
+        assert_eq!(request.name, "GET /books/:id");
+        assert_eq!(request.span_kind, SpanKind::Server);
+        assert_eq!(request.span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(request.parent_span_id.to_string(), "00f067aa0ba902b7");
+        assert!(request.attributes.iter().any(|kv| kv.key.as_str() == "http.response.status_code"));
+        assert_eq!(request.status, Status::Unset);
+
+        assert_eq!(repository.name, "repository");
+        assert_eq!(repository.span_kind, SpanKind::Client);
+        assert_eq!(repository.span_context.trace_id(), request.span_context.trace_id());
+        assert_eq!(repository.parent_span_id, request.span_context.span_id());
+        assert!(matches!(repository.status, Status::Error { .. }));
+    }
+
+    #[test]
+    fn unsampled_traces_are_not_exported() {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request");
+            set_remote_parent(&request, &headers(&REMOTE.replace("-01", "-00")));
+            request.in_scope(|| tracing::info_span!("repository").in_scope(|| {}));
+            // Cabecera mal formada: traza nueva
+            let request = tracing::info_span!("request");
+            set_remote_parent(&request, &headers("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"));
+            request.in_scope(|| {});
+        });
+
+        let spans = recorder.spans();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].parent_span_id, opentelemetry::trace::SpanId::INVALID);
+    }
+
+    #[derive(Clone, Default)]
+    struct Collector {
+        requests: Arc<tokio::sync::Mutex<Vec<ExportTraceServiceRequest>>>,
+    }
+
+    #[tonic::async_trait]
+    impl TraceService for Collector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            self.requests.lock().await.push(request.into_inner());
+            Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    // Varios hilos: `shutdown` bloquea uno mientras el procesador envía en otro
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exporter_sends_batches_to_the_collector() {
+        let collector = Collector::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(collector.clone()))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let provider = tracer_provider(Some(&format!("http://{}", addr)));
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", otel.name = "GET /books").in_scope(|| {});
+        });
+        Telemetry { provider }.shutdown().await;
+
+        let requests = collector.requests.lock().await;
+        let resource_spans = &requests[0].resource_spans[0];
+        let service = &resource_spans.resource.as_ref().unwrap().attributes;
+        assert!(service.iter().any(|kv| kv.key == "service.name"));
+        assert_eq!(resource_spans.scope_spans[0].spans[0].name, "GET /books");
+    }
+}
@@ -1,37 +1,86 @@
+// Los tests originales pasan las URL como `&format!(...)`; se mantienen así
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use tokio::task;
 use reqwest::StatusCode;
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
+use sqlx::{Executor, SqlitePool, postgres::PgPoolOptions, sqlite::SqlitePoolOptions};
 use std::sync::Arc;
 use once_cell::sync::Lazy;
 use serde_json::json;
 use library_api::app::build_app;
-use library_api::infra::sqlite_book_repository::SqliteBookRepository;
+use library_api::app::{
+    book_repository::RepoError,
+    idempotency_store::{IdempotencyStore, Reservation, StoredResponse},
+};
+use library_api::config::Config;
+use library_api::infra::{
+    Database, memory_backend, memory_book_repository::InMemoryBookRepository, postgres_backend, sqlite_backend,
+};
+use library_api::grpc::{catalog_service::Catalog, proto::{self, catalog_service_client::CatalogServiceClient}};
 use axum::serve;
 use tokio::net::TcpListener;
 
 // BD en memoria compartida para los tests
 static DB_URL: Lazy<String> = Lazy::new(|| "sqlite::memory:".to_string());
 
+/// Configuración de los tests: la del binario salvo el secreto y un origen CORS
+fn test_config() -> Config {
+    Config {
+        cors_allowed_origins: vec!["https://app.example".into()],
+        ..Config::new(DB_URL.clone(), "test-secret".into())
+    }
+}
+
+/// App sobre los almacenes en memoria: la mayoría de tests no necesitan BD
 async fn spawn_app() -> String {
-    // obtener token
-    std::env::set_var("JWT_SECRET", "test-secret");
-    // Conectar y migrar la BD en memoria
-    
+    serve_app(build_app(memory_backend(), test_config())).await
+}
+
+/// Igual que `spawn_app` pero contra SQLite
+async fn spawn_sqlite_app() -> String {
+    // Una sola conexión: cada conexión a `:memory:` abre una BD distinta
     let pool: SqlitePool = SqlitePoolOptions::new()
+        .max_connections(1)
         .connect(&DB_URL)
         .await
         .unwrap();
-    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    // Igual que al arrancar el binario
+    Database::Sqlite(pool.clone()).migrate(true).await.unwrap();
+
+    serve_app(build_app(sqlite_backend(pool), test_config())).await
+}
+
+/// Igual que `spawn_app` pero contra Postgres, en un esquema nuevo por test.
+/// `None` si `TEST_POSTGRES_URL` no está definida.
+async fn spawn_postgres_app() -> Option<String> {
+    let url = std::env::var("TEST_POSTGRES_URL").ok()?;
+
+    let schema = format!("test_{}", uuid::Uuid::new_v4().simple());
+    let admin = PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap();
+    sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&admin).await.unwrap();
+    let search_path = format!("SET search_path TO {}", schema);
+    let pool = PgPoolOptions::new()
+        .after_connect(move |conn, _| {
+            let search_path = search_path.clone();
+            Box::pin(async move {
+                conn.execute(search_path.as_str()).await?;
+                Ok(())
+            })
+        })
+        .connect(&url)
+        .await
+        .unwrap();
+    Database::Postgres(pool.clone()).migrate(true).await.unwrap();
 
-    // Construir el repo y la app
-    let repo = Arc::new(SqliteBookRepository { pool });
-    let app = build_app(repo);
+    Some(serve_app(build_app(postgres_backend(pool), test_config())).await)
+}
 
+async fn serve_app(app: axum::Router) -> String {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
 
     task::spawn(async move {
-        serve(listener, app).await.unwrap();
+        serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await.unwrap();
     });
 
     format!("http://{}", addr)
@@ -49,6 +98,21 @@ async fn get_token(base: &str) -> String {
     res.json::<String>().await.unwrap()
 }
 
+/// Token válido con rol `cataloguer` (el login solo emite tokens de admin)
+fn cataloguer_token() -> String {
+    let claims = library_api::middleware::auth::Claims {
+        sub: "cataloguer".into(),
+        exp: (chrono::Utc::now().timestamp() + 3600) as usize,
+        role: library_api::middleware::auth::Role::Cataloguer,
+    };
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(b"test-secret"),
+    )
+    .unwrap()
+}
+
 #[tokio::test]
 async fn post_and_get_book_flow() {
     let base = spawn_app().await;
@@ -119,7 +183,7 @@ async fn put_and_delete_book_flow() {
     let put_res = client
         .put(&format!("{}/books/{}", &base, id))
         .bearer_auth(&token)
-        .json(&json!({ "title": "Nineteen Eighty-Four" }))
+        .json(&json!({ "title": "Nineteen Eighty-Four", "author": "George Orwell", "published_year": 1949 }))
         .send()
         .await
         .unwrap();
@@ -143,6 +207,10 @@ async fn put_and_delete_book_flow() {
         .await
         .unwrap();
     assert_eq!(not_found.status(), StatusCode::NOT_FOUND);
+    let err: serde_json::Value = not_found.json().await.unwrap();
+    assert_eq!(err["code"], "not_found");
+    // Sin `X-Request-Id` del cliente se genera uno
+    assert!(err["request_id"].as_str().is_some_and(|id| !id.is_empty()));
 }
 #[tokio::test]
 async fn get_books_returns_empty_list() {
@@ -176,8 +244,11 @@ async fn post_book_without_auth_is_unauthorized() {
         .unwrap();
 
     assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers()["content-type"], "application/problem+json");
     let json: serde_json::Value = resp.json().await.unwrap();
-    assert_eq!(json["error"], "Unauthorized");
+    assert_eq!(json["code"], "unauthorized");
+    assert_eq!(json["status"], 401);
+    assert_eq!(json["detail"], "Unauthorized");
 }
 
 #[tokio::test]
@@ -260,16 +331,1882 @@ async fn put_book_invalid_payload_returns_400() {
     let res = client
         .put(&format!("{}/books/{}", &base, id))
         .bearer_auth(&token)
-        .json(&json!({ "title": "" }))
+        .header("x-request-id", "req-123")
+        .json(&json!({ "title": "", "author": "Robert" }))
         .send()
         .await
         .unwrap();
     assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.headers()["x-request-id"], "req-123");
+    assert_eq!(res.headers()["content-type"], "application/problem+json");
 
     let err: serde_json::Value = res.json().await.unwrap();
-    assert!(err["error"]
+    assert_eq!(err["type"], "urn:library-api:problem:validation_failed");
+    assert_eq!(err["code"], "validation_failed");
+    assert_eq!(err["request_id"], "req-123");
+    assert_eq!(err["errors"]["title"], json!(["Title cannot be empty"]));
+    assert!(err["detail"]
+        .as_str()
+        .unwrap()
+        .contains("Title cannot be empty"));
+}
+
+
+#[tokio::test]
+async fn malformed_json_bodies_are_problem_details() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let send = |content_type: &'static str, body: &'static str| {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(&token)
+            .header("content-type", content_type)
+            .body(body)
+            .send()
+    };
+    for (content_type, body, status, code) in [
+        ("application/json", r#"{"title": "Dune""#, StatusCode::BAD_REQUEST, "bad_request"),
+        ("application/json", r#"{"title": 1, "author": "Frank Herbert"}"#, StatusCode::BAD_REQUEST, "bad_request"),
+        ("text/plain", r#"{"title": "Dune", "author": "Frank Herbert"}"#, StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type"),
+    ] {
+        let res = send(content_type, body).await.unwrap();
+        assert_eq!(res.status(), status, "{}", body);
+        assert_eq!(res.headers()["content-type"], "application/problem+json");
+        let err: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(err["code"], code);
+        assert!(err["request_id"].is_string());
+    }
+
+    // También en las rutas públicas
+    let res = client
+        .post(format!("{}/login", &base))
+        .header("content-type", "application/json")
+        .body("{")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.headers()["content-type"], "application/problem+json");
+}
+
+#[tokio::test]
+async fn openapi_spec_matches_router() {
+    // Se prueban todos los métodos de cada ruta: más que la ráfaga de `/login`
+    let config = Config { rate_limit_login_per_minute: 0, ..test_config() };
+    let base = serve_app(build_app(memory_backend(), config)).await;
+    let client = reqwest::Client::new();
+    // Con token, para que el middleware de auth no oculte los 405
+    let token = get_token(&base).await;
+
+    let res = client
+        .get(format!("{}/openapi.json", &base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let spec: serde_json::Value = res.json().await.unwrap();
+    let paths = spec["paths"].as_object().expect("el spec debe tener paths");
+    assert!(!paths.is_empty());
+
+    let all_methods = ["get", "post", "put", "patch", "delete"];
+    for (path, item) in paths {
+        let url = format!("{}{}", &base, path.replace("{id}", "does-not-exist"));
+        for method in all_methods {
+            let res = client
+                .request(method.to_uppercase().parse().unwrap(), &url)
+                .bearer_auth(&token)
+                .json(&json!({}))
+                .send()
+                .await
+                .unwrap();
+            let status = res.status();
+            let body = res.text().await.unwrap();
+
+            if item.get(method).is_some() {
+                // Documentado: el router debe tener la ruta (el fallback de axum es 404 sin cuerpo)
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {} no está en el router", method, path);
+                assert!(
+                    !(status == StatusCode::NOT_FOUND && body.is_empty()),
+                    "{} {} no está en el router",
+                    method,
+                    path
+                );
+            } else {
+                // No documentado: el router no debe aceptarlo
+                assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {} no está en el spec", method, path);
+            }
+        }
+    }
+}
+
+/// Rutas del router que no son REST y por eso no van en el spec
+const UNDOCUMENTED_ROUTES: [&str; 3] = ["/openapi.json", "/docs", "/graphql"];
+
+/// Pares (ruta en formato OpenAPI, método) de cada `.route(...)` de `app::routes`.
+/// axum no deja listar las rutas de un `Router`, así que se leen del fuente.
+fn router_routes() -> Vec<(String, String)> {
+    let source = include_str!("../src/app/mod.rs");
+    let mut routes = Vec::new();
+    for (start, _) in source.match_indices(".route(") {
+        let rest = &source[start + ".route(".len()..];
+        let path = rest.split('"').nth(1).expect("la ruta debe ser un literal");
+        // Argumentos hasta el paréntesis que cierra `.route(`
+        let mut depth = 1;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                depth += match c { '(' => 1, ')' => -1, _ => 0 };
+                depth == 0
+            })
+            .map(|(i, _)| i)
+            .unwrap();
+        let args = &rest[..end];
+        let path = path
+            .split('/')
+            .map(|s| s.strip_prefix(':').map_or(s.to_string(), |p| format!("{{{}}}", p)))
+            .collect::<Vec<_>>()
+            .join("/");
+        for method in ["get", "post", "put", "patch", "delete"] {
+            let called = args.match_indices(&format!("{}(", method)).any(|(i, _)| {
+                i == 0 || !args[..i].ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == ':')
+            });
+            if called {
+                routes.push((path.clone(), method.to_string()));
+            }
+        }
+    }
+    routes
+}
+
+#[tokio::test]
+async fn router_routes_are_documented() {
+    let base = spawn_app().await;
+    let spec: serde_json::Value = reqwest::get(format!("{}/openapi.json", &base)).await.unwrap().json().await.unwrap();
+
+    let routes = router_routes();
+    assert!(routes.len() > 20, "no se han leído las rutas: {:?}", routes);
+    for (path, method) in routes {
+        if UNDOCUMENTED_ROUTES.contains(&path.as_str()) {
+            continue;
+        }
+        assert!(spec["paths"][&path][&method].is_object(), "{} {} no está en el spec", method, path);
+    }
+}
+
+#[tokio::test]
+async fn docs_ui_is_served() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/docs", &base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let html = res.text().await.unwrap();
+    assert!(html.contains("/openapi.json"));
+}
+
+#[tokio::test]
+async fn graphql_mutations_require_auth_and_queries_are_public() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let create = json!({
+        "query": "mutation($input: CreateBookInput!) { createBook(input: $input) { id title } }",
+        "variables": { "input": { "title": "Dune", "author": "Frank Herbert", "publishedYear": 1965 } }
+    });
+
+    // 1) Sin token la mutación falla
+    let res: serde_json::Value = client
+        .post(format!("{}/graphql", &base))
+        .json(&create)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res["errors"][0]["extensions"]["code"], "unauthorized");
+
+    // 2) Con token crea el libro
+    let res: serde_json::Value = client
+        .post(format!("{}/graphql", &base))
+        .bearer_auth(&token)
+        .json(&create)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(res["errors"].is_null(), "{}", res);
+    let id = res["data"]["createBook"]["id"].as_str().unwrap().to_string();
+
+    // 3) La consulta es pública y agrupa varios `book` en una sola carga
+    let res: serde_json::Value = client
+        .post(format!("{}/graphql", &base))
+        .json(&json!({
+            "query": format!(
+                r#"{{ a: book(id: "{id}") {{ title }} b: book(id: "{id}") {{ author }} missing: book(id: "nope") {{ id }} }}"#
+            )
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res["data"]["a"]["title"], "Dune");
+    assert_eq!(res["data"]["b"]["author"], "Frank Herbert");
+    assert!(res["data"]["missing"].is_null());
+}
+
+#[tokio::test]
+async fn graphql_rejects_invalid_input_and_excessive_complexity() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let res: serde_json::Value = client
+        .post(format!("{}/graphql", &base))
+        .bearer_auth(&token)
+        .json(&json!({
+            "query": r#"mutation { createBook(input: { title: "", author: "x" }) { id } }"#
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res["errors"][0]["extensions"]["code"], "validation_failed");
+    assert_eq!(res["errors"][0]["extensions"]["errors"]["title"], json!(["Title cannot be empty"]));
+    assert!(res["errors"][0]["message"]
         .as_str()
         .unwrap()
         .contains("Title cannot be empty"));
+
+    // Muchos alias disparan el límite de complejidad
+    let aliases: String = (0..300).map(|i| format!("b{}: books {{ id }} ", i)).collect();
+    let res: serde_json::Value = client
+        .post(format!("{}/graphql", &base))
+        .json(&json!({ "query": format!("{{ {} }}", aliases) }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(res["data"].is_null());
+    assert!(res["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("complex"));
 }
 
+async fn spawn_grpc() -> CatalogServiceClient<tonic::transport::Channel> {
+    spawn_grpc_with(Arc::new(InMemoryBookRepository::new())).await
+}
+
+async fn spawn_grpc_with(repo: Arc<InMemoryBookRepository>) -> CatalogServiceClient<tonic::transport::Channel> {
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(Catalog::new(repo, Arc::new(test_config())).into_service())
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+
+    CatalogServiceClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn grpc_catalog_crud_and_stream() {
+    let base = spawn_app().await;
+    let token = get_token(&base).await;
+    let mut client = spawn_grpc().await;
+
+    // 1) Create sin token es rechazado
+    let err = client
+        .create(proto::CreateBookRequest {
+            title: "Dune".into(),
+            author: "Frank Herbert".into(),
+            published_year: Some(1965),
+            isbn: None,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+
+    // 2) Create con token
+    let mut req = tonic::Request::new(proto::CreateBookRequest {
+        title: "Dune".into(),
+        author: "Frank Herbert".into(),
+        published_year: Some(1965),
+        isbn: Some("978-0-441-17271-9".into()),
+    });
+    req.metadata_mut()
+        .insert("authorization", format!("Bearer {}", token).parse().unwrap());
+    let created = client.create(req).await.unwrap().into_inner();
+
+    // 3) Get / Search / StreamList
+    let fetched = client
+        .get(proto::GetBookRequest { id: created.id.clone() })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fetched.title, "Dune");
+    assert_eq!(fetched.isbn.as_deref(), Some("978-0-441-17271-9"));
+
+    let found = client
+        .search(proto::SearchBooksRequest { title: None, author: Some("Herbert".into()) })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(found.books.len(), 1);
+
+    let mut stream = client
+        .stream_list(proto::ListBooksRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    let mut streamed = Vec::new();
+    while let Some(book) = stream.message().await.unwrap() {
+        streamed.push(book);
+    }
+    assert_eq!(streamed.len(), 1);
+
+    // 4) Get de un id inexistente
+    let err = client
+        .get(proto::GetBookRequest { id: "nope".into() })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn grpc_stream_list_pages_through_the_whole_catalog() {
+    use library_api::{app::book_repository::BookRepository, domain::book::Book};
+
+    // Más de una página de `StreamList`, con un libro borrado en medio
+    let repo = Arc::new(InMemoryBookRepository::new());
+    let mut ids = Vec::new();
+    for i in 0..250 {
+        let book = repo.create(Book::new(format!("Book {}", i), "Anon".into(), None)).await.unwrap();
+        ids.push(book.id);
+    }
+    repo.delete(&ids.remove(120), None, "admin").await.unwrap();
+    ids.sort();
+    let mut client = spawn_grpc_with(repo).await;
+
+    let mut stream = client.stream_list(proto::ListBooksRequest {}).await.unwrap().into_inner();
+    let mut streamed = Vec::new();
+    while let Some(book) = stream.message().await.unwrap() {
+        streamed.push(book.id);
+    }
+    assert_eq!(streamed, ids);
+}
+
+#[tokio::test]
+async fn grpc_calls_are_rate_limited_by_peer_or_subject() {
+    let config = Config { rate_limit_per_minute: 1, rate_limit_burst: 2, ..test_config() };
+    let catalog = Catalog::new(Arc::new(InMemoryBookRepository::new()), Arc::new(config)).into_service();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(catalog)
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+    let mut client = CatalogServiceClient::connect(format!("http://{}", addr)).await.unwrap();
+
+    let get = |token: Option<String>| {
+        let mut req = tonic::Request::new(proto::GetBookRequest { id: "nope".into() });
+        if let Some(token) = token {
+            req.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        }
+        req
+    };
+    // Las lecturas anónimas cuentan por la IP de la conexión
+    for _ in 0..2 {
+        assert_eq!(client.get(get(None)).await.unwrap_err().code(), tonic::Code::NotFound);
+    }
+    let err = client.get(get(None)).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(err.metadata().get("retry-after").unwrap(), "60");
+
+    // Con token, por usuario
+    assert_eq!(client.get(get(Some(cataloguer_token()))).await.unwrap_err().code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn grpc_calls_share_the_metered_repository_with_http() {
+    use library_api::{grpc::telemetry::GrpcTelemetryLayer, infra::memory_backend, metrics::Metrics};
+
+    // Como en el binario: un repositorio medido y un `Metrics` para los dos servidores
+    let metrics = Arc::new(Metrics::new());
+    let backend = memory_backend().metered(metrics.clone());
+    let catalog = Catalog::new(backend.books.clone(), Arc::new(test_config())).into_service();
+    let base = serve_app(library_api::app::build_metered_app(backend, test_config(), metrics.clone())).await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move {
+        tonic::transport::Server::builder()
+            .layer(GrpcTelemetryLayer::new(metrics))
+            .add_service(catalog)
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+    let mut client = CatalogServiceClient::connect(format!("http://{}", addr)).await.unwrap();
+
+    let mut req = tonic::Request::new(proto::CreateBookRequest {
+        title: "Dune".into(),
+        author: "Frank Herbert".into(),
+        published_year: None,
+        isbn: None,
+    });
+    req.metadata_mut()
+        .insert("authorization", format!("Bearer {}", get_token(&base).await).parse().unwrap());
+    client.create(req).await.unwrap();
+    let err = client.get(proto::GetBookRequest { id: "nope".into() }).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::NotFound);
+    let mut stream = client.stream_list(proto::ListBooksRequest {}).await.unwrap().into_inner();
+    while stream.message().await.unwrap().is_some() {}
+
+    // La llamada se registra al soltar la respuesta, que puede ir un poco por detrás del cliente
+    let expected = [
+        r#"grpc_requests_total{method="/library.catalog.v1.CatalogService/Create",code="Ok"} 1"#,
+        r#"grpc_requests_total{method="/library.catalog.v1.CatalogService/Get",code="NotFound"} 1"#,
+        r#"grpc_requests_total{method="/library.catalog.v1.CatalogService/StreamList",code="Ok"} 1"#,
+        // Solo gRPC ha creado y buscado libros
+        r#"repository_call_duration_seconds_count{method="create",outcome="ok"} 1"#,
+        r#"repository_call_duration_seconds_count{method="get_by_id",outcome="ok"} 1"#,
+    ];
+    let mut body = String::new();
+    for _ in 0..50 {
+        body = reqwest::get(format!("{}/metrics", &base)).await.unwrap().text().await.unwrap();
+        if expected.iter().all(|line| body.contains(line)) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    for line in expected {
+        assert!(body.contains(line), "{}\n{}", line, body);
+    }
+}
+
+#[tokio::test]
+async fn etags_guard_concurrent_updates_and_deletes() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let created: serde_json::Value = client
+        .post(format!("{}/books", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Emma", "author": "Jane Austen" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+    let url = format!("{}/books/{}", &base, id);
+
+    // 1) GET devuelve ETag y responde 304 a If-None-Match
+    let res = client.get(&url).send().await.unwrap();
+    let etag = res.headers()["etag"].to_str().unwrap().to_string();
+    let res = client
+        .get(&url)
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    // 2) Primer PUT con el ETag vigente gana
+    let res = client
+        .put(&url)
+        .bearer_auth(&token)
+        .header("if-match", &etag)
+        .json(&json!({ "title": "Emma (1815)", "author": "Jane Austen" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let new_etag = res.headers()["etag"].to_str().unwrap().to_string();
+    assert_ne!(new_etag, etag);
+
+    // 3) Segundo PUT con el ETag viejo es rechazado
+    let res = client
+        .put(&url)
+        .bearer_auth(&token)
+        .header("if-match", &etag)
+        .json(&json!({ "title": "Emma!", "author": "Jane Austen" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let err: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(err["code"], "precondition_failed");
+
+    // 4) DELETE con ETag viejo falla, con el vigente borra
+    let res = client
+        .delete(&url)
+        .bearer_auth(&token)
+        .header("if-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    let res = client
+        .delete(&url)
+        .bearer_auth(&token)
+        .header("if-match", &new_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn update_and_delete_of_missing_book_return_404() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+    let url = format!("{}/books/{}", &base, "missing-id");
+
+    let res = client
+        .put(&url)
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Ghost", "author": "Nobody" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = client.delete(&url).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let err: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(err["code"], "not_found");
+
+    // Modo idempotente: borrar algo inexistente también es 204
+    let res = client
+        .delete(format!("{}?idempotent=true", url))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn put_replaces_the_whole_book() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let created: serde_json::Value = client
+        .post(format!("{}/books", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Ulysses", "author": "James Joyce", "published_year": 1922 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("{}/books/{}", &base, created["id"].as_str().unwrap());
+
+    // Sin published_year queda en null
+    let updated: serde_json::Value = client
+        .put(&url)
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Ulysses", "author": "James Joyce" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(updated["published_year"].is_null());
+
+    // Faltan campos obligatorios
+    let res = client
+        .put(&url)
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Ulysses" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_client_error());
+}
+
+#[tokio::test]
+async fn patch_supports_merge_patch_and_json_patch() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let created: serde_json::Value = client
+        .post(format!("{}/books", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Middlemarch", "author": "George Eliot", "published_year": 1871 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("{}/books/{}", &base, created["id"].as_str().unwrap());
+
+    // 1) Merge patch: null borra published_year
+    let res = client
+        .patch(&url)
+        .bearer_auth(&token)
+        .header("content-type", "application/merge-patch+json")
+        .body(r#"{ "title": "Middlemarch: A Study", "published_year": null }"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let patched: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(patched["title"], "Middlemarch: A Study");
+    assert_eq!(patched["author"], "George Eliot");
+    assert!(patched["published_year"].is_null());
+
+    // 2) JSON Patch con test + replace
+    let res = client
+        .patch(&url)
+        .bearer_auth(&token)
+        .header("content-type", "application/json-patch+json")
+        .body(
+            json!([
+                { "op": "test", "path": "/author", "value": "George Eliot" },
+                { "op": "replace", "path": "/author", "value": "Mary Ann Evans" },
+                { "op": "add", "path": "/published_year", "value": 1872 }
+            ])
+            .to_string(),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let patched: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(patched["author"], "Mary Ann Evans");
+    assert_eq!(patched["published_year"], 1872);
+
+    // 3) Un test fallido es un conflicto
+    let res = client
+        .patch(&url)
+        .bearer_auth(&token)
+        .header("content-type", "application/json-patch+json")
+        .body(json!([{ "op": "test", "path": "/author", "value": "George Eliot" }]).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    // 4) El resultado se valida como CreateBook
+    let res = client
+        .patch(&url)
+        .bearer_auth(&token)
+        .header("content-type", "application/merge-patch+json")
+        .body(r#"{ "title": "" }"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let err: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(err["errors"]["title"], json!(["Title cannot be empty"]));
+
+    // 5) Campos no editables y media types desconocidos
+    let res = client
+        .patch(&url)
+        .bearer_auth(&token)
+        .header("content-type", "application/merge-patch+json")
+        .body(r#"{ "id": "other" }"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let res = client
+        .patch(&url)
+        .bearer_auth(&token)
+        .json(&json!({ "title": "x" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn batch_endpoint_reports_per_item_results() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+    let url = format!("{}/books/batch", &base);
+
+    // 1) Best-effort: los válidos se aplican aunque otros fallen
+    let res = client
+        .post(&url)
+        .bearer_auth(&token)
+        .json(&json!({
+            "mode": "best_effort",
+            "operations": [
+                { "op": "create", "body": { "title": "Persuasion", "author": "Jane Austen" } },
+                { "op": "create", "body": { "title": "", "author": "Nobody" } },
+                { "op": "delete", "id": "missing-id" }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["committed"], true);
+    assert_eq!(body["results"][0]["status"], 201);
+    let id = body["results"][0]["body"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["results"][1]["status"], 400);
+    assert_eq!(body["results"][1]["error"]["errors"]["title"], json!(["Title cannot be empty"]));
+    assert_eq!(body["results"][2]["status"], 404);
+    assert_eq!(body["results"][2]["error"]["code"], "not_found");
+
+    // 2) Atómico: un fallo deshace el resto
+    let res = client
+        .post(&url)
+        .bearer_auth(&token)
+        .json(&json!({
+            "operations": [
+                { "op": "update", "id": id, "body": { "title": "Persuasion (1817)", "author": "Jane Austen" } },
+                { "op": "delete", "id": "missing-id" }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["committed"], false);
+    assert_eq!(body["results"][0]["status"], 424);
+    assert_eq!(body["results"][1]["status"], 404);
+    let book: serde_json::Value = client
+        .get(format!("{}/books/{}", &base, id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(book["title"], "Persuasion");
+
+    // 3) Atómico y sin errores: update + delete
+    let res = client
+        .post(&url)
+        .bearer_auth(&token)
+        .json(&json!({
+            "mode": "atomic",
+            "operations": [
+                { "op": "update", "id": id, "version": 1, "body": { "title": "Persuasion (1817)", "author": "Jane Austen" } },
+                { "op": "delete", "id": id }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["committed"], true);
+    assert_eq!(body["results"][0]["status"], 200);
+    assert_eq!(body["results"][0]["body"]["version"], 2);
+    assert_eq!(body["results"][1]["status"], 204);
+
+    // Un lote vacío se rechaza sin abrir transacción
+    let res = client
+        .post(format!("{}/books/batch", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "operations": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn idempotency_key_replays_post_responses() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+    let book = json!({ "title": "Walden", "author": "Henry David Thoreau", "published_year": 1854 });
+
+    let post_as = |token: String, body: serde_json::Value| {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(token)
+            .header("idempotency-key", "walden-1")
+            .json(&body)
+            .send()
+    };
+    let post = |body: serde_json::Value| post_as(token.clone(), body);
+
+    // 1) Primera petición crea, la repetición devuelve lo mismo, cabeceras incluidas
+    let first = post(book.clone()).await.unwrap();
+    assert_eq!(first.status(), StatusCode::CREATED);
+    let first_etag = first.headers()["etag"].clone();
+    let first: serde_json::Value = first.json().await.unwrap();
+
+    let second = post(book.clone()).await.unwrap();
+    assert_eq!(second.status(), StatusCode::CREATED);
+    assert_eq!(second.headers()["idempotent-replayed"], "true");
+    assert_eq!(second.headers()["etag"], first_etag);
+    assert_eq!(second.headers()["location"], format!("/books/{}", first["id"].as_str().unwrap()).as_str());
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(first["id"], second["id"]);
+
+    let list: Vec<serde_json::Value> = client
+        .get(format!("{}/books/search?title=Walden", &base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(list.len(), 1);
+
+    // 2) Misma clave con otro cuerpo
+    let res = post(json!({ "title": "Walden II", "author": "B. F. Skinner" })).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // 3) Otro usuario con la misma clave no ve la respuesta del primero
+    let other = post_as(cataloguer_token(), book.clone()).await.unwrap();
+    assert_eq!(other.status(), StatusCode::CREATED);
+    assert!(other.headers().get("idempotent-replayed").is_none());
+    let other: serde_json::Value = other.json().await.unwrap();
+    assert_ne!(other["id"], first["id"]);
+}
+
+/// Almacén que reserva claves pero no consigue guardar las respuestas
+struct FailingCompletion(library_api::infra::memory_idempotency_store::InMemoryIdempotencyStore);
+
+#[async_trait::async_trait]
+impl IdempotencyStore for FailingCompletion {
+    async fn reserve(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        not_before: chrono::DateTime<chrono::Utc>,
+        lease_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Reservation, RepoError> {
+        self.0.reserve(scope, key, request_hash, not_before, lease_before).await
+    }
+
+    async fn complete(&self, _scope: &str, _key: &str, _response: &StoredResponse) -> Result<(), RepoError> {
+        Err(RepoError::Other(anyhow::anyhow!("disk full")))
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), RepoError> {
+        self.0.release(scope, key).await
+    }
+}
+
+#[tokio::test]
+async fn a_failed_response_write_does_not_hide_the_creation() {
+    let backend = library_api::app::Backend {
+        idempotency_store: Arc::new(FailingCompletion(Default::default())),
+        ..memory_backend()
+    };
+    let base = serve_app(build_app(backend, test_config())).await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+    let post = || {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(&token)
+            .header("idempotency-key", "walden-1")
+            .json(&json!({ "title": "Walden", "author": "Henry David Thoreau" }))
+            .send()
+    };
+
+    // Con un 500 el cliente reintentaría y, caducada la reserva, crearía otro
+    assert_eq!(post().await.unwrap().status(), StatusCode::CREATED);
+    // Sin respuesta guardada, la reserva sigue bloqueando la clave
+    assert_eq!(post().await.unwrap().status(), StatusCode::CONFLICT);
+    let books: Vec<serde_json::Value> = client.get(format!("{}/books", &base)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(books.len(), 1);
+}
+
+#[tokio::test]
+async fn duplicates_are_reported_and_merged() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let create = |body: serde_json::Value| {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+    };
+
+    // 1) El segundo alta avisa del parecido pero se crea igualmente
+    let first = create(json!({ "title": "The Lord of the Rings", "author": "J.R.R. Tolkien", "isbn": "978-0-618-64015-7" }))
+        .await
+        .unwrap();
+    assert!(first.headers().get("x-possible-duplicates").is_none());
+    let first: serde_json::Value = first.json().await.unwrap();
+
+    let second = create(json!({ "title": "Lord of the Rings", "author": "J. R. R. Tolkien", "published_year": 1954 }))
+        .await
+        .unwrap();
+    assert_eq!(second.status(), StatusCode::CREATED);
+    assert_eq!(second.headers()["x-possible-duplicates"], first["id"].as_str().unwrap());
+    let second: serde_json::Value = second.json().await.unwrap();
+
+    let res = create(json!({ "title": "Dune", "author": "Frank Herbert", "isbn": "978-0-441-17271-8" }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // 2) Informe, solo para usuarios autenticados
+    let res = client.get(format!("{}/books/duplicates", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let report: Vec<serde_json::Value> = client
+        .get(format!("{}/books/duplicates", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0]["reason"], "similar_title_and_author");
+
+    // 3) Fusión: el destino hereda el año y el id antiguo redirige
+    let url = format!("{}/books/{}/merge", &base, first["id"].as_str().unwrap());
+    let res = client
+        .post(&url)
+        .bearer_auth(&token)
+        .json(&json!({ "source_id": second["id"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let merged: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(merged["published_year"], 1954);
+    assert_eq!(merged["isbn"], "978-0-618-64015-7");
+
+    let no_redirect = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let res = no_redirect
+        .get(format!("{}/books/{}", &base, second["id"].as_str().unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+    let followed: serde_json::Value = client
+        .get(format!("{}/books/{}", &base, second["id"].as_str().unwrap()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(followed["id"], first["id"]);
+
+    // 4) Ya no quedan duplicados; fusionar de nuevo el origen da 404
+    let report: Vec<serde_json::Value> = client
+        .get(format!("{}/books/duplicates", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(report.is_empty());
+    let res = client
+        .post(&url)
+        .bearer_auth(&token)
+        .json(&json!({ "source_id": second["id"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn deleted_books_can_be_restored_or_purged_by_admins() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let book: serde_json::Value = client
+        .post(format!("{}/books", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Middlemarch", "author": "George Eliot" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = book["id"].as_str().unwrap();
+    let res = client
+        .delete(format!("{}/books/{}", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let res = client.get(format!("{}/books/{}", &base, id)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // 1) Un token sin rol admin no accede a la papelera
+    let cataloguer = cataloguer_token();
+    let res = client
+        .get(format!("{}/books/trash", &base))
+        .bearer_auth(&cataloguer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // 2) Listar y restaurar
+    let trash: Vec<serde_json::Value> = client
+        .get(format!("{}/books/trash", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(trash.len(), 1);
+    assert!(trash[0]["deleted_at"].is_string());
+
+    let res = client
+        .post(format!("{}/books/trash/{}/restore", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client.get(format!("{}/books/{}", &base, id)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 3) Purgar exige que el libro esté en la papelera
+    let purge = || {
+        client
+            .delete(format!("{}/books/trash/{}", &base, id))
+            .bearer_auth(&token)
+            .send()
+    };
+    assert_eq!(purge().await.unwrap().status(), StatusCode::NOT_FOUND);
+    client
+        .delete(format!("{}/books/{}", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(purge().await.unwrap().status(), StatusCode::NO_CONTENT);
+    let res = client
+        .post(format!("{}/books/trash/{}/restore", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn history_records_changes_and_reverts() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let book: serde_json::Value = client
+        .post(format!("{}/books", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Ulysses", "author": "James Joyce", "published_year": 1922 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = book["id"].as_str().unwrap();
+    client
+        .put(format!("{}/books/{}", &base, id))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Ulises", "author": "James Joyce" }))
+        .send()
+        .await
+        .unwrap();
+
+    // 1) El historial exige token y guarda quién y qué cambió
+    let res = client.get(format!("{}/books/{}/history", &base, id)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let history: Vec<serde_json::Value> = client
+        .get(format!("{}/books/{}/history", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["action"], "create");
+    assert_eq!(history[0]["actor"], "admin");
+    assert!(history[0]["before"].is_null());
+    assert_eq!(history[1]["action"], "update");
+    assert_eq!(history[1]["before"]["title"], "Ulysses");
+    assert_eq!(history[1]["after"]["published_year"], serde_json::Value::Null);
+
+    // 2) Volver al estado de la primera revisión
+    let res = client
+        .post(format!("{}/books/{}/history/{}/revert", &base, id, history[0]["id"]))
+        .bearer_auth(&token)
+        .header("if-match", "\"2\"")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let reverted: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(reverted["title"], "Ulysses");
+    assert_eq!(reverted["published_year"], 1922);
+    assert_eq!(reverted["version"], 3);
+
+    // 3) El borrado también queda registrado y no se puede "revertir a él"
+    client
+        .delete(format!("{}/books/{}", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let history: Vec<serde_json::Value> = client
+        .get(format!("{}/books/{}/history", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let actions: Vec<&str> = history.iter().map(|r| r["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["create", "update", "revert", "delete"]);
+    let res = client
+        .post(format!("{}/books/{}/history/{}/revert", &base, id, history[3]["id"]))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn every_mutation_path_records_a_revision() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+    let actions = |id: String| {
+        let request = client
+            .get(format!("{}/books/{}/history", &base, id))
+            .bearer_auth(&token)
+            .send();
+        async move {
+            let history: Vec<serde_json::Value> = request.await.unwrap().json().await.unwrap();
+            history
+                .iter()
+                .map(|r| format!("{}:{}", r["action"].as_str().unwrap(), r["actor"].as_str().unwrap()))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    // 1) Lote: alta, cambio y baja en una sola petición
+    let body: serde_json::Value = client
+        .post(format!("{}/books/batch", &base))
+        .bearer_auth(&token)
+        .json(&json!({
+            "operations": [{ "op": "create", "body": { "title": "Emma", "author": "Jane Austen" } }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let emma = body["results"][0]["body"]["id"].as_str().unwrap().to_string();
+    let res = client
+        .post(format!("{}/books/batch", &base))
+        .bearer_auth(&token)
+        .json(&json!({
+            "operations": [
+                { "op": "update", "id": emma, "body": { "title": "Emma (1815)", "author": "Jane Austen" } },
+                { "op": "delete", "id": emma }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(actions(emma.clone()).await, ["create:admin", "update:admin", "delete:admin"]);
+
+    // 2) Un lote atómico que falla no deja revisiones
+    let body: serde_json::Value = client
+        .post(format!("{}/books/batch", &base))
+        .bearer_auth(&token)
+        .json(&json!({
+            "operations": [
+                { "op": "create", "body": { "title": "Sanditon", "author": "Jane Austen" } },
+                { "op": "delete", "id": "missing-id" }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["committed"], false);
+    let res = client
+        .get(format!("{}/books/missing-id/history", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // 3) Fusión: "merge" en el destino y "delete" en el origen
+    let create = |title: &str| {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(&token)
+            .json(&json!({ "title": title, "author": "Jane Austen" }))
+            .send()
+    };
+    let target: serde_json::Value = create("Mansfield Park").await.unwrap().json().await.unwrap();
+    let source: serde_json::Value = create("Mansfield Park").await.unwrap().json().await.unwrap();
+    let target = target["id"].as_str().unwrap().to_string();
+    let source = source["id"].as_str().unwrap().to_string();
+    let res = client
+        .post(format!("{}/books/{}/merge", &base, target))
+        .bearer_auth(&token)
+        .json(&json!({ "source_id": source }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(actions(target).await, ["create:admin", "merge:admin"]);
+    assert_eq!(actions(source.clone()).await, ["create:admin", "delete:admin"]);
+
+    // 4) Papelera: restaurar y purgar también dejan rastro
+    let res = client
+        .post(format!("{}/books/trash/{}/restore", &base, source))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    client
+        .delete(format!("{}/books/{}", &base, source))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let res = client
+        .delete(format!("{}/books/trash/{}", &base, source))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        actions(source).await,
+        ["create:admin", "delete:admin", "restore:admin", "delete:admin", "purge:admin"]
+    );
+
+    // 5) GraphQL escribe el mismo historial que REST
+    let graphql = |query: String| {
+        client
+            .post(format!("{}/graphql", &base))
+            .bearer_auth(&token)
+            .json(&json!({ "query": query }))
+            .send()
+    };
+    let res: serde_json::Value = graphql(
+        r#"mutation { createBook(input: { title: "Lady Susan", author: "Jane Austen" }) { id } }"#.into(),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let id = res["data"]["createBook"]["id"].as_str().unwrap().to_string();
+    let res: serde_json::Value = graphql(format!(
+        r#"mutation {{ updateBook(id: "{id}", input: {{ publishedYear: 1871 }}) {{ id }} }}"#
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert!(res["errors"].is_null(), "{}", res);
+    let res: serde_json::Value = graphql(format!(r#"mutation {{ deleteBook(id: "{id}") }}"#))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(res["data"]["deleteBook"], true);
+    assert_eq!(actions(id).await, ["create:admin", "update:admin", "delete:admin"]);
+}
+
+#[tokio::test]
+async fn search_filters_by_updated_since_and_creation_date() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    let create = |title: &'static str| {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(&token)
+            .json(&json!({ "title": title, "author": "Virginia Woolf" }))
+            .send()
+    };
+    let orlando: serde_json::Value = create("Orlando").await.unwrap().json().await.unwrap();
+    assert_eq!(orlando["updated_at"], orlando["created_at"]);
+    assert_eq!(orlando["updated_by"], "admin");
+    let waves: serde_json::Value = create("The Waves").await.unwrap().json().await.unwrap();
+
+    // Las fechas se guardan con milisegundos: se deja margen a ambos lados
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let since = chrono::Utc::now().to_rfc3339();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let edited: serde_json::Value = client
+        .put(format!("{}/books/{}", &base, orlando["id"].as_str().unwrap()))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Orlando: A Biography", "author": "Virginia Woolf" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ts = |v: &serde_json::Value| chrono::DateTime::parse_from_rfc3339(v.as_str().unwrap()).unwrap();
+    assert!(ts(&edited["updated_at"]) > ts(&orlando["updated_at"]));
+
+    let changed: Vec<serde_json::Value> = client
+        .get(format!("{}/books/search", &base))
+        .query(&[("author", "Woolf"), ("updated_since", since.as_str())])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0]["title"], "Orlando: A Biography");
+
+    let res = client
+        .get(format!("{}/books/search?updated_since=yesterday", &base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Rango de fechas de alta
+    let created: Vec<serde_json::Value> = client
+        .get(format!("{}/books/search", &base))
+        .query(&[("created_from", "2000-01-01T00:00:00Z"), ("created_to", since.as_str())])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 2);
+    let res = client
+        .get(format!("{}/books/search", &base))
+        .query(&[("created_from", since.as_str()), ("created_to", "2000-01-01T00:00:00Z")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Los borrados desde el mismo instante salen de la papelera
+    let res = client
+        .delete(format!("{}/books/{}", &base, waves["id"].as_str().unwrap()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let deleted: Vec<serde_json::Value> = client
+        .get(format!("{}/books/trash", &base))
+        .bearer_auth(&token)
+        .query(&[("deleted_since", since.as_str())])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["id"], waves["id"]);
+    assert_eq!(deleted[0]["updated_at"], deleted[0]["deleted_at"]);
+    assert_eq!(deleted[0]["updated_by"], "admin");
+    let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+    let deleted: Vec<serde_json::Value> = client
+        .get(format!("{}/books/trash", &base))
+        .bearer_auth(&token)
+        .query(&[("deleted_since", tomorrow.as_str())])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(deleted.is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_backend_serves_the_same_api() {
+    check_storage_backend(spawn_sqlite_app().await).await;
+}
+
+#[tokio::test]
+async fn postgres_backend_serves_the_same_api() {
+    // Solo con un Postgres local, p. ej. TEST_POSTGRES_URL=postgres://postgres@localhost/postgres
+    if let Some(base) = spawn_postgres_app().await {
+        check_storage_backend(base).await;
+    }
+}
+
+/// Recorrido por la API que toca los tres almacenes de un backend con BD
+async fn check_storage_backend(base: String) {
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    // 1) Alta idempotente
+    let post = || {
+        client
+            .post(format!("{}/books", &base))
+            .bearer_auth(&token)
+            .header("idempotency-key", "dune-1")
+            .json(&json!({ "title": "Dune", "author": "Frank Herbert", "isbn": "978-0-441-17271-9" }))
+            .send()
+    };
+    let created = post().await.unwrap();
+    assert_eq!(created.status(), StatusCode::CREATED);
+    let created: serde_json::Value = created.json().await.unwrap();
+    let id = created["id"].as_str().unwrap();
+    let res = client.get(format!("{}/books/{}", &base, id)).send().await.unwrap();
+    let etag = res.headers()["etag"].to_str().unwrap().to_string();
+    let replay = post().await.unwrap();
+    assert_eq!(replay.headers()["idempotent-replayed"], "true");
+
+    // 2) Búsqueda sin distinguir mayúsculas
+    let found: Vec<serde_json::Value> = client
+        .get(format!("{}/books/search?title=dUNE&author=herbert", &base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["created_at"], created["created_at"]);
+
+    // 3) Actualización con If-Match: la segunda llega con un ETag viejo
+    let put = || {
+        client
+            .put(format!("{}/books/{}", &base, id))
+            .bearer_auth(&token)
+            .header("if-match", &etag)
+            .json(&json!({ "title": "Dune Messiah", "author": "Frank Herbert" }))
+            .send()
+    };
+    assert_eq!(put().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(put().await.unwrap().status(), StatusCode::PRECONDITION_FAILED);
+
+    // 4) Historial y vuelta atrás
+    let history: Vec<serde_json::Value> = client
+        .get(format!("{}/books/{}/history", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1]["before"]["title"], "Dune");
+    let reverted: serde_json::Value = client
+        .post(format!("{}/books/{}/history/{}/revert", &base, id, history[0]["id"]))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(reverted["title"], "Dune");
+    assert_eq!(reverted["version"], 3);
+
+    // 5) Papelera
+    let res = client
+        .delete(format!("{}/books/{}", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let trash: Vec<serde_json::Value> = client
+        .get(format!("{}/books/trash", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(trash.len(), 1);
+    assert!(trash[0]["deleted_at"].is_string());
+    let res = client
+        .post(format!("{}/books/trash/{}/restore", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let history: Vec<serde_json::Value> = client
+        .get(format!("{}/books/{}/history", &base, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let actions: Vec<_> = history.iter().map(|r| r["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["create", "update", "revert", "delete", "restore"]);
+
+    // 6) Esquema al día tras las migraciones
+    let res = client
+        .get(format!("{}/admin/migrations", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let status: serde_json::Value = res.json().await.unwrap();
+    assert!(!status["applied"].as_array().unwrap().is_empty());
+    assert_eq!(status["pending"], json!([]));
+    assert_eq!(status["unknown"], json!([]));
+
+    // 7) Listo para recibir tráfico y con estadísticas de la BD real
+    let res = client.get(format!("{}/readyz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let status: serde_json::Value = client
+        .get(format!("{}/status", &base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(status["storage"]["size_bytes"].as_i64().unwrap() > 0);
+    assert!(status["storage"]["pool"]["max"].as_u64().unwrap() >= 1);
+    assert_eq!(status["migrations_current"], true);
+
+    // 8) Gauges del pool en las métricas
+    let body = client.get(format!("{}/metrics", &base)).send().await.unwrap().text().await.unwrap();
+    assert!(body.contains("db_pool_connections{state=\"idle\"}"), "{}", body);
+    assert!(body.contains("db_pool_max_connections "));
+}
+
+#[tokio::test]
+async fn migrations_endpoint_is_admin_only() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/admin/migrations", &base);
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = client.get(&url).bearer_auth(cataloguer_token()).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let status: serde_json::Value = client
+        .get(&url)
+        .bearer_auth(get_token(&base).await)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["backend"], "memory");
+    assert_eq!(status["applied"], json!([]));
+}
+
+#[tokio::test]
+async fn probes_are_public_and_status_is_admin_only() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let res = client.get(format!("{}/healthz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.json::<serde_json::Value>().await.unwrap()["status"], "ok");
+
+    let res = client.get(format!("{}/readyz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let ready: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(ready, json!({ "status": "ready", "database": "ok", "migrations": "current" }));
+
+    let url = format!("{}/status", &base);
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = client.get(&url).bearer_auth(cataloguer_token()).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let status: serde_json::Value = client
+        .get(&url)
+        .bearer_auth(get_token(&base).await)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["version"], env!("CARGO_PKG_VERSION"));
+    assert!(status["uptime_secs"].is_u64());
+    assert_eq!(status["storage"], json!({ "backend": "memory", "size_bytes": null, "pool": null }));
+    let res = client.get(format!("{}/admin/status", &base)).bearer_auth(get_token(&base).await).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn readyz_fails_with_pending_migrations_or_closed_pool() {
+    let pool = SqlitePoolOptions::new().max_connections(1).connect(&DB_URL).await.unwrap();
+    let base = serve_app(build_app(sqlite_backend(pool.clone()), test_config())).await;
+    let client = reqwest::Client::new();
+
+    // Sin migrar: la BD responde pero el esquema no está al día
+    let res = client.get(format!("{}/readyz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let ready: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(ready, json!({ "status": "unavailable", "database": "ok", "migrations": "pending" }));
+
+    Database::Sqlite(pool.clone()).migrate(true).await.unwrap();
+    let res = client.get(format!("{}/readyz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Pool cerrado: el proceso sigue vivo pero no está listo
+    pool.close().await;
+    let res = client.get(format!("{}/readyz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.json::<serde_json::Value>().await.unwrap()["database"], "unreachable");
+    let res = client.get(format!("{}/healthz", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Las métricas siguen saliendo, sin los gauges que necesitan la BD
+    let res = client.get(format!("{}/metrics", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.text().await.unwrap();
+    assert!(body.contains(r#"http_requests_total{method="GET",route="/readyz",status="503"} 2"#), "{}", body);
+    assert!(body.contains("storage_up 0"));
+    assert!(!body.contains("library_books{"));
+    assert!(!body.contains("db_pool_connections{"));
+}
+
+#[tokio::test]
+async fn metrics_count_requests_errors_and_repository_calls() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = get_token(&base).await;
+
+    client
+        .post(format!("{}/books", &base))
+        .bearer_auth(&token)
+        .json(&json!({ "title": "Dune", "author": "Frank Herbert" }))
+        .send()
+        .await
+        .unwrap();
+    let res = client.get(format!("{}/books/missing", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    client.get(format!("{}/no/such/route", &base)).send().await.unwrap();
+    let purge = reqwest::Method::from_bytes(b"PURGE").unwrap();
+    client.request(purge, format!("{}/books", &base)).send().await.unwrap();
+
+    // Un scrape previo no deja llamadas `count` en el histograma del repositorio
+    client.get(format!("{}/metrics", &base)).send().await.unwrap();
+    let res = client.get(format!("{}/metrics", &base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers()["content-type"].to_str().unwrap().starts_with("text/plain; version=0.0.4"));
+    let body = res.text().await.unwrap();
+
+    // La ruta es la plantilla, no la URL
+    assert!(body.contains(r#"http_requests_total{method="POST",route="/books",status="201"} 1"#), "{}", body);
+    assert!(body.contains(r#"http_requests_total{method="GET",route="/books/:id",status="404"} 1"#));
+    // Las URLs sin ruta se agrupan en una sola serie
+    assert!(body.contains(r#"method="GET",route="unmatched""#));
+    // Los métodos de extensión no abren series propias
+    assert!(body.contains(r#"http_requests_total{method="other",route="/books",status="#), "{}", body);
+    assert!(!body.contains("PURGE"));
+    assert!(body.contains(r#"http_request_duration_seconds_bucket{method="GET",route="/books/:id",status="404",le="+Inf"} 1"#));
+    assert!(body.contains(r#"app_errors_total{code="not_found"} 1"#));
+    assert!(body.contains(r#"repository_call_duration_seconds_count{method="create",outcome="ok"} 1"#));
+    assert!(body.contains(r#"library_books{state="active"} 1"#));
+    assert!(body.contains(r#"library_books{state="trashed"} 0"#));
+    assert!(!body.contains(r#"method="count""#));
+    assert!(body.contains("storage_up 1"));
+    // Sin BD no hay pool
+    assert!(!body.contains("db_pool_connections"));
+}
+
+#[tokio::test]
+async fn cors_only_for_configured_origins() {
+    let base = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    // Preflight: se responde sin token y sin llegar a la ruta
+    let res = client
+        .request(reqwest::Method::OPTIONS, format!("{}/books", base))
+        .header("origin", "https://app.example")
+        .header("access-control-request-method", "POST")
+        .header("access-control-request-headers", "authorization, content-type")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(res.headers()["access-control-allow-origin"], "https://app.example");
+    assert!(res.headers()["access-control-allow-methods"].to_str().unwrap().contains("POST"));
+    assert!(res.headers()["access-control-allow-headers"].to_str().unwrap().contains("authorization"));
+
+    // Petición normal desde un origen admitido: expone cabeceras propias
+    let res = client
+        .get(format!("{}/books", base))
+        .header("origin", "https://app.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["access-control-allow-origin"], "https://app.example");
+    assert!(res.headers()["access-control-expose-headers"].to_str().unwrap().contains("etag"));
+
+    // Origen no admitido: sin cabeceras CORS
+    let res = client
+        .get(format!("{}/books", base))
+        .header("origin", "https://evil.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn login_token_expires_after_configured_ttl() {
+    let config = Config { token_ttl: std::time::Duration::from_secs(120), ..test_config() };
+    let base = serve_app(build_app(memory_backend(), config)).await;
+
+    let token = get_token(&base).await;
+    let claims = jsonwebtoken::dangerous_insecure_decode::<library_api::middleware::auth::Claims>(&token)
+        .unwrap()
+        .claims;
+    let ttl = claims.exp as i64 - chrono::Utc::now().timestamp();
+    assert!((110..=120).contains(&ttl), "ttl {}", ttl);
+}
+
+#[tokio::test]
+async fn rate_limits_anonymous_clients_by_ip_and_users_by_subject() {
+    let config = Config {
+        rate_limit_per_minute: 1,
+        rate_limit_burst: 2,
+        rate_limit_login_per_minute: 1,
+        rate_limit_login_burst: 2,
+        ..test_config()
+    };
+    let base = serve_app(build_app(memory_backend(), config)).await;
+    let client = reqwest::Client::new();
+
+    let login = |password: &'static str| {
+        client
+            .post(format!("{}/login", base))
+            .json(&json!({ "username": "admin", "password": password }))
+            .send()
+    };
+    let first = login("password").await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(first.headers()["ratelimit-limit"], "2");
+    assert_eq!(first.headers()["ratelimit-remaining"], "1");
+    let token: String = first.json().await.unwrap();
+    assert_eq!(login("guess").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+    let res = login("guess").await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers()["retry-after"], "60");
+    assert_eq!(res.headers()["ratelimit-remaining"], "0");
+    assert_eq!(res.headers()["ratelimit-reset"], "120");
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "rate_limited");
+
+    // `/login` tiene su cubo: adivinar contraseñas no gasta el de las lecturas
+    for _ in 0..2 {
+        assert_eq!(client.get(format!("{}/books", base)).send().await.unwrap().status(), StatusCode::OK);
+    }
+    let res = client.get(format!("{}/books", base)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Las rutas protegidas cuentan por usuario, no por IP
+    let create = |token: String| {
+        client
+            .post(format!("{}/books", base))
+            .bearer_auth(token)
+            .json(&json!({ "title": "Dune", "author": "Frank Herbert" }))
+            .send()
+    };
+    for _ in 0..2 {
+        assert_eq!(create(token.clone()).await.unwrap().status(), StatusCode::CREATED);
+    }
+    let res = create(token).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(res.headers().contains_key("retry-after"));
+    assert_eq!(create(cataloguer_token()).await.unwrap().status(), StatusCode::CREATED);
+
+    // GraphQL cuenta por usuario si trae token y, si no, por IP
+    let graphql = |token: Option<String>| {
+        let req = client.post(format!("{}/graphql", base)).json(&json!({ "query": "{ books { id } }" }));
+        match token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+        .send()
+    };
+    assert_eq!(graphql(None).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(graphql(Some(cataloguer_token())).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(graphql(Some(cataloguer_token())).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Las sondas no se limitan
+    assert_eq!(client.get(format!("{}/healthz", base)).send().await.unwrap().status(), StatusCode::OK);
+}